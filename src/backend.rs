@@ -0,0 +1,81 @@
+use crate::error::SplitError;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Which tool does the splitting and joining
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Mkvmerge,
+    Ffmpeg,
+}
+
+impl Backend {
+    pub fn parse(value: &str) -> Result<Backend, SplitError> {
+        match value {
+            "mkvmerge" => Ok(Backend::Mkvmerge),
+            "ffmpeg" => Ok(Backend::Ffmpeg),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Unknown backend {}, expected mkvmerge or ffmpeg",
+                value
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Backend::Mkvmerge => "mkvmerge",
+            Backend::Ffmpeg => "ffmpeg",
+        })
+    }
+}
+
+/// A tool that cuts the audio apart and joins the pieces back together, for library
+/// users who split with something other than mkvmerge or ffmpeg.
+///
+/// Set one as [`Config::custom_backend`](crate::Config::custom_backend) and it does the
+/// split and the join in place of `backend`. Everything else, like probing, copying the
+/// whole input when there is nothing to cut, or `--stream`, still goes through
+/// `backend`. [`MkvmergeBackend`](crate::MkvmergeBackend) and
+/// [`FfmpegBackend`](crate::FfmpegBackend) are the built-in tools behind this trait.
+pub trait SplitBackend: fmt::Debug + Send + Sync {
+    /// Cuts `input` at each of `cut_times`, given as `HH:MM:SS.nnnnnnnnn` on the
+    /// delayed timeline, with the audio delayed by `delay` milliseconds. The pieces are
+    /// written to `out_dir`, which is the run's own, and every one of them is returned
+    /// in order: one more than there are cut times, counting the piece before the
+    /// first cut.
+    fn split(
+        &self,
+        input: &Path,
+        cut_times: &[String],
+        delay: isize,
+        out_dir: &Path,
+    ) -> Result<Vec<PathBuf>, SplitError>;
+
+    /// Joins `pieces`, as [`split`](SplitBackend::split) returned them, into `output`
+    /// in the order given
+    fn merge(&self, pieces: &[PathBuf], output: &Path) -> Result<(), SplitError>;
+}
+
+/// How a delay is applied to the audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelayMode {
+    /// Shift the timestamps, which relies on players honouring the container's offsets
+    Sync,
+    /// Put real silence in front of the audio for a positive delay
+    Silence,
+}
+
+impl DelayMode {
+    pub fn parse(value: &str) -> Result<DelayMode, SplitError> {
+        match value {
+            "sync" => Ok(DelayMode::Sync),
+            "silence" => Ok(DelayMode::Silence),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Unknown delay mode {}, expected sync or silence",
+                value
+            ))),
+        }
+    }
+}
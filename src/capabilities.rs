@@ -0,0 +1,134 @@
+use crate::error::SplitError;
+use crate::mkvmerge::MkvmergeVersion;
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// The ffprobe options every probe is built from, with what each is needed for
+const FFPROBE_OPTIONS: [(&str, &str); 4] = [
+    ("-show_entries", "reading durations, codecs and start times"),
+    ("-select_streams", "probing the chosen audio track"),
+    ("-of", "reading ffprobe's output"),
+    ("-show_streams", "listing the audio tracks"),
+];
+
+/// The mkvmerge options every split and join uses, besides the `--split` mode
+const MKVMERGE_OPTIONS: [(&str, &str); 2] = [
+    ("--sync", "applying the delay"),
+    ("--audio-tracks", "picking the audio track"),
+];
+
+/// Checks once per process that ffprobe's `-h` lists every option the probes use,
+/// naming the first one that is missing.
+///
+/// An ffprobe that can't be run passes, since some runs can do without it and the
+/// probes themselves say when it is missing. So does one whose help prints nothing,
+/// as there is then nothing to go on.
+pub fn check_ffprobe(ffprobe: &Path) -> Result<(), SplitError> {
+    static CHECKED: OnceLock<Result<(), String>> = OnceLock::new();
+    CHECKED
+        .get_or_init(|| {
+            let help = match help_text(ffprobe, "-h") {
+                Some(help) => help,
+                None => return Ok(()),
+            };
+            missing_option(&help, &FFPROBE_OPTIONS)
+        })
+        .clone()
+        .map_err(|message| SplitError::ProcessFailed("ffprobe".to_owned(), message))
+}
+
+/// Checks once per process that mkvmerge's `--help` lists the `--split` mode that
+/// `version` is expected to have and every other option a split uses, naming the
+/// first one that is missing. Passes when the help prints nothing, like
+/// [`check_ffprobe`].
+pub fn check_mkvmerge(mkvmerge: &Path, version: MkvmergeVersion) -> Result<(), SplitError> {
+    static CHECKED: OnceLock<Result<(), String>> = OnceLock::new();
+    CHECKED
+        .get_or_init(|| {
+            let help = match help_text(mkvmerge, "--help") {
+                Some(help) => help,
+                None => return Ok(()),
+            };
+            // The help lists the mode like `--split timestamps:A[,B...]`
+            let mode = format!("{}:", version.split_mode());
+            if !help.split_whitespace().any(|word| word.starts_with(&mode)) {
+                return Err(format!(
+                    "this build's --help has no `--split {}`, which is needed for cutting \
+                     at the trims",
+                    mode
+                ));
+            }
+            missing_option(&help, &MKVMERGE_OPTIONS)
+        })
+        .clone()
+        .map_err(|message| SplitError::ProcessFailed("mkvmerge".to_owned(), message))
+}
+
+/// What `program` prints for `help_arg` on stdout and stderr, which ffprobe builds
+/// differ on, or `None` if it can't be run or prints nothing
+fn help_text(program: &Path, help_arg: &str) -> Option<String> {
+    let output = Command::new(program).arg(help_arg).output().ok()?;
+    let help = String::from_utf8_lossy(&output.stdout).into_owned()
+        + &String::from_utf8_lossy(&output.stderr);
+    if help.trim().is_empty() {
+        None
+    } else {
+        Some(help)
+    }
+}
+
+/// Names the first of `options` that `help` doesn't list as a word of its own
+fn missing_option(help: &str, options: &[(&str, &str)]) -> Result<(), String> {
+    let listed = |option: &str| {
+        help.split(|c: char| c.is_whitespace() || c == ',')
+            .any(|word| word == option)
+    };
+    match options.iter().find(|(option, _)| !listed(option)) {
+        Some((option, needed_for)) => Err(format!(
+            "this build's help doesn't list {}, which is needed for {}",
+            option, needed_for
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_program_that_cant_be_run_has_no_help() {
+        assert_eq!(help_text(Path::new("/nonexistent/ffprobe"), "-h"), None);
+        // The probes say so themselves when they are needed
+        assert!(check_ffprobe(Path::new("/nonexistent/ffprobe")).is_ok());
+        assert!(check_mkvmerge(
+            Path::new("/nonexistent/mkvmerge"),
+            MkvmergeVersion(80, 0, 0)
+        )
+        .is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_program_that_prints_nothing_has_no_help() {
+        assert_eq!(help_text(Path::new("true"), "-h"), None);
+    }
+
+    #[test]
+    fn finds_options_listed_as_words_of_their_own() {
+        let help = "-show_entries entry_list\n-select_streams spec\n-of format, -show_streams";
+        assert_eq!(missing_option(help, &FFPROBE_OPTIONS), Ok(()));
+    }
+
+    #[test]
+    fn names_the_first_missing_option() {
+        // `-show_streams_x` isn't `-show_streams`, and neither is a mention inside a word
+        let help = "-show_entries x -select_streams y -of z -show_streams_x no-show_streams";
+        let missing = missing_option(help, &FFPROBE_OPTIONS).unwrap_err();
+        assert!(missing.contains("-show_streams"), "{}", missing);
+        assert!(missing.contains("listing the audio tracks"), "{}", missing);
+        let missing = missing_option("", &MKVMERGE_OPTIONS).unwrap_err();
+        assert!(missing.contains("--sync"), "{}", missing);
+    }
+}
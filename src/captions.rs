@@ -0,0 +1,99 @@
+use crate::error::SplitError;
+use crate::timecode::format_nanos;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// How long each marker stays on screen, unless its segment is shorter
+const MARKER_NANOS: u128 = 2_000_000_000;
+
+/// The subtitle formats `--output-markers` can write, picked by the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptionFormat {
+    Srt,
+    WebVtt,
+}
+
+impl CaptionFormat {
+    pub fn from_path(path: &Path) -> Result<CaptionFormat, SplitError> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("srt") => Ok(CaptionFormat::Srt),
+            Some("vtt") => Ok(CaptionFormat::WebVtt),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Can't tell the marker format of {}, expected a .srt or .vtt file",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Formats a timestamp as `HH:MM:SS,mmm` for SRT or `HH:MM:SS.mmm` for WebVTT
+    fn timestamp(self, nanos: u128) -> String {
+        // format_nanos gives nine digits after the point, and both formats want three
+        let timestamp = format_nanos(nanos);
+        let timestamp = &timestamp[..timestamp.len() - 6];
+        match self {
+            CaptionFormat::Srt => timestamp.replace('.', ","),
+            CaptionFormat::WebVtt => timestamp.to_owned(),
+        }
+    }
+}
+
+/// Writes a subtitle file with a marker at the start of each kept segment, to be shown
+/// over the source video to check where the cuts fall.
+///
+/// `spans` are the kept segments of the source in nanoseconds, in the order they are
+/// joined. Each marker reads `Segment N` followed by the segment's start and end,
+/// where `N` counts from 1 in join order, and is shown for two seconds or the length
+/// of the segment if that is shorter. Markers are written in order of time.
+pub fn write_markers(
+    path: &Path,
+    format: CaptionFormat,
+    spans: &[(u128, Option<u128>)],
+) -> Result<(), SplitError> {
+    let mut markers: Vec<(usize, u128, Option<u128>)> = spans
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, end))| (i + 1, start, end))
+        .collect();
+    markers.sort_by_key(|&(_, start, _)| start);
+
+    let mut captions = String::new();
+    if format == CaptionFormat::WebVtt {
+        writeln!(captions, "WEBVTT").unwrap();
+        writeln!(captions).unwrap();
+    }
+    for (cue, &(number, start, end)) in markers.iter().enumerate() {
+        let shown_until = end.map_or(start + MARKER_NANOS, |end| end.min(start + MARKER_NANOS));
+        if format == CaptionFormat::Srt {
+            writeln!(captions, "{}", cue + 1).unwrap();
+        }
+        writeln!(
+            captions,
+            "{} --> {}",
+            format.timestamp(start),
+            format.timestamp(shown_until)
+        )
+        .unwrap();
+        match end {
+            Some(end) => writeln!(
+                captions,
+                "Segment {}: {} - {}",
+                number,
+                format.timestamp(start),
+                format.timestamp(end)
+            ),
+            None => writeln!(
+                captions,
+                "Segment {}: {} - end",
+                number,
+                format.timestamp(start)
+            ),
+        }
+        .unwrap();
+        writeln!(captions).unwrap();
+    }
+    fs::write(path, captions).map_err(|e| SplitError::Io(path.to_path_buf(), e))
+}
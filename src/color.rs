@@ -0,0 +1,44 @@
+use crate::error::SplitError;
+use std::env;
+use std::io::IsTerminal;
+
+/// When to colour the tool's own output, from `--color`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Colour a stream only when it is a terminal and `NO_COLOR` is unset
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Result<ColorMode, SplitError> {
+        match value {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Unknown color mode {}, expected auto, always or never",
+                value
+            ))),
+        }
+    }
+
+    /// Whether to colour what is written to `stream`, like `io::stdout()`
+    pub fn enabled(self, stream: impl IsTerminal) -> bool {
+        match self {
+            ColorMode::Auto => stream.is_terminal() && env::var_os("NO_COLOR").is_none(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+/// Wraps `text` in the ANSI colour `code` (like `31` for red) when `enabled`
+pub fn paint(enabled: bool, code: &str, text: &str) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_owned()
+    }
+}
@@ -0,0 +1,290 @@
+use crate::backend::Backend;
+use crate::error::SplitError;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAME: &str = "split_aud.toml";
+
+/// Defaults read from a `split_aud.toml`, which command line flags override.
+///
+/// Only a flat subset of TOML is understood: `key = value` lines, where a value is
+/// a quoted string, an integer or a boolean, plus `#` comments.
+///
+/// ```toml
+/// framerate = "24000/1001"
+/// mkvmerge = "/opt/mkvtoolnix/mkvmerge"
+/// ffprobe = "/opt/ffmpeg/ffprobe"
+/// ffmpeg = "/opt/ffmpeg/ffmpeg"
+/// backend = "mkvmerge"
+/// verbose = true
+/// strip_suffix = " DELAY -?\\d+ms"
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileConfig {
+    /// Parsed once the `--framerate-tolerance` to parse it with is known
+    pub framerate: Option<String>,
+    pub mkvmerge: Option<PathBuf>,
+    pub ffprobe: Option<PathBuf>,
+    pub ffmpeg: Option<PathBuf>,
+    pub backend: Option<Backend>,
+    pub verbose: Option<bool>,
+    /// One `--strip-suffix` pattern, which replaces the built-in ones
+    pub strip_suffix: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+/// The config file to read: the one given with `--config`, or else the first one
+/// [`find_config_file`] finds.
+pub fn config_file_path(given: Option<PathBuf>) -> Option<PathBuf> {
+    given.or_else(find_config_file)
+}
+
+/// Looks for a config file in the current directory, then in `$XDG_CONFIG_HOME`
+/// (falling back to `~/.config`).
+pub fn find_config_file() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    search_config_file(Path::new(""), config_home.as_deref())
+}
+
+/// Looks for a config file in `dir`, then in `config_home` and its `split_aud` directory
+fn search_config_file(dir: &Path, config_home: Option<&Path>) -> Option<PathBuf> {
+    let local = dir.join(CONFIG_FILE_NAME);
+    if local.is_file() {
+        return Some(local);
+    }
+    let config_home = config_home?;
+    [
+        config_home.join(CONFIG_FILE_NAME),
+        config_home.join("split_aud").join(CONFIG_FILE_NAME),
+    ]
+    .iter()
+    .find(|path| path.is_file())
+    .cloned()
+}
+
+pub fn load_config_file(path: &Path) -> Result<FileConfig, SplitError> {
+    let contents = fs::read_to_string(path).map_err(|e| SplitError::Io(path.to_path_buf(), e))?;
+    let invalid = |line: usize, message: String| {
+        SplitError::InvalidArgument(format!("{}:{}: {}", path.display(), line, message))
+    };
+
+    let mut config = FileConfig::default();
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = match line.find('=') {
+            Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+            None => {
+                return Err(invalid(
+                    line_number,
+                    format!("expected `key = value`, found `{}`", line),
+                ))
+            }
+        };
+        let value = parse_value(value).map_err(|message| invalid(line_number, message))?;
+        match (key, value) {
+            ("framerate", Value::String(framerate)) => {
+                config.framerate = Some(framerate);
+            }
+            ("mkvmerge", Value::String(path)) => config.mkvmerge = Some(PathBuf::from(path)),
+            ("ffprobe", Value::String(path)) => config.ffprobe = Some(PathBuf::from(path)),
+            ("ffmpeg", Value::String(path)) => config.ffmpeg = Some(PathBuf::from(path)),
+            ("backend", Value::String(backend)) => config.backend = Some(Backend::parse(&backend)?),
+            ("verbose", Value::Boolean(verbose)) => config.verbose = Some(verbose),
+            ("strip_suffix", Value::String(suffix)) => config.strip_suffix = Some(suffix),
+            ("framerate", _)
+            | ("mkvmerge", _)
+            | ("ffprobe", _)
+            | ("ffmpeg", _)
+            | ("backend", _)
+            | ("strip_suffix", _) => {
+                return Err(invalid(line_number, format!("`{}` must be a string", key)));
+            }
+            ("verbose", _) => {
+                return Err(invalid(
+                    line_number,
+                    format!("`{}` must be true or false", key),
+                ));
+            }
+            _ => return Err(invalid(line_number, format!("unknown key `{}`", key))),
+        }
+    }
+    Ok(config)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = None;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (c, in_string) {
+            ('"', None) | ('\'', None) => in_string = Some(c),
+            // An escaped quote doesn't end a basic string
+            ('\\', Some('"')) => {
+                chars.next();
+            }
+            (c, Some(quote)) if c == quote => in_string = None,
+            ('#', None) => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return Ok(Value::String(value[1..value.len() - 1].to_owned()));
+    }
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut unescaped = String::new();
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('"') => unescaped.push('"'),
+                Some('\\') => unescaped.push('\\'),
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                other => return Err(format!("unsupported escape `\\{}`", other.unwrap_or(' '))),
+            }
+        }
+        return Ok(Value::String(unescaped));
+    }
+    match value {
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => (),
+    }
+    value
+        .replace('_', "")
+        .parse::<i64>()
+        .map(Value::Integer)
+        .map_err(|_| format!("unsupported value `{}`", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    /// An empty directory of the test's own under the system temp directory
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("split_aud-config-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn load(name: &str, contents: &str) -> Result<FileConfig, SplitError> {
+        let dir = test_dir(name);
+        let path = dir.join(CONFIG_FILE_NAME);
+        fs::write(&path, contents).unwrap();
+        let config = load_config_file(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        config
+    }
+
+    fn error_message(result: Result<FileConfig, SplitError>) -> String {
+        match result {
+            Err(SplitError::InvalidArgument(message)) => message,
+            other => panic!("expected an invalid argument, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_quoted_strings_with_escapes() {
+        assert_eq!(
+            parse_value(r#""a \"b\" \\ c\td""#),
+            Ok(Value::String("a \"b\" \\ c\td".to_owned()))
+        );
+        // Literal strings keep their backslashes
+        assert_eq!(
+            parse_value(r"' DELAY -?\d+ms'"),
+            Ok(Value::String(r" DELAY -?\d+ms".to_owned()))
+        );
+        assert!(parse_value(r#""\q""#).is_err());
+        assert_eq!(parse_value("1_000"), Ok(Value::Integer(1000)));
+        assert_eq!(parse_value("true"), Ok(Value::Boolean(true)));
+        assert!(parse_value("yes").is_err());
+    }
+
+    #[test]
+    fn a_hash_inside_a_string_is_not_a_comment() {
+        let config = load(
+            "hash",
+            "mkvmerge = \"/opt/#1/mkvmerge\" # the newer one\n\
+             ffprobe = \"/opt/\\\"q\\\"#/ffprobe\"\n\
+             ffmpeg = '/opt/#2/ffmpeg'\n# verbose = true\n",
+        )
+        .unwrap();
+        assert_eq!(config.mkvmerge, Some(PathBuf::from("/opt/#1/mkvmerge")));
+        assert_eq!(config.ffprobe, Some(PathBuf::from("/opt/\"q\"#/ffprobe")));
+        assert_eq!(config.ffmpeg, Some(PathBuf::from("/opt/#2/ffmpeg")));
+        assert_eq!(config.verbose, None);
+    }
+
+    #[test]
+    fn an_unknown_key_is_an_error() {
+        let message = error_message(load("unknown", "verbose = true\nframrate = \"25\"\n"));
+        assert!(
+            message.ends_with(":2: unknown key `framrate`"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn values_must_have_the_right_type() {
+        let message = error_message(load("type-string", "framerate = 25\n"));
+        assert!(
+            message.contains("`framerate` must be a string"),
+            "{}",
+            message
+        );
+        let message = error_message(load("type-bool", "verbose = \"yes\"\n"));
+        assert!(
+            message.contains("`verbose` must be true or false"),
+            "{}",
+            message
+        );
+        let message = error_message(load("no-value", "verbose\n"));
+        assert!(message.contains("expected `key = value`"), "{}", message);
+    }
+
+    #[test]
+    fn a_given_config_file_overrides_the_search() {
+        let dir = test_dir("search");
+        let home = dir.join("home");
+        fs::create_dir_all(home.join("split_aud")).unwrap();
+        let nested = home.join("split_aud").join(CONFIG_FILE_NAME);
+        fs::write(&nested, "").unwrap();
+        assert_eq!(search_config_file(&dir, Some(&home)), Some(nested));
+        fs::write(home.join(CONFIG_FILE_NAME), "").unwrap();
+        assert_eq!(
+            search_config_file(&dir, Some(&home)),
+            Some(home.join(CONFIG_FILE_NAME))
+        );
+        fs::write(dir.join(CONFIG_FILE_NAME), "").unwrap();
+        assert_eq!(
+            search_config_file(&dir, Some(&home)),
+            Some(dir.join(CONFIG_FILE_NAME))
+        );
+        let given = dir.join("given.toml");
+        assert_eq!(config_file_path(Some(given.clone())), Some(given));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
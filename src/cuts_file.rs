@@ -0,0 +1,306 @@
+use crate::error::SplitError;
+use crate::framerate::Rational;
+use crate::timecode::{parse_seconds, parse_smpte_timecode, parse_timecode};
+use crate::trims::Trim;
+use std::convert::TryFrom;
+
+/// How the numbers in a cuts file are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutsUnit {
+    /// Frame numbers, with an inclusive end like `Trim(start, end)`
+    Frames,
+    /// Seconds, like `12.5`
+    Seconds,
+    /// Timecodes, like `00:00:12.500`, or SMPTE `00:00:12:12` with a frame field
+    Timecode,
+}
+
+impl CutsUnit {
+    pub fn parse(value: &str) -> Result<CutsUnit, SplitError> {
+        match value {
+            "frames" => Ok(CutsUnit::Frames),
+            "seconds" => Ok(CutsUnit::Seconds),
+            "timecode" => Ok(CutsUnit::Timecode),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Invalid cuts unit: {}",
+                value
+            ))),
+        }
+    }
+
+    /// The rate that parsed cuts are counted in.
+    ///
+    /// Seconds and timecodes are read as nanoseconds, so they are used as given
+    /// instead of being rounded to a frame.
+    pub fn timebase(self, framerate: Rational) -> Rational {
+        match self {
+            CutsUnit::Frames => framerate,
+            CutsUnit::Seconds | CutsUnit::Timecode => Rational {
+                num: 1_000_000_000,
+                den: 1,
+            },
+        }
+    }
+}
+
+/// What the second value on each line of a cuts file is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CutsFileMode {
+    /// Where the range ends
+    Range,
+    /// How long the range is, counted in the same unit as the start
+    StartLength,
+}
+
+impl CutsFileMode {
+    pub fn parse(value: &str) -> Result<CutsFileMode, SplitError> {
+        match value {
+            "range" => Ok(CutsFileMode::Range),
+            "start-length" => Ok(CutsFileMode::StartLength),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Unknown cuts file mode {}, expected range or start-length",
+                value
+            ))),
+        }
+    }
+}
+
+/// Reads the ranges to keep from a cuts file, one `start end` pair per line, or one
+/// `start length` pair with `CutsFileMode::StartLength`.
+///
+/// The two values may be separated by whitespace or a comma. Blank lines and
+/// lines starting with `#` are skipped. `framerate` is what the frame field of an
+/// SMPTE timecode counts in.
+pub fn parse_cuts_file(
+    contents: &str,
+    unit: CutsUnit,
+    mode: CutsFileMode,
+    framerate: Rational,
+) -> Result<Vec<Trim>, SplitError> {
+    let mut trims = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let text = line.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: String| SplitError::InvalidTrim {
+            line: i + 1,
+            text: text.to_owned(),
+            message,
+        };
+
+        let values: Vec<&str> = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .collect();
+        if values.len() != 2 {
+            return Err(invalid(format!(
+                "expected a start and {}, found {} values",
+                match mode {
+                    CutsFileMode::Range => "an end",
+                    CutsFileMode::StartLength => "a length",
+                },
+                values.len()
+            )));
+        }
+        let (start, end) = match mode {
+            CutsFileMode::Range => parse_cut(values[0], values[1], unit, framerate),
+            CutsFileMode::StartLength => parse_start_length(values[0], values[1], unit, framerate),
+        }
+        .map_err(&invalid)?;
+        trims.push(Trim {
+            start,
+            end,
+            line: i + 1,
+            text: text.to_owned(),
+            to_end: false,
+            start_from_end: false,
+            end_from_end: false,
+        });
+    }
+    Ok(trims)
+}
+
+/// Reads one `start end` pair counted in `unit`, checking that it isn't backwards.
+///
+/// The range comes back ending where it stops, so a frame end, which is the last frame
+/// kept, is moved one past it.
+pub fn parse_cut(
+    start: &str,
+    end: &str,
+    unit: CutsUnit,
+    framerate: Rational,
+) -> Result<(usize, usize), String> {
+    let (start_value, end_value) = (
+        parse_value(start, unit, framerate)?,
+        parse_value(end, unit, framerate)?,
+    );
+    if start_value > end_value {
+        return Err(format!("start {} is after end {}", start, end));
+    }
+    match unit {
+        CutsUnit::Frames => Ok((start_value, end_value + 1)),
+        CutsUnit::Seconds | CutsUnit::Timecode => Ok((start_value, end_value)),
+    }
+}
+
+/// Reads one `start length` pair counted in `unit` as the range it covers, which ends
+/// `length` after the start whatever the unit. 100 frames from frame 0 keep frames 0
+/// to 99, the same as `--trim-end-is-count` reads a script's trims.
+fn parse_start_length(
+    start: &str,
+    length: &str,
+    unit: CutsUnit,
+    framerate: Rational,
+) -> Result<(usize, usize), String> {
+    let (start_value, length_value) = (
+        parse_value(start, unit, framerate)?,
+        parse_value(length, unit, framerate)?,
+    );
+    if length_value == 0 {
+        return Err(format!("length {} keeps nothing", length));
+    }
+    start_value
+        .checked_add(length_value)
+        .map(|end| (start_value, end))
+        .ok_or_else(|| format!("length {} runs past the largest position", length))
+}
+
+fn parse_value(value: &str, unit: CutsUnit, framerate: Rational) -> Result<usize, String> {
+    match unit {
+        // Always decimal, so a zero-padded 0100 is frame 100
+        CutsUnit::Frames => value
+            .replace('_', "")
+            .parse::<usize>()
+            .map_err(|_| format!("invalid frame number {}", value)),
+        CutsUnit::Seconds => parse_seconds(value)
+            .and_then(|nanos| usize::try_from(nanos).ok())
+            .ok_or_else(|| format!("invalid number of seconds {}", value)),
+        // `HH:MM:SS:FF` ends in a frame count, `HH:MM:SS.fff` in a fraction of a second
+        CutsUnit::Timecode => parse_timecode(value)
+            .or_else(|| parse_smpte_timecode(value, framerate))
+            .and_then(|nanos| usize::try_from(nanos).ok())
+            .ok_or_else(|| format!("invalid timecode {}", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timecode::frame_to_timecode;
+
+    const FILM: Rational = Rational {
+        num: 24000,
+        den: 1001,
+    };
+
+    fn ranges(contents: &str, unit: CutsUnit, mode: CutsFileMode) -> Vec<(usize, usize)> {
+        parse_cuts_file(contents, unit, mode, FILM)
+            .unwrap()
+            .into_iter()
+            .map(|trim| (trim.start, trim.end))
+            .collect()
+    }
+
+    #[test]
+    fn reads_frame_ranges_skipping_comments_and_blank_lines() {
+        let contents = "# intro\n0 100\n\n200,300\n  400 ,  0_500  \n";
+        assert_eq!(
+            ranges(contents, CutsUnit::Frames, CutsFileMode::Range),
+            vec![(0, 101), (200, 301), (400, 501)]
+        );
+        let trims = parse_cuts_file(contents, CutsUnit::Frames, CutsFileMode::Range, FILM).unwrap();
+        assert_eq!(trims[1].line, 4);
+    }
+
+    #[test]
+    fn reads_seconds_and_timecodes_as_nanoseconds() {
+        assert_eq!(
+            ranges("1.5 2", CutsUnit::Seconds, CutsFileMode::Range),
+            vec![(1_500_000_000, 2_000_000_000)]
+        );
+        assert_eq!(
+            ranges("00:00:01.5 01:00", CutsUnit::Timecode, CutsFileMode::Range),
+            vec![(1_500_000_000, 60_000_000_000)]
+        );
+        // An SMPTE frame field counts at the nominal rate
+        assert_eq!(
+            ranges(
+                "00:00:01:00 00:00:02:12",
+                CutsUnit::Timecode,
+                CutsFileMode::Range
+            ),
+            vec![(1_001_000_000, 2_502_500_000)]
+        );
+    }
+
+    #[test]
+    fn reads_start_and_length_pairs() {
+        assert_eq!(
+            ranges("100 50", CutsUnit::Frames, CutsFileMode::StartLength),
+            vec![(100, 150)]
+        );
+        assert_eq!(
+            ranges("1 0.5", CutsUnit::Seconds, CutsFileMode::StartLength),
+            vec![(1_000_000_000, 1_500_000_000)]
+        );
+        assert!(
+            parse_cuts_file("100 0", CutsUnit::Frames, CutsFileMode::StartLength, FILM).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for contents in &["100", "100 200 300", "200 100", "abc 100", "1.0000000001 2"] {
+            let unit = if contents.contains('.') {
+                CutsUnit::Seconds
+            } else {
+                CutsUnit::Frames
+            };
+            assert!(
+                matches!(
+                    parse_cuts_file(contents, unit, CutsFileMode::Range, FILM),
+                    Err(SplitError::InvalidTrim { line: 1, .. })
+                ),
+                "{}",
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn ranges_and_lengths_give_the_same_timecodes() {
+        let timecodes = |contents: &str, unit: CutsUnit, mode: CutsFileMode| {
+            let timebase = unit.timebase(FILM);
+            ranges(contents, unit, mode)
+                .into_iter()
+                .map(|(start, end)| {
+                    (
+                        frame_to_timecode(start, timebase),
+                        frame_to_timecode(end, timebase),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            timecodes("100 149\n200 200", CutsUnit::Frames, CutsFileMode::Range),
+            timecodes("100 50\n200 1", CutsUnit::Frames, CutsFileMode::StartLength)
+        );
+        assert_eq!(
+            timecodes("1 1.5", CutsUnit::Seconds, CutsFileMode::Range),
+            timecodes("1 0.5", CutsUnit::Seconds, CutsFileMode::StartLength)
+        );
+        assert_eq!(
+            timecodes(
+                "00:00:01.000 00:00:01.500",
+                CutsUnit::Timecode,
+                CutsFileMode::Range
+            ),
+            timecodes(
+                "00:00:01.000 00:00:00.500",
+                CutsUnit::Timecode,
+                CutsFileMode::StartLength
+            )
+        );
+    }
+}
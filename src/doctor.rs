@@ -0,0 +1,132 @@
+use crate::backend::Backend;
+use crate::capabilities::{check_ffprobe, check_mkvmerge};
+use crate::color::paint;
+use crate::error::{spawn_error, SplitError};
+use crate::mkvmerge::mkvmerge_version;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+
+/// How one check of the `doctor` subcommand went
+enum Outcome {
+    Pass(String),
+    /// Something that only matters for some runs, like ffmpeg with the mkvmerge backend
+    Warn(String),
+    Fail(String),
+}
+
+/// Checks that the tools a split needs can be run, and that the working directory
+/// can be written to, printing a line for each check.
+///
+/// Fails if any check failed, so that scripts can tell from the exit code.
+pub fn run_doctor(
+    mkvmerge: &Path,
+    ffprobe: &Path,
+    ffmpeg: &Path,
+    backend: Backend,
+    color: bool,
+) -> Result<(), SplitError> {
+    let checks = vec![
+        (
+            "mkvmerge",
+            match mkvmerge_version(mkvmerge)
+                .and_then(|version| check_mkvmerge(mkvmerge, version).map(|_| version))
+            {
+                Ok(version) => Outcome::Pass(format!("{} ({})", version, mkvmerge.display())),
+                Err(e) if backend == Backend::Mkvmerge => Outcome::Fail(e.to_string()),
+                Err(e) => Outcome::Warn(format!("only needed with --backend mkvmerge: {}", e)),
+            },
+        ),
+        (
+            "ffprobe",
+            match tool_version(ffprobe, "ffprobe")
+                .and_then(|version| check_ffprobe(ffprobe).map(|_| version))
+            {
+                Ok(version) => Outcome::Pass(format!("{} ({})", version, ffprobe.display())),
+                Err(e) => Outcome::Fail(e.to_string()),
+            },
+        ),
+        (
+            "ffmpeg",
+            match tool_version(ffmpeg, "ffmpeg") {
+                Ok(version) => Outcome::Pass(format!("{} ({})", version, ffmpeg.display())),
+                Err(e) if backend == Backend::Ffmpeg => Outcome::Fail(e.to_string()),
+                Err(e) => Outcome::Warn(format!(
+                    "only needed with --backend ffmpeg, --delay-mode silence and --preview: {}",
+                    e
+                )),
+            },
+        ),
+        ("directory", check_writable()),
+    ];
+
+    let paint = |code: &str, text: &str| paint(color, code, text);
+    let mut failed = 0;
+    for (name, outcome) in &checks {
+        let (status, message) = match *outcome {
+            Outcome::Pass(ref message) => (paint("32", " ok "), message),
+            Outcome::Warn(ref message) => (paint("33", "warn"), message),
+            Outcome::Fail(ref message) => {
+                failed += 1;
+                (paint("31", "FAIL"), message)
+            }
+        };
+        println!("[{}] {:<9} {}", status, name, message);
+    }
+
+    if failed > 0 {
+        return Err(SplitError::CheckFailed(format!(
+            "{} of {} checks failed",
+            failed,
+            checks.len()
+        )));
+    }
+    println!(
+        "{}",
+        paint("32", "Everything needed for a split is in place")
+    );
+    Ok(())
+}
+
+/// Runs `program -version` and returns the first line it prints, like
+/// `ffprobe version 6.1.1 Copyright (c) 2007-2023 the FFmpeg developers`
+fn tool_version(program: &Path, name: &str) -> Result<String, SplitError> {
+    let output = Command::new(program)
+        .arg("-version")
+        .output()
+        .map_err(|e| spawn_error(name, e))?;
+    if !output.status.success() {
+        return Err(SplitError::ProcessFailed(
+            name.to_owned(),
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next().unwrap_or_default();
+    // Drop the copyright, which is the same for every build
+    let version = line.split(" Copyright").next().unwrap_or(line).trim();
+    if version.is_empty() {
+        return Err(SplitError::ProcessFailed(
+            name.to_owned(),
+            "printed no version".to_owned(),
+        ));
+    }
+    Ok(version.to_owned())
+}
+
+/// Checks that pieces and outputs can be written to the working directory
+fn check_writable() -> Outcome {
+    let dir = match env::current_dir() {
+        Ok(dir) => dir,
+        Err(e) => return Outcome::Fail(format!("could not find the working directory: {}", e)),
+    };
+    let probe = dir.join(format!(".split_aud-doctor-{}", process::id()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Outcome::Pass(format!("{} is writable", dir.display()))
+        }
+        Err(e) => Outcome::Fail(format!("cannot write to {}: {}", dir.display(), e)),
+    }
+}
@@ -0,0 +1,45 @@
+use crate::error::SplitError;
+use crate::framerate::Rational;
+use crate::timecode::frame_to_smpte;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Writes the kept ranges as a CMX3600 EDL, for `--output-edl`.
+///
+/// Each `(start, end)` range of frames becomes one audio-only cut event on the `AX`
+/// reel, named after `source` in a `FROM CLIP NAME` comment. Record timecodes start
+/// at `00:00:00:00` and follow on from each other in the order given, which is the
+/// order the output joins them. Timecodes are non-drop-frame, as `FCM:` says.
+pub fn write_edl(
+    path: &Path,
+    title: &str,
+    source: &Path,
+    ranges: &[(usize, usize)],
+    framerate: Rational,
+) -> Result<(), SplitError> {
+    let clip_name = source
+        .file_name()
+        .map_or_else(|| source.to_string_lossy(), |name| name.to_string_lossy());
+    let mut edl = String::new();
+    writeln!(edl, "TITLE: {}", title).unwrap();
+    writeln!(edl, "FCM: NON-DROP FRAME").unwrap();
+    let mut record = 0;
+    for (i, &(start, end)) in ranges.iter().enumerate() {
+        let record_end = record + (end - start);
+        writeln!(edl).unwrap();
+        writeln!(
+            edl,
+            "{:03}  AX       A     C        {} {} {} {}",
+            i + 1,
+            frame_to_smpte(start, framerate),
+            frame_to_smpte(end, framerate),
+            frame_to_smpte(record, framerate),
+            frame_to_smpte(record_end, framerate)
+        )
+        .unwrap();
+        writeln!(edl, "* FROM CLIP NAME: {}", clip_name).unwrap();
+        record = record_end;
+    }
+    fs::write(path, edl).map_err(|e| SplitError::Io(path.to_path_buf(), e))
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can go wrong while splitting.
+///
+/// Each variant maps to a stable process exit code so that scripts can
+/// branch on the kind of failure:
+///
+/// | Code | Meaning                                   |
+/// |------|-------------------------------------------|
+/// | 1    | I/O error reading or writing a file       |
+/// | 2    | No trims found, or the script is empty    |
+/// | 3    | A required subprocess could not be found  |
+/// | 4    | A subprocess failed                       |
+/// | 5    | Invalid arguments                         |
+/// | 6    | A trim in the script is invalid           |
+/// | 7    | A sanity check or `--verify` failed, or a |
+/// |      | warning with `--warn-as-error`            |
+/// | 8    | The script does not declare a framerate   |
+/// | 9    | Cancelled at the confirmation prompt      |
+#[derive(Debug)]
+pub enum SplitError {
+    Io(PathBuf, io::Error),
+    Script(PathBuf, io::Error),
+    NoTrims(String),
+    EmptyScript,
+    /// The script has nothing but comments and blank lines
+    NoStatements,
+    ProcessNotFound(String),
+    ProcessFailed(String, String),
+    InvalidArgument(String),
+    InvalidTrim {
+        line: usize,
+        text: String,
+        message: String,
+    },
+    CheckFailed(String),
+    NoFramerate,
+    Cancelled,
+}
+
+impl SplitError {
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            SplitError::Io(..) | SplitError::Script(..) => 1,
+            SplitError::NoTrims(_) | SplitError::EmptyScript | SplitError::NoStatements => 2,
+            SplitError::ProcessNotFound(_) => 3,
+            SplitError::ProcessFailed(..) => 4,
+            SplitError::InvalidArgument(_) => 5,
+            SplitError::InvalidTrim { .. } => 6,
+            SplitError::CheckFailed(_) => 7,
+            SplitError::NoFramerate => 8,
+            SplitError::Cancelled => 9,
+        }
+    }
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SplitError::Io(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+            SplitError::Script(ref path, ref e) => match e.kind() {
+                io::ErrorKind::NotFound => write!(f, "avs script not found: {}", path.display()),
+                io::ErrorKind::PermissionDenied => {
+                    write!(
+                        f,
+                        "permission denied reading avs script: {}",
+                        path.display()
+                    )
+                }
+                _ => write!(f, "could not read avs script {}: {}", path.display(), e),
+            },
+            SplitError::NoTrims(ref checked) => write!(f, "No trims found (checked {})", checked),
+            SplitError::EmptyScript => write!(f, "avs script is empty"),
+            SplitError::NoStatements => write!(
+                f,
+                "script contains no executable statements (only comments/whitespace)"
+            ),
+            SplitError::ProcessNotFound(ref program) => {
+                write!(
+                    f,
+                    "{} not found, is it installed and on your PATH?",
+                    program
+                )
+            }
+            SplitError::ProcessFailed(ref program, ref message) => {
+                write!(f, "{} failed: {}", program, message)
+            }
+            SplitError::InvalidArgument(ref message) => write!(f, "{}", message),
+            SplitError::InvalidTrim {
+                line,
+                ref text,
+                ref message,
+            } => write!(f, "Invalid trim `{}` on line {}: {}", text, line, message),
+            SplitError::CheckFailed(ref message) => write!(f, "{}", message),
+            SplitError::NoFramerate => write!(f, "No framerate declared in script"),
+            SplitError::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+/// Convert a failure to spawn `program` into the matching error.
+pub fn spawn_error(program: &str, e: io::Error) -> SplitError {
+    if e.kind() == io::ErrorKind::NotFound {
+        SplitError::ProcessNotFound(program.to_owned())
+    } else {
+        SplitError::ProcessFailed(program.to_owned(), e.to_string())
+    }
+}
+
+/// Prints a warning, or with `--warn-as-error` fails with it instead
+pub fn warn(as_error: bool, message: String) -> Result<(), SplitError> {
+    if as_error {
+        return Err(SplitError::CheckFailed(format!(
+            "{} (failing because of --warn-as-error)",
+            message
+        )));
+    }
+    eprintln!("Warning: {}", message);
+    Ok(())
+}
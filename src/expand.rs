@@ -0,0 +1,111 @@
+use crate::error::SplitError;
+use std::env;
+use std::path::PathBuf;
+
+/// Expands a leading `~` and any `$VAR` or `${VAR}` in a path argument.
+///
+/// `~` is only expanded on its own or followed by a `/`. A `$` that isn't followed
+/// by a variable name is left alone, and an unset variable is an error rather than
+/// silently turning into an empty string.
+pub fn expand_path(path: &str) -> Result<PathBuf, SplitError> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") {
+        let home = env::var("HOME").map_err(|_| {
+            SplitError::InvalidArgument(format!("Cannot expand ~ in {}: HOME is not set", path))
+        })?;
+        expanded.push_str(&home);
+        rest = &rest[1..];
+    }
+
+    while let Some(pos) = rest.find('$') {
+        expanded.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => {
+                    return Err(SplitError::InvalidArgument(format!(
+                        "Unclosed ${{ in {}",
+                        path
+                    )))
+                }
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let value = env::var(name).map_err(|_| {
+            SplitError::InvalidArgument(format!(
+                "Environment variable {} in {} is not set",
+                name, path
+            ))
+        })?;
+        expanded.push_str(&value);
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+    Ok(PathBuf::from(expanded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn expand(path: &str) -> PathBuf {
+        expand_path(path).unwrap()
+    }
+
+    #[test]
+    fn expands_a_leading_tilde_to_home() {
+        let home = env::var("HOME").unwrap();
+        assert_eq!(expand("~"), Path::new(&home));
+        assert_eq!(expand("~/x"), Path::new(&home).join("x"));
+        // Another user's home isn't looked up, and a tilde inside a path is a name
+        assert_eq!(expand("~user/x"), Path::new("~user/x"));
+        assert_eq!(expand("a/~/x"), Path::new("a/~/x"));
+    }
+
+    #[test]
+    fn expands_variables_with_and_without_braces() {
+        env::set_var("SPLIT_AUD_EXPAND_TEST", "/data");
+        assert_eq!(
+            expand("$SPLIT_AUD_EXPAND_TEST/a.mka"),
+            Path::new("/data/a.mka")
+        );
+        assert_eq!(
+            expand("${SPLIT_AUD_EXPAND_TEST}_old/a.mka"),
+            Path::new("/data_old/a.mka")
+        );
+    }
+
+    #[test]
+    fn a_bare_dollar_is_kept() {
+        assert_eq!(expand("cost$"), Path::new("cost$"));
+        assert_eq!(expand("a$-b/$.mka"), Path::new("a$-b/$.mka"));
+    }
+
+    #[test]
+    fn an_unset_variable_is_an_error() {
+        env::remove_var("SPLIT_AUD_EXPAND_UNSET");
+        let error = expand_path("$SPLIT_AUD_EXPAND_UNSET/a.mka").unwrap_err();
+        assert!(matches!(error, SplitError::InvalidArgument(ref message)
+            if message.contains("SPLIT_AUD_EXPAND_UNSET")));
+    }
+
+    #[test]
+    fn an_unclosed_brace_is_an_error() {
+        assert!(matches!(
+            expand_path("${HOME/a.mka"),
+            Err(SplitError::InvalidArgument(_))
+        ));
+    }
+}
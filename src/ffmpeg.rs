@@ -0,0 +1,597 @@
+use crate::error::{spawn_error, SplitError};
+use crate::report::{Report, ReportCommand};
+use crate::timecode::format_nanos;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+pub fn run_ffmpeg(
+    ffmpeg: &Path,
+    args: Vec<String>,
+    report: &mut Report,
+) -> Result<Output, SplitError> {
+    let command_line = command_line(ffmpeg, args);
+    let result = Command::new(ffmpeg).args(&command_line[1..]).output();
+    report.commands.push(ReportCommand {
+        args: command_line,
+        status: result.as_ref().ok().and_then(|output| output.status.code()),
+    });
+
+    let output = result.map_err(|e| spawn_error("ffmpeg", e))?;
+    if !output.status.success() {
+        return Err(SplitError::ProcessFailed(
+            "ffmpeg".to_owned(),
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    Ok(output)
+}
+
+/// The whole command `run_ffmpeg` runs for `args`, starting with `ffmpeg` itself
+pub fn command_line(ffmpeg: &Path, args: Vec<String>) -> Vec<String> {
+    let mut command_line = vec![
+        ffmpeg.to_string_lossy().into_owned(),
+        "-hide_banner".to_owned(),
+        "-loglevel".to_owned(),
+        "error".to_owned(),
+        "-y".to_owned(),
+    ];
+    command_line.extend(args);
+    command_line
+}
+
+/// How an output is written
+#[derive(Debug, Clone, Copy)]
+pub struct OutputFormat {
+    /// Let the output file's extension pick the container, rather than Matroska
+    pub follow_extension: bool,
+    /// Leave out what changes from run to run, like the segment UID and muxing date
+    pub bitexact: bool,
+}
+
+/// Arguments that pick the output format: Matroska unless the container
+/// should follow the output file's extension
+fn format_args(format: OutputFormat) -> Vec<String> {
+    let mut args = Vec::new();
+    if !format.follow_extension {
+        args.extend(vec!["-f".to_owned(), "matroska".to_owned()]);
+    }
+    if format.bitexact {
+        args.extend(vec!["-fflags".to_owned(), "+bitexact".to_owned()]);
+    }
+    args
+}
+
+/// Copies the `streams` (an ffmpeg `-map`) between the `start` and `end` of `span`
+/// (in nanoseconds of the input) to `output`. Without an `end` it runs to the end of
+/// the input.
+pub fn extract_segment(
+    ffmpeg: &Path,
+    input: &Path,
+    span: (u128, Option<u128>),
+    streams: &str,
+    output: &Path,
+    format: OutputFormat,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    run_ffmpeg(
+        ffmpeg,
+        extract_segment_args(input, span, streams, output, format),
+        report,
+    )?;
+    Ok(())
+}
+
+/// The arguments `extract_segment` runs ffmpeg with
+pub fn extract_segment_args(
+    input: &Path,
+    (start, end): (u128, Option<u128>),
+    streams: &str,
+    output: &Path,
+    format: OutputFormat,
+) -> Vec<String> {
+    let mut args = vec!["-ss".to_owned(), format_nanos(start)];
+    if let Some(end) = end {
+        args.push("-to".to_owned());
+        args.push(format_nanos(end));
+    }
+    args.extend(vec![
+        "-i".to_owned(),
+        input.to_string_lossy().into_owned(),
+        "-map".to_owned(),
+        streams.to_owned(),
+        "-c".to_owned(),
+        "copy".to_owned(),
+    ]);
+    args.extend(format_args(format));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// Joins `pieces` into `output` with ffmpeg's concat demuxer, which reads the list of
+/// pieces from `list`.
+pub fn concat(
+    ffmpeg: &Path,
+    pieces: &[PathBuf],
+    list: &Path,
+    output: &Path,
+    format: OutputFormat,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let contents: String = pieces
+        .iter()
+        .map(|piece| format!("file '{}'\n", list_entry(piece, list)))
+        .collect();
+    run_concat(ffmpeg, &contents, list, None, output, format, report)
+}
+
+/// The concat `list` that has the demuxer seek to each of the `spans` (in nanoseconds
+/// of the input) of `input` in turn, for [`concat_spans`]
+pub fn spans_list(input: &Path, spans: &[(u128, Option<u128>)], list: &Path) -> String {
+    let name = list_entry(input, list);
+    let mut contents = String::new();
+    for &(start, end) in spans {
+        contents.push_str(&format!("file '{}'\n", name));
+        contents.push_str(&format!("inpoint {}\n", format_nanos(start)));
+        if let Some(end) = end {
+            contents.push_str(&format!("outpoint {}\n", format_nanos(end)));
+        }
+    }
+    contents
+}
+
+/// Joins the spans of a [`spans_list`] straight into `output`, so that no pieces are
+/// written in between. Only the `streams` (an ffmpeg `-map`) are kept. The list is
+/// written to `list`.
+pub fn concat_spans(
+    ffmpeg: &Path,
+    contents: &str,
+    list: &Path,
+    streams: &str,
+    output: &Path,
+    format: OutputFormat,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    run_concat(
+        ffmpeg,
+        contents,
+        list,
+        Some(streams),
+        output,
+        format,
+        report,
+    )
+}
+
+/// How `path` is named in the concat `list`, quoted for a `file` line
+fn list_entry(path: &Path, list: &Path) -> String {
+    // Entries are relative to the list, so anything not next to it gets a full path
+    let name = if path.parent() == list.parent() {
+        path.file_name()
+            .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+            .into_owned()
+    } else {
+        fs::canonicalize(path)
+            .unwrap_or_else(|_| path.to_path_buf())
+            .to_string_lossy()
+            .into_owned()
+    };
+    name.replace('\'', "'\\''")
+}
+
+/// Writes `contents` to `list` and runs the concat demuxer over it, removing the list after.
+/// Without `streams` ffmpeg picks which streams to keep.
+fn run_concat(
+    ffmpeg: &Path,
+    contents: &str,
+    list: &Path,
+    streams: Option<&str>,
+    output: &Path,
+    format: OutputFormat,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    fs::write(list, contents).map_err(|e| SplitError::Io(list.to_path_buf(), e))?;
+    let args = concat_args(list, streams, output, format);
+    let result = run_ffmpeg(ffmpeg, args, report);
+    let _ = fs::remove_file(list);
+    result.map(|_| ())
+}
+
+/// The arguments `run_concat` runs ffmpeg with for the concat `list`
+pub fn concat_args(
+    list: &Path,
+    streams: Option<&str>,
+    output: &Path,
+    format: OutputFormat,
+) -> Vec<String> {
+    let mut args = vec![
+        "-f".to_owned(),
+        "concat".to_owned(),
+        "-safe".to_owned(),
+        "0".to_owned(),
+        "-i".to_owned(),
+        list.to_string_lossy().into_owned(),
+    ];
+    if let Some(streams) = streams {
+        args.push("-map".to_owned());
+        args.push(streams.to_owned());
+    }
+    args.push("-c".to_owned());
+    args.push("copy".to_owned());
+    args.extend(format_args(format));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// Copies the `streams` (an ffmpeg `-map`) of the whole input to `output`, shifted by
+/// `delay` milliseconds.
+pub fn remux(
+    ffmpeg: &Path,
+    input: &Path,
+    delay: isize,
+    streams: &str,
+    output: &Path,
+    format: OutputFormat,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    run_ffmpeg(
+        ffmpeg,
+        remux_args(input, delay, streams, output, format),
+        report,
+    )?;
+    Ok(())
+}
+
+/// The arguments `remux` runs ffmpeg with
+pub fn remux_args(
+    input: &Path,
+    delay: isize,
+    streams: &str,
+    output: &Path,
+    format: OutputFormat,
+) -> Vec<String> {
+    let mut args = vec![
+        "-itsoffset".to_owned(),
+        format!("{:.3}", delay as f64 / 1000f64),
+        "-i".to_owned(),
+        input.to_string_lossy().into_owned(),
+        "-map".to_owned(),
+        streams.to_owned(),
+        "-c".to_owned(),
+        "copy".to_owned(),
+    ];
+    args.extend(format_args(format));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+/// Writes `delay` milliseconds of silence to `output`, encoded so that it can be
+/// joined to a `codec` stream with the given sample rate and channel layout
+/// without re-encoding the rest of the audio.
+pub fn generate_silence(
+    ffmpeg: &Path,
+    codec: &str,
+    sample_rate: &str,
+    channel_layout: &str,
+    delay: isize,
+    output: &Path,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let args = silence_args(codec, sample_rate, channel_layout, delay, output)?;
+    run_ffmpeg(ffmpeg, args, report)?;
+    Ok(())
+}
+
+/// The arguments `generate_silence` runs ffmpeg with
+pub fn silence_args(
+    codec: &str,
+    sample_rate: &str,
+    channel_layout: &str,
+    delay: isize,
+    output: &Path,
+) -> Result<Vec<String>, SplitError> {
+    let encoder = match codec {
+        "aac" | "ac3" | "eac3" | "flac" | "alac" | "truehd" => codec,
+        "dts" => "dca",
+        "opus" => "libopus",
+        "vorbis" => "libvorbis",
+        "mp3" => "libmp3lame",
+        codec if codec.starts_with("pcm_") => codec,
+        _ => {
+            return Err(SplitError::InvalidArgument(format!(
+                "Cannot generate silence for a {} stream",
+                codec
+            )))
+        }
+    };
+    let mut args = vec![
+        "-f".to_owned(),
+        "lavfi".to_owned(),
+        "-i".to_owned(),
+        format!("anullsrc=r={}:cl={}", sample_rate, channel_layout),
+        "-t".to_owned(),
+        format!("{:.3}", delay as f64 / 1000f64),
+        "-c:a".to_owned(),
+        encoder.to_owned(),
+    ];
+    // ffmpeg's DTS and TrueHD encoders are still marked experimental
+    if encoder == "dca" || encoder == "truehd" {
+        args.push("-strict".to_owned());
+        args.push("-2".to_owned());
+    }
+    // Only the packets of the silence are copied on, so it needn't be bitexact
+    args.extend(format_args(OutputFormat {
+        follow_extension: false,
+        bitexact: false,
+    }));
+    args.push(output.to_string_lossy().into_owned());
+    Ok(args)
+}
+
+/// Checks that a stream of `codec` can be copied as-is into a file with `extension`.
+pub fn check_copy_container(codec: &str, extension: &str) -> Result<(), SplitError> {
+    if container_holds(codec, extension) {
+        Ok(())
+    } else {
+        Err(SplitError::InvalidArgument(format!(
+            "Cannot copy a {} stream into a .{} file",
+            codec,
+            extension.to_ascii_lowercase()
+        )))
+    }
+}
+
+/// Whether a file with `extension` can hold a `codec` audio stream
+fn container_holds(codec: &str, extension: &str) -> bool {
+    let extension = extension.to_ascii_lowercase();
+    // Matroska holds just about anything
+    if extension == "mka" || extension == "mkv" {
+        return true;
+    }
+    let allowed: &[&str] = match codec {
+        "aac" => &["aac", "m4a", "mp4"],
+        "ac3" => &["ac3"],
+        "eac3" => &["eac3", "ec3"],
+        "dts" => &["dts"],
+        "truehd" => &["thd"],
+        "flac" => &["flac"],
+        "opus" => &["opus", "ogg"],
+        "vorbis" => &["ogg"],
+        "mp3" => &["mp3"],
+        "alac" => &["m4a"],
+        codec if codec.starts_with("pcm_") => &["wav"],
+        _ => &[],
+    };
+    allowed.contains(&extension.as_str())
+}
+
+/// What `--transcode` encodes the output to, like `opus:128k`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcode {
+    pub codec: String,
+    /// Passed to ffmpeg's `-b:a` as it is, like `128k` or `192000`
+    pub bitrate: Option<String>,
+}
+
+impl Transcode {
+    pub fn parse(value: &str) -> Result<Transcode, SplitError> {
+        let (codec, bitrate) = match value.split_once(':') {
+            Some((codec, bitrate)) => (codec, Some(bitrate)),
+            None => (value, None),
+        };
+        let codec = codec.to_ascii_lowercase();
+        if transcode_encoder(&codec).is_none() {
+            return Err(SplitError::InvalidArgument(format!(
+                "Unknown transcode codec {}, expected aac, ac3, eac3, flac, mp3, opus or vorbis",
+                codec
+            )));
+        }
+        if let Some(bitrate) = bitrate {
+            let digits = bitrate.strip_suffix('k').unwrap_or(bitrate);
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return Err(SplitError::InvalidArgument(format!(
+                    "Invalid transcode bitrate {}, expected a number of bits per second like 128k",
+                    bitrate
+                )));
+            }
+            if codec == "flac" {
+                return Err(SplitError::InvalidArgument(
+                    "flac is lossless and takes no bitrate".to_owned(),
+                ));
+            }
+        }
+        Ok(Transcode {
+            codec,
+            bitrate: bitrate.map(str::to_owned),
+        })
+    }
+
+    /// Checks that a file with `extension` can hold the codec
+    pub fn check_container(&self, extension: &str) -> Result<(), SplitError> {
+        if container_holds(&self.codec, extension) {
+            Ok(())
+        } else {
+            Err(SplitError::InvalidArgument(format!(
+                "Cannot write {} audio to a .{} file",
+                self.codec,
+                extension.to_ascii_lowercase()
+            )))
+        }
+    }
+}
+
+/// The ffmpeg encoder for a `--transcode` codec
+fn transcode_encoder(codec: &str) -> Option<&'static str> {
+    match codec {
+        "aac" => Some("aac"),
+        "ac3" => Some("ac3"),
+        "eac3" => Some("eac3"),
+        "flac" => Some("flac"),
+        "mp3" => Some("libmp3lame"),
+        "opus" => Some("libopus"),
+        "vorbis" => Some("libvorbis"),
+        _ => None,
+    }
+}
+
+/// Encodes the audio of `input` to `output` as `transcode` asks
+pub fn transcode(
+    ffmpeg: &Path,
+    input: &Path,
+    transcode: &Transcode,
+    output: &Path,
+    format: OutputFormat,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    run_ffmpeg(
+        ffmpeg,
+        transcode_args(input, transcode, output, format),
+        report,
+    )?;
+    Ok(())
+}
+
+/// The arguments `transcode` runs ffmpeg with
+pub fn transcode_args(
+    input: &Path,
+    transcode: &Transcode,
+    output: &Path,
+    format: OutputFormat,
+) -> Vec<String> {
+    let mut args = vec![
+        "-i".to_owned(),
+        input.to_string_lossy().into_owned(),
+        "-map".to_owned(),
+        "0:a".to_owned(),
+        "-c:a".to_owned(),
+        transcode_encoder(&transcode.codec).unwrap().to_owned(),
+    ];
+    if let Some(ref bitrate) = transcode.bitrate {
+        args.push("-b:a".to_owned());
+        args.push(bitrate.clone());
+    }
+    args.extend(format_args(format));
+    args.push(output.to_string_lossy().into_owned());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_is_generated_for_the_delay() {
+        let args = silence_args("aac", "48000", "stereo", 250, Path::new("silence.mka")).unwrap();
+        assert_eq!(
+            args[..8],
+            [
+                "-f",
+                "lavfi",
+                "-i",
+                "anullsrc=r=48000:cl=stereo",
+                "-t",
+                "0.250",
+                "-c:a",
+                "aac"
+            ]
+        );
+        assert_eq!(args.last().unwrap(), "silence.mka");
+        assert!(!args.contains(&"-strict".to_owned()));
+    }
+
+    #[test]
+    fn experimental_encoders_are_allowed_for_silence() {
+        let args = silence_args("dts", "48000", "5.1", 40, Path::new("silence.mka")).unwrap();
+        assert!(args.windows(2).any(|pair| pair == ["-c:a", "dca"]));
+        assert!(args.windows(2).any(|pair| pair == ["-strict", "-2"]));
+    }
+
+    #[test]
+    fn silence_cannot_be_generated_for_an_unknown_codec() {
+        assert!(matches!(
+            silence_args("mlp", "48000", "stereo", 40, Path::new("silence.mka")),
+            Err(SplitError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn transcoding_picks_the_encoder_and_bitrate() {
+        let transcode = Transcode::parse("Opus:128k").unwrap();
+        let format = OutputFormat {
+            follow_extension: true,
+            bitexact: false,
+        };
+        let args = transcode_args(
+            Path::new("in.mka"),
+            &transcode,
+            Path::new("out.opus"),
+            format,
+        );
+        assert_eq!(
+            args,
+            ["-i", "in.mka", "-map", "0:a", "-c:a", "libopus", "-b:a", "128k", "out.opus"]
+        );
+        // Without a bitrate the encoder's own default is used
+        let transcode = Transcode::parse("flac").unwrap();
+        let format = OutputFormat {
+            follow_extension: false,
+            bitexact: true,
+        };
+        let args = transcode_args(
+            Path::new("in.mka"),
+            &transcode,
+            Path::new("out.mka"),
+            format,
+        );
+        assert_eq!(
+            args,
+            [
+                "-i",
+                "in.mka",
+                "-map",
+                "0:a",
+                "-c:a",
+                "flac",
+                "-f",
+                "matroska",
+                "-fflags",
+                "+bitexact",
+                "out.mka"
+            ]
+        );
+    }
+
+    #[test]
+    fn transcoding_rejects_bad_values() {
+        assert!(Transcode::parse("mlp").is_err());
+        assert!(Transcode::parse("aac:fast").is_err());
+        assert!(Transcode::parse("aac:").is_err());
+        assert!(Transcode::parse("flac:500k").is_err());
+    }
+
+    #[test]
+    fn transcoding_checks_the_output_extension() {
+        let transcode = Transcode::parse("opus").unwrap();
+        assert!(transcode.check_container("opus").is_ok());
+        assert!(transcode.check_container("OGG").is_ok());
+        assert!(transcode.check_container("mka").is_ok());
+        match transcode.check_container("M4A") {
+            Err(SplitError::InvalidArgument(message)) => {
+                assert_eq!(message, "Cannot write opus audio to a .m4a file")
+            }
+            other => panic!("expected a container error, got {:?}", other),
+        }
+        assert!(Transcode::parse("mp3")
+            .unwrap()
+            .check_container("flac")
+            .is_err());
+    }
+
+    #[test]
+    fn copying_checks_the_output_extension() {
+        assert!(check_copy_container("pcm_s16le", "wav").is_ok());
+        assert!(check_copy_container("truehd", "thd").is_ok());
+        assert!(check_copy_container("dts", "ac3").is_err());
+        assert!(check_copy_container("mlp", "mlp").is_err());
+    }
+}
@@ -0,0 +1,570 @@
+use crate::error::{spawn_error, SplitError};
+use crate::json::Json;
+use crate::timecode;
+use std::path::Path;
+use std::process::Command;
+
+/// Runs ffprobe with the given arguments against `file` and returns its stdout.
+pub fn run_ffprobe(ffprobe: &Path, args: &[&str], file: &Path) -> Result<String, SplitError> {
+    let output = Command::new(ffprobe)
+        .arg("-v")
+        .arg("error")
+        .args(args)
+        .arg(file)
+        .output()
+        .map_err(|e| spawn_error("ffprobe", e))?;
+    if !output.status.success() {
+        return Err(SplitError::ProcessFailed(
+            "ffprobe".to_owned(),
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads a number of seconds as ffprobe prints it, whatever the locale. Builds that
+/// honour the locale may print a comma as the decimal separator, and some print a
+/// unit after the number, like `60.000000 s`.
+fn parse_seconds(value: &str) -> Option<f64> {
+    let number = value
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+        .trim_end();
+    number.replace(',', ".").parse::<f64>().ok()
+}
+
+/// Reads the duration ffprobe printed, in seconds. A missing duration comes back
+/// empty or as `N/A`.
+fn parse_duration(output: &str) -> Option<f64> {
+    output.lines().find_map(parse_seconds)
+}
+
+/// Where to read a file's duration from, for `--duration-source`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DurationSource {
+    /// The container's duration
+    Format,
+    /// The first audio stream's duration
+    Stream,
+    /// The container's duration, or the stream's when the container doesn't have one
+    Auto,
+}
+
+impl DurationSource {
+    pub fn parse(value: &str) -> Result<DurationSource, SplitError> {
+        match value {
+            "format" => Ok(DurationSource::Format),
+            "stream" => Ok(DurationSource::Stream),
+            "auto" => Ok(DurationSource::Auto),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Unknown duration source {}, expected format, stream or auto",
+                value
+            ))),
+        }
+    }
+}
+
+/// Gets the duration of a media file in seconds. A stream duration is read from
+/// audio stream `track`.
+pub fn probe_duration(
+    ffprobe: &Path,
+    file: &Path,
+    source: DurationSource,
+    track: usize,
+) -> Result<f64, SplitError> {
+    let probe = |args: &[&str]| -> Result<Option<f64>, SplitError> {
+        let mut all_args = args.to_vec();
+        all_args.extend(&["-of", "default=noprint_wrappers=1:nokey=1"]);
+        Ok(parse_duration(&run_ffprobe(ffprobe, &all_args, file)?))
+    };
+    let format = || probe(&["-show_entries", "format=duration"]);
+    let stream_spec = format!("a:{}", track);
+    let stream = || {
+        probe(&[
+            "-select_streams",
+            &stream_spec,
+            "-show_entries",
+            "stream=duration",
+        ])
+    };
+    let duration = match source {
+        DurationSource::Format => format()?,
+        DurationSource::Stream => stream()?,
+        DurationSource::Auto => match format()? {
+            Some(duration) => Some(duration),
+            None => stream()?,
+        },
+    };
+    let duration = duration.ok_or_else(|| {
+        SplitError::ProcessFailed(
+            "ffprobe".to_owned(),
+            format!("could not read the duration of {}", file.display()),
+        )
+    })?;
+    check_duration(duration).map_err(|reported| {
+        SplitError::ProcessFailed(
+            "ffprobe".to_owned(),
+            format!("reported {} for {}", reported, file.display()),
+        )
+    })
+}
+
+/// Checks that a duration could be real, saying what was reported if it can't.
+///
+/// Anything past [`MAX_DURATION_SECS`] is a broken probe, and would overflow once
+/// counted in frames. NaN fails the range check too.
+fn check_duration(duration: f64) -> Result<f64, String> {
+    if (0f64..=MAX_DURATION_SECS).contains(&duration) {
+        Ok(duration)
+    } else if duration < 0f64 {
+        Err(format!("a negative duration of {}s", duration))
+    } else {
+        Err("a duration of over a week".to_owned())
+    }
+}
+
+/// The longest duration believed from ffprobe, a week
+const MAX_DURATION_SECS: f64 = 7f64 * 24f64 * 60f64 * 60f64;
+
+/// Gets the codec name of audio stream `track`, like `aac` or `ac3`.
+pub fn probe_codec(ffprobe: &Path, file: &Path, track: usize) -> Result<String, SplitError> {
+    let output = run_ffprobe(
+        ffprobe,
+        &[
+            "-select_streams",
+            &format!("a:{}", track),
+            "-show_entries",
+            "stream=codec_name",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ],
+        file,
+    )?;
+    let codec = output.trim();
+    if codec.is_empty() {
+        return Err(SplitError::ProcessFailed(
+            "ffprobe".to_owned(),
+            format!("no audio stream found in {}", file.display()),
+        ));
+    }
+    Ok(codec.to_owned())
+}
+
+/// Gets the frame rate of the first video stream as ffprobe gives it, normally a
+/// `num/den` fraction, or `None` if there is no video stream.
+pub fn probe_video_framerate(ffprobe: &Path, file: &Path) -> Result<Option<String>, SplitError> {
+    let output = run_ffprobe(
+        ffprobe,
+        &[
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=r_frame_rate",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ],
+        file,
+    )?;
+    Ok(output
+        .lines()
+        .map(str::trim)
+        .find(|rate| !rate.is_empty())
+        .map(str::to_owned))
+}
+
+/// Gets the start time of audio stream `track` in seconds, which is non-zero when
+/// an edit list or encoder delay shifts where the audio begins.
+pub fn probe_start_time(
+    ffprobe: &Path,
+    file: &Path,
+    track: usize,
+) -> Result<Option<f64>, SplitError> {
+    let output = run_ffprobe(
+        ffprobe,
+        &[
+            "-select_streams",
+            &format!("a:{}", track),
+            "-show_entries",
+            "stream=start_time",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ],
+        file,
+    )?;
+    Ok(parse_seconds(&output))
+}
+
+/// Gets when each packet of audio stream `track` starts, in nanoseconds and in order.
+///
+/// Packets before the start of the file, like an encoder's priming samples, are left out.
+pub fn probe_packet_times(
+    ffprobe: &Path,
+    file: &Path,
+    track: usize,
+) -> Result<Vec<u128>, SplitError> {
+    let output = run_ffprobe(
+        ffprobe,
+        &[
+            "-select_streams",
+            &format!("a:{}", track),
+            "-show_entries",
+            "packet=pts_time",
+            "-of",
+            "csv=p=0",
+        ],
+        file,
+    )?;
+    let mut times: Vec<u128> = output
+        .lines()
+        .filter_map(parse_seconds)
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0f64)
+        .map(|seconds| (seconds * 1e9).round() as u128)
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+    Ok(times)
+}
+
+/// The sample rate and channel layout of an audio stream, in the form ffmpeg's
+/// `anullsrc` takes them, and the format of its samples when ffprobe knows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleFormat {
+    pub sample_rate: String,
+    pub channel_layout: String,
+    /// Like `fltp` or `s32`
+    pub sample_fmt: Option<String>,
+}
+
+/// Gets the sample rate, channel layout and sample format of audio stream `track`
+pub fn probe_sample_format(
+    ffprobe: &Path,
+    file: &Path,
+    track: usize,
+) -> Result<SampleFormat, SplitError> {
+    let output = run_ffprobe(
+        ffprobe,
+        &[
+            "-select_streams",
+            &format!("a:{}", track),
+            "-show_entries",
+            "stream=sample_rate,sample_fmt,channels,channel_layout",
+            "-of",
+            "default=noprint_wrappers=1",
+        ],
+        file,
+    )?;
+    parse_sample_format(&output).ok_or_else(|| {
+        SplitError::ProcessFailed(
+            "ffprobe".to_owned(),
+            format!("could not read the sample rate of {}", file.display()),
+        )
+    })
+}
+
+/// Reads the `key=value` lines `probe_sample_format` asks ffprobe for. There is no
+/// format without a sample rate.
+fn parse_sample_format(output: &str) -> Option<SampleFormat> {
+    let field = |name: &str| {
+        output
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix(name)?.strip_prefix('='))
+            .find(|value| !value.is_empty() && *value != "unknown")
+            .map(str::to_owned)
+    };
+    let sample_rate = field("sample_rate")?;
+    // Streams without a layout still have a channel count, which ffmpeg accepts as `2c`
+    let channel_layout = field("channel_layout")
+        .or_else(|| field("channels").map(|channels| format!("{}c", channels)))
+        .unwrap_or_else(|| "stereo".to_owned());
+    Some(SampleFormat {
+        sample_rate,
+        channel_layout,
+        sample_fmt: field("sample_fmt"),
+    })
+}
+
+/// What ffprobe reports about one audio stream, for `--audio-info`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioStream {
+    pub index: i64,
+    pub codec: String,
+    pub codec_long_name: Option<String>,
+    pub sample_rate: Option<String>,
+    pub channels: Option<i64>,
+    pub channel_layout: Option<String>,
+    pub sample_format: Option<String>,
+    /// Only known for lossless and PCM codecs
+    pub bit_depth: Option<i64>,
+    pub language: Option<String>,
+}
+
+/// Gets the properties of every audio stream, from ffprobe's JSON output.
+pub fn probe_audio_streams(ffprobe: &Path, file: &Path) -> Result<Vec<AudioStream>, SplitError> {
+    let output = run_ffprobe(
+        ffprobe,
+        &["-select_streams", "a", "-show_streams", "-of", "json"],
+        file,
+    )?;
+    let invalid = |message: String| {
+        SplitError::ProcessFailed(
+            "ffprobe".to_owned(),
+            format!(
+                "could not read the streams of {}: {}",
+                file.display(),
+                message
+            ),
+        )
+    };
+    let json = Json::parse(&output).map_err(&invalid)?;
+    let streams = match json.get("streams") {
+        Some(Json::Array(streams)) => streams,
+        _ => return Err(invalid("no `streams` array".to_owned())),
+    };
+
+    // ffprobe writes some numbers as strings, like "48000" for the sample rate
+    fn string(stream: &Json, key: &str) -> Option<String> {
+        match stream.get(key) {
+            Some(Json::String(value)) if !value.is_empty() && value != "unknown" => {
+                Some(value.clone())
+            }
+            Some(&Json::Int(value)) => Some(value.to_string()),
+            _ => None,
+        }
+    }
+    fn int(stream: &Json, key: &str) -> Option<i64> {
+        string(stream, key)
+            .and_then(|value| value.parse::<i64>().ok())
+            .filter(|&value| value > 0)
+    }
+
+    Ok(streams
+        .iter()
+        .map(|stream| AudioStream {
+            index: int(stream, "index").unwrap_or(0),
+            codec: string(stream, "codec_name").unwrap_or_else(|| "unknown".to_owned()),
+            codec_long_name: string(stream, "codec_long_name"),
+            sample_rate: string(stream, "sample_rate"),
+            channels: int(stream, "channels"),
+            channel_layout: string(stream, "channel_layout"),
+            sample_format: string(stream, "sample_fmt"),
+            bit_depth: int(stream, "bits_per_raw_sample")
+                .or_else(|| int(stream, "bits_per_sample")),
+            language: stream.get("tags").and_then(|tags| string(tags, "language")),
+        })
+        .collect())
+}
+
+/// A chapter of the input, with times in nanoseconds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub start: u128,
+    pub end: u128,
+    pub title: Option<String>,
+}
+
+/// Gets the chapters of `file` in the order ffprobe lists them, from its JSON output.
+pub fn probe_chapters(ffprobe: &Path, file: &Path) -> Result<Vec<Chapter>, SplitError> {
+    let output = run_ffprobe(ffprobe, &["-show_chapters", "-of", "json"], file)?;
+    let invalid = |message: String| {
+        SplitError::ProcessFailed(
+            "ffprobe".to_owned(),
+            format!(
+                "could not read the chapters of {}: {}",
+                file.display(),
+                message
+            ),
+        )
+    };
+    let json = Json::parse(&output).map_err(&invalid)?;
+    let chapters = match json.get("chapters") {
+        Some(Json::Array(chapters)) => chapters,
+        _ => return Err(invalid("no `chapters` array".to_owned())),
+    };
+
+    let mut read = Vec::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        // Times are strings of seconds, like "12.345000"
+        let time = |key: &str| match chapter.get(key) {
+            Some(Json::String(value)) => timecode::parse_seconds(value).ok_or_else(|| {
+                invalid(format!(
+                    "chapter {} has an invalid {} {}",
+                    i + 1,
+                    key,
+                    value
+                ))
+            }),
+            _ => Err(invalid(format!("chapter {} has no {}", i + 1, key))),
+        };
+        let title = match chapter.get("tags").and_then(|tags| tags.get("title")) {
+            Some(Json::String(title)) if !title.is_empty() => Some(title.clone()),
+            _ => None,
+        };
+        read.push(Chapter {
+            start: time("start_time")?,
+            end: time("end_time")?,
+            title,
+        });
+    }
+    Ok(read)
+}
+
+/// Gets the value of the container tag `name` of `file`, matched without regard to
+/// case, like ffprobe lists it among the format tags. For Matroska these are the tags
+/// that apply to the whole segment rather than to a track or chapter.
+pub fn probe_tag(ffprobe: &Path, file: &Path, name: &str) -> Result<Option<String>, SplitError> {
+    let output = run_ffprobe(
+        ffprobe,
+        &["-show_entries", "format_tags", "-of", "json"],
+        file,
+    )?;
+    let json = Json::parse(&output).map_err(|message| {
+        SplitError::ProcessFailed(
+            "ffprobe".to_owned(),
+            format!("could not read the tags of {}: {}", file.display(), message),
+        )
+    })?;
+    let tags = match json.get("format").and_then(|format| format.get("tags")) {
+        Some(Json::Object(tags)) => tags,
+        _ => return Ok(None),
+    };
+    Ok(tags
+        .iter()
+        .find(|(tag, _)| tag.eq_ignore_ascii_case(name))
+        .and_then(|(_, value)| match value {
+            Json::String(value) => Some(value.clone()),
+            _ => None,
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::path::PathBuf;
+    #[cfg(unix)]
+    use std::{env, fs, process};
+
+    #[test]
+    fn reads_durations_in_any_locale() {
+        assert_eq!(parse_duration("1234.567000\n"), Some(1234.567));
+        assert_eq!(parse_duration("1234,567000\n"), Some(1234.567));
+        assert_eq!(parse_duration("60.000000 s\n"), Some(60.0));
+    }
+
+    #[test]
+    fn a_missing_duration_is_none() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("N/A\n"), None);
+        // A stream without a duration may print it before one that has one
+        assert_eq!(parse_duration("N/A\n12.5\n"), Some(12.5));
+    }
+
+    #[test]
+    fn reads_scientific_notation() {
+        assert_eq!(parse_duration("1.5e3\n"), Some(1500.0));
+        assert_eq!(parse_duration("6E+1\n").map(check_duration), Some(Ok(60.0)));
+    }
+
+    #[test]
+    fn refuses_negative_and_absurd_durations() {
+        let check = |output: &str| check_duration(parse_duration(output).unwrap());
+        assert!(check("-1.500000").unwrap_err().contains("negative"));
+        assert!(check("1e12").unwrap_err().contains("over a week"));
+        assert!(check("1e400").is_err());
+        assert_eq!(check_duration(f64::NAN).map_err(|_| ()), Err(()));
+        assert_eq!(check("604800"), Ok(MAX_DURATION_SECS));
+    }
+
+    #[test]
+    fn reads_the_sample_format() {
+        let format = parse_sample_format(
+            "sample_rate=48000\nsample_fmt=fltp\nchannels=6\nchannel_layout=5.1(side)\n",
+        )
+        .unwrap();
+        assert_eq!(
+            format,
+            SampleFormat {
+                sample_rate: "48000".to_owned(),
+                channel_layout: "5.1(side)".to_owned(),
+                sample_fmt: Some("fltp".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn a_missing_channel_layout_falls_back_to_the_channel_count() {
+        let format = parse_sample_format(
+            "sample_rate=44100\nsample_fmt=unknown\nchannels=2\nchannel_layout=\n",
+        )
+        .unwrap();
+        assert_eq!(format.channel_layout, "2c");
+        assert_eq!(format.sample_fmt, None);
+        assert_eq!(parse_sample_format("channels=2\n"), None);
+    }
+
+    /// An empty directory of the test's own under the system temp directory
+    #[cfg(unix)]
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("split_aud-ffprobe-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a shell script into `dir` to stand in for ffprobe
+    #[cfg(unix)]
+    fn fake_ffprobe(dir: &Path, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join("ffprobe");
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_missing_format_duration_falls_back_to_the_stream() {
+        let dir = test_dir("duration-fallback");
+        let ffprobe = fake_ffprobe(
+            &dir,
+            "case \"$*\" in\n\
+             *format=duration*) echo N/A ;;\n\
+             *stream=duration*) echo 42.500000 ;;\n\
+             esac",
+        );
+        let file = Path::new("in.mka");
+        assert_eq!(
+            probe_duration(&ffprobe, file, DurationSource::Auto, 0).unwrap(),
+            42.5
+        );
+        assert_eq!(
+            probe_duration(&ffprobe, file, DurationSource::Stream, 0).unwrap(),
+            42.5
+        );
+        // Asked for the format's duration alone, there is none
+        assert!(matches!(
+            probe_duration(&ffprobe, file, DurationSource::Format, 0),
+            Err(SplitError::ProcessFailed(..))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn the_format_duration_is_preferred() {
+        let dir = test_dir("duration-format");
+        let ffprobe = fake_ffprobe(
+            &dir,
+            "case \"$*\" in\n\
+             *format=duration*) echo 40.000000 ;;\n\
+             *stream=duration*) echo 42.500000 ;;\n\
+             esac",
+        );
+        let file = Path::new("in.mka");
+        assert_eq!(
+            probe_duration(&ffprobe, file, DurationSource::Auto, 0).unwrap(),
+            40.0
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
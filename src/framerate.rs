@@ -0,0 +1,351 @@
+use crate::error::SplitError;
+use regex::Regex;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Where the framerate in use was decided
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramerateSource {
+    Default,
+    Argument,
+    MediaInfo,
+    Video,
+    Script,
+}
+
+impl fmt::Display for FramerateSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            FramerateSource::Default => "default",
+            FramerateSource::Argument => "--framerate",
+            FramerateSource::MediaInfo => "--mediainfo",
+            FramerateSource::Video => "--fps-from",
+            FramerateSource::Script => "script",
+        })
+    }
+}
+
+/// An exact framerate, kept as a fraction so it can be printed back the way it was given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rational {
+    pub num: u64,
+    pub den: u64,
+}
+
+impl Rational {
+    pub const NTSC: Rational = Rational {
+        num: 30000,
+        den: 1001,
+    };
+
+    /// Parses a `num/den` fraction such as `24000/1001`, or a decimal such as `23.976`.
+    ///
+    /// A decimal within `tolerance` of one of the NTSC rates is taken to mean that rate,
+    /// so `23.976` becomes `24000/1001`. Any other decimal is used exactly as written,
+    /// so `23.5` becomes `47/2`.
+    ///
+    /// Rates below [`MIN_FRAMERATE`] are refused, since every frame of them lasts
+    /// so long that frame numbers turn into timecodes of years.
+    pub fn parse(value: &str, tolerance: f64) -> Result<Rational, SplitError> {
+        let rate = Rational::parse_unchecked(value, tolerance)?;
+        if (rate.num as u128) * (MIN_FRAMERATE.den as u128)
+            < (MIN_FRAMERATE.num as u128) * (rate.den as u128)
+        {
+            return Err(SplitError::InvalidArgument(format!(
+                "Framerate {} is below the smallest supported rate of {} fps",
+                value, MIN_FRAMERATE
+            )));
+        }
+        Ok(rate)
+    }
+
+    fn parse_unchecked(value: &str, tolerance: f64) -> Result<Rational, SplitError> {
+        let invalid = || SplitError::InvalidArgument(format!("Invalid framerate: {}", value));
+        if !value.contains('/') {
+            let exact = parse_decimal(value.trim()).ok_or_else(invalid)?;
+            let snapped = NTSC_RATES
+                .iter()
+                .find(|rate| (rate.as_f64() - exact.as_f64()).abs() <= tolerance);
+            return Ok(*snapped.unwrap_or(&exact));
+        }
+        let parts: Vec<&str> = value.trim().split('/').collect();
+        if parts.len() != 2 {
+            return Err(invalid());
+        }
+        let num = parts[0].trim().parse::<u64>().map_err(|_| invalid())?;
+        let den = parts[1].trim().parse::<u64>().map_err(|_| invalid())?;
+        if num == 0 || den == 0 {
+            return Err(invalid());
+        }
+        Ok(Rational { num, den })
+    }
+
+    /// The rate of fields for an interlaced source running at this frame rate.
+    pub fn doubled(self) -> Rational {
+        Rational {
+            num: self.num * 2,
+            den: self.den,
+        }
+    }
+
+    pub fn as_f32(self) -> f32 {
+        self.num as f32 / self.den as f32
+    }
+
+    fn as_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+}
+
+/// The slowest framerate accepted, one frame every 1000 seconds
+pub const MIN_FRAMERATE: Rational = Rational { num: 1, den: 1000 };
+
+/// How close a decimal framerate has to be to an NTSC rate to be snapped to it
+pub const DEFAULT_FRAMERATE_TOLERANCE: f64 = 0.001;
+
+/// The rates that decimals like `23.976` and `29.97` are usually short for
+const NTSC_RATES: [Rational; 5] = [
+    Rational {
+        num: 24000,
+        den: 1001,
+    },
+    Rational {
+        num: 30000,
+        den: 1001,
+    },
+    Rational {
+        num: 48000,
+        den: 1001,
+    },
+    Rational {
+        num: 60000,
+        den: 1001,
+    },
+    Rational {
+        num: 120000,
+        den: 1001,
+    },
+];
+
+/// Turns a decimal like `23.5` into the exact fraction it writes out, in lowest terms
+fn parse_decimal(value: &str) -> Option<Rational> {
+    let (whole, fraction) = match value.find('.') {
+        Some(pos) => (&value[..pos], &value[pos + 1..]),
+        None => (value, ""),
+    };
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+    let den = 10u64.checked_pow(fraction.len() as u32)?;
+    let num = format!("{}{}", whole, fraction).parse::<u64>().ok()?;
+    if num == 0 {
+        return None;
+    }
+    let divisor = gcd(num, den);
+    Some(Rational {
+        num: num / divisor,
+        den: den / divisor,
+    })
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+/// A framerate declared in a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptFramerate {
+    pub framerate: Rational,
+    /// The call that set it, as written, when it was `ConvertFPS` or `ChangeFPS`,
+    /// which make new frames rather than relabelling the existing ones
+    pub resampled_by: Option<String>,
+}
+
+/// Finds the framerate a script declares with `AssumeFPS`, `ConvertFPS` or
+/// `ChangeFPS`, if any.
+///
+/// Handles AviSynth's `AssumeFPS(24000, 1001)` and `ConvertFPS(25)` as well as
+/// VapourSynth's `core.std.AssumeFPS(clip, fpsnum=24000, fpsden=1001)`.
+/// The last declaration wins, since it decides the rate of the final clip.
+pub fn detect_script_framerate(script: &str) -> Option<ScriptFramerate> {
+    static CALL_REGEX: OnceLock<Regex> = OnceLock::new();
+    let call_regex = CALL_REGEX
+        .get_or_init(|| Regex::new(r"(?i)\b((?:assume|convert|change)fps)\(([^)]*)\)").unwrap());
+
+    static KEYWORD_REGEX: OnceLock<Regex> = OnceLock::new();
+    let keyword_regex = KEYWORD_REGEX
+        .get_or_init(|| Regex::new(r"fpsnum\s*=\s*(\d+)(?:\s*,\s*fpsden\s*=\s*(\d+))?").unwrap());
+
+    static POSITIONAL_REGEX: OnceLock<Regex> = OnceLock::new();
+    let positional_regex = POSITIONAL_REGEX.get_or_init(|| {
+        Regex::new(r"^(?:\s*[A-Za-z_]\w*\s*,)?\s*(\d+)\s*(?:,\s*(\d+)\s*)?$").unwrap()
+    });
+
+    let mut framerate = None;
+    for call in call_regex.captures_iter(script) {
+        let args = &call[2];
+        let captures = keyword_regex
+            .captures(args)
+            .or_else(|| positional_regex.captures(args));
+        if let Some(captures) = captures {
+            let num = captures[1].parse::<u64>().ok();
+            let den = captures
+                .get(2)
+                .map_or(Some(1), |den| den.as_str().parse::<u64>().ok());
+            if let (Some(num), Some(den)) = (num, den) {
+                if num > 0 && den > 0 {
+                    let name = &call[1];
+                    framerate = Some(ScriptFramerate {
+                        framerate: Rational { num, den },
+                        resampled_by: if name.eq_ignore_ascii_case("assumefps") {
+                            None
+                        } else {
+                            Some(name.to_owned())
+                        },
+                    });
+                }
+            }
+        }
+    }
+    framerate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<Rational, SplitError> {
+        Rational::parse(value, DEFAULT_FRAMERATE_TOLERANCE)
+    }
+
+    #[test]
+    fn decimals_close_to_an_ntsc_rate_are_snapped_to_it() {
+        let film = Rational {
+            num: 24000,
+            den: 1001,
+        };
+        assert_eq!(parse("23.976").unwrap(), film);
+        assert_eq!(parse("29.97").unwrap(), Rational::NTSC);
+        // 0.00098 from 24000/1001 is still within the tolerance, 0.00198 isn't
+        assert_eq!(parse("23.977").unwrap(), film);
+        assert_eq!(
+            parse("23.978").unwrap(),
+            Rational {
+                num: 11989,
+                den: 500
+            }
+        );
+    }
+
+    #[test]
+    fn other_decimals_are_used_exactly() {
+        assert_eq!(parse("23.5").unwrap(), Rational { num: 47, den: 2 });
+        assert_eq!(parse("25").unwrap(), Rational { num: 25, den: 1 });
+        assert_eq!(
+            parse(" 24000/1001 ").unwrap(),
+            Rational {
+                num: 24000,
+                den: 1001
+            }
+        );
+    }
+
+    #[test]
+    fn zero_and_malformed_rates_are_rejected() {
+        for value in &[
+            "0",
+            "0.0",
+            "0/1",
+            "1/0",
+            "",
+            ".",
+            "24/1001/2",
+            "-24",
+            "fast",
+        ] {
+            assert!(
+                matches!(parse(value), Err(SplitError::InvalidArgument(_))),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn rates_below_the_minimum_are_refused() {
+        assert!(matches!(
+            parse("1/1000000"),
+            Err(SplitError::InvalidArgument(ref message)) if message.contains("smallest")
+        ));
+        assert!(parse("0.0001").is_err());
+        assert_eq!(parse("1/1000").unwrap(), MIN_FRAMERATE);
+        assert_eq!(parse("0.001").unwrap(), MIN_FRAMERATE);
+    }
+
+    #[test]
+    fn finds_the_rate_a_script_declares() {
+        let resampled = detect_script_framerate("src = src.ConvertFPS(24, 1)").unwrap();
+        assert_eq!(resampled.framerate, Rational { num: 24, den: 1 });
+        assert_eq!(resampled.resampled_by.as_deref(), Some("ConvertFPS"));
+
+        let assumed = detect_script_framerate("AssumeFPS(24000, 1001)").unwrap();
+        assert_eq!(
+            assumed.framerate,
+            Rational {
+                num: 24000,
+                den: 1001
+            }
+        );
+        assert_eq!(assumed.resampled_by, None);
+
+        let keywords =
+            detect_script_framerate("clip = core.std.AssumeFPS(clip, fpsnum=30000, fpsden=1001)")
+                .unwrap();
+        assert_eq!(keywords.framerate, Rational::NTSC);
+        assert_eq!(
+            detect_script_framerate("src.AssumeFPS(src, 25)")
+                .unwrap()
+                .framerate,
+            Rational { num: 25, den: 1 }
+        );
+        assert_eq!(detect_script_framerate("src.Trim(0, 100)"), None);
+    }
+
+    #[test]
+    fn the_last_declared_rate_wins() {
+        let script = "AssumeFPS(25)\nChangeFPS(50)\nclip = core.std.AssumeFPS(clip, fpsnum=24)\n";
+        assert_eq!(
+            detect_script_framerate(script),
+            Some(ScriptFramerate {
+                framerate: Rational { num: 24, den: 1 },
+                resampled_by: None,
+            })
+        );
+    }
+
+    #[test]
+    fn doubling_gives_the_field_rate() {
+        assert_eq!(
+            Rational::NTSC.doubled(),
+            Rational {
+                num: 60000,
+                den: 1001
+            }
+        );
+        assert_eq!(
+            Rational { num: 25, den: 1 }.doubled(),
+            Rational { num: 50, den: 1 }
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::{spawn_error, SplitError};
+use crate::shell::quote;
+use std::path::Path;
+use std::process::Command;
+
+/// Runs the `--post-hook` command once the output has been written.
+///
+/// `{output}`, `{input}` and `{script}` in `template` are replaced with those paths,
+/// quoted for the shell, and the result is run by `sh -c` (`cmd /C` on Windows) with
+/// the tool's own stdout and stderr. `{input}` and `{script}` are empty when there
+/// is no single one, as with `--compile` or cuts that didn't come from a script.
+/// A hook that exits with anything but 0 fails the run.
+pub fn run_post_hook(
+    template: &str,
+    output: &Path,
+    input: Option<&Path>,
+    script: Option<&Path>,
+) -> Result<(), SplitError> {
+    let path = |path: Option<&Path>| {
+        quote(&path.map_or_else(String::new, |path| path.to_string_lossy().into_owned()))
+    };
+    let command = substitute(
+        template,
+        &[
+            ("{output}", path(Some(output))),
+            ("{input}", path(input)),
+            ("{script}", path(script)),
+        ],
+    );
+    println!("Running post hook: {}", command);
+
+    let status = if cfg!(windows) {
+        Command::new("cmd").arg("/C").arg(&command).status()
+    } else {
+        Command::new("sh").arg("-c").arg(&command).status()
+    }
+    .map_err(|e| spawn_error("post hook", e))?;
+    match status.code() {
+        Some(0) => {
+            println!("Post hook exited with status 0");
+            Ok(())
+        }
+        Some(code) => Err(SplitError::ProcessFailed(
+            "post hook".to_owned(),
+            format!("exited with status {}", code),
+        )),
+        None => Err(SplitError::ProcessFailed(
+            "post hook".to_owned(),
+            "killed by a signal".to_owned(),
+        )),
+    }
+}
+
+/// Replaces each of the `placeholders` in `template` in a single pass, so that nothing a
+/// path brings in is read as a placeholder itself
+fn substitute(template: &str, placeholders: &[(&str, String)]) -> String {
+    let mut command = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        command.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match placeholders
+            .iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder))
+        {
+            Some((placeholder, value)) => {
+                command.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                command.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    command.push_str(rest);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(output: &str, input: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("{output}", quote(output)),
+            ("{input}", quote(input)),
+            ("{script}", quote("")),
+        ]
+    }
+
+    #[test]
+    fn replaces_every_placeholder() {
+        assert_eq!(
+            substitute(
+                "cp {output} {input}.bak {script}",
+                &placeholders("out.mka", "in.mka")
+            ),
+            format!(
+                "cp {} {}.bak {}",
+                quote("out.mka"),
+                quote("in.mka"),
+                quote("")
+            )
+        );
+    }
+
+    #[test]
+    fn does_not_substitute_inside_a_replaced_path() {
+        let output = "{input}'; rm -rf ~; '.mka";
+        assert_eq!(
+            substitute("ls {output}", &placeholders(output, "in.mka")),
+            format!("ls {}", quote(output))
+        );
+    }
+
+    #[test]
+    fn keeps_other_braces() {
+        assert_eq!(
+            substitute("echo {} {outputs", &placeholders("o", "i")),
+            "echo {} {outputs"
+        );
+    }
+}
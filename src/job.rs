@@ -0,0 +1,365 @@
+use crate::backend::Backend;
+use crate::cuts_file::{parse_cut, CutsUnit};
+use crate::error::SplitError;
+use crate::expand::expand_path;
+use crate::framerate::Rational;
+use crate::json::Json;
+use crate::trims::Trim;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A whole run described in one JSON file, for `--job`.
+///
+/// `input` is required, along with exactly one of `script`, `cuts_file` or `cuts`.
+/// `output` is required unless there is a script. Cuts are `[start, end]` pairs
+/// counted in `cuts_unit`, written as numbers or as strings like `"00:01:02.500"`.
+///
+/// ```json
+/// {
+///   "input": "Ep01.mka",
+///   "output": "Ep01.trimmed.mka",
+///   "cuts": [[0, 1000], [2000, 3000]],
+///   "cuts_unit": "frames",
+///   "framerate": "24000/1001",
+///   "delay": -50,
+///   "backend": "mkvmerge"
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub script: Option<PathBuf>,
+    pub cuts_file: Option<PathBuf>,
+    /// Turned into trims by [`cut_trims`] once the framerate is known
+    pub cuts: Option<Vec<JobCut>>,
+    pub cuts_unit: CutsUnit,
+    /// Parsed once the `--framerate-tolerance` to parse it with is known
+    pub framerate: Option<String>,
+    pub delay: Option<isize>,
+    pub backend: Option<Backend>,
+}
+
+/// One cut given directly, from a job's `cuts` or `--trim`, not yet parsed
+#[derive(Debug, Clone)]
+pub struct JobCut {
+    pub start: String,
+    pub end: String,
+    /// The pair as it was written, for messages
+    pub text: String,
+}
+
+impl JobCut {
+    /// Reads the `START-END` of a `--trim`
+    pub fn parse_trim(trim: &str) -> Result<JobCut, SplitError> {
+        match trim.split('-').collect::<Vec<_>>()[..] {
+            [start, end] => Ok(JobCut {
+                start: start.trim().to_owned(),
+                end: end.trim().to_owned(),
+                text: format!("--trim {}", trim),
+            }),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Invalid --trim {}, expected START-END",
+                trim
+            ))),
+        }
+    }
+}
+
+pub fn load_job(path: &Path) -> Result<Job, SplitError> {
+    let contents = fs::read_to_string(path).map_err(|e| SplitError::Io(path.to_path_buf(), e))?;
+    let invalid =
+        |message: String| SplitError::InvalidArgument(format!("{}: {}", path.display(), message));
+
+    let json = Json::parse(&contents).map_err(&invalid)?;
+    let fields = match json {
+        Json::Object(ref fields) => fields,
+        _ => return Err(invalid("expected an object".to_owned())),
+    };
+    for (key, _) in fields {
+        if !KEYS.contains(&key.as_str()) {
+            return Err(invalid(format!("unknown key `{}`", key)));
+        }
+    }
+
+    let string = |key: &str| match json.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(Json::String(value)) => Ok(Some(value.as_str())),
+        Some(_) => Err(invalid(format!("`{}` must be a string", key))),
+    };
+    let path_of = |key: &str| string(key)?.map(expand_path).transpose();
+
+    let cuts_unit = match string("cuts_unit")? {
+        Some(unit) => CutsUnit::parse(unit)?,
+        None => CutsUnit::Frames,
+    };
+    let job = Job {
+        input: path_of("input")?.ok_or_else(|| invalid("`input` is required".to_owned()))?,
+        output: path_of("output")?,
+        script: path_of("script")?,
+        cuts_file: path_of("cuts_file")?,
+        cuts: match json.get("cuts") {
+            None | Some(Json::Null) => None,
+            Some(cuts) => Some(read_cuts(cuts)?),
+        },
+        cuts_unit,
+        framerate: match json.get("framerate") {
+            None | Some(Json::Null) => None,
+            Some(Json::String(framerate)) => Some(framerate.clone()),
+            Some(framerate @ Json::Int(_)) | Some(framerate @ Json::Float(_)) => {
+                Some(framerate.to_string())
+            }
+            Some(_) => {
+                return Err(invalid(
+                    "`framerate` must be a string or a number".to_owned(),
+                ))
+            }
+        },
+        delay: match json.get("delay") {
+            None | Some(Json::Null) => None,
+            Some(&Json::Int(delay)) => Some(delay as isize),
+            Some(_) => return Err(invalid("`delay` must be a whole number of ms".to_owned())),
+        },
+        backend: string("backend")?.map(Backend::parse).transpose()?,
+    };
+
+    let sources = [
+        job.script.is_some(),
+        job.cuts_file.is_some(),
+        job.cuts.is_some(),
+    ];
+    if sources.iter().filter(|&&given| given).count() != 1 {
+        return Err(invalid(
+            "exactly one of `script`, `cuts_file` or `cuts` is required".to_owned(),
+        ));
+    }
+    if job.output.is_none() && job.script.is_none() {
+        return Err(invalid(
+            "`output` is required when no script is given".to_owned(),
+        ));
+    }
+    Ok(job)
+}
+
+const KEYS: &[&str] = &[
+    "input",
+    "output",
+    "script",
+    "cuts_file",
+    "cuts",
+    "cuts_unit",
+    "framerate",
+    "delay",
+    "backend",
+];
+
+/// Reads the `cuts` array, numbering each cut from 1 in place of a line number
+fn read_cuts(cuts: &Json) -> Result<Vec<JobCut>, SplitError> {
+    let cuts = match *cuts {
+        Json::Array(ref cuts) => cuts,
+        _ => {
+            return Err(SplitError::InvalidArgument(
+                "`cuts` must be an array of [start, end] pairs".to_owned(),
+            ))
+        }
+    };
+    let mut read = Vec::new();
+    for (i, cut) in cuts.iter().enumerate() {
+        let text = cut.to_string();
+        let invalid = |message: String| SplitError::InvalidTrim {
+            line: i + 1,
+            text: text.clone(),
+            message,
+        };
+        let (start, end) = match *cut {
+            Json::Array(ref values) if values.len() == 2 => (
+                cut_value(&values[0])
+                    .ok_or_else(|| invalid("start must be a number or a string".to_owned()))?,
+                cut_value(&values[1])
+                    .ok_or_else(|| invalid("end must be a number or a string".to_owned()))?,
+            ),
+            _ => return Err(invalid("expected a [start, end] pair".to_owned())),
+        };
+        read.push(JobCut { start, end, text });
+    }
+    Ok(read)
+}
+
+/// Parses a job's cuts counted in `unit`, where `framerate` is what the frame field
+/// of an SMPTE timecode counts in
+pub fn cut_trims(
+    cuts: &[JobCut],
+    unit: CutsUnit,
+    framerate: Rational,
+) -> Result<Vec<Trim>, SplitError> {
+    let mut trims = Vec::new();
+    for (i, cut) in cuts.iter().enumerate() {
+        let (start, end) = parse_cut(&cut.start, &cut.end, unit, framerate).map_err(|message| {
+            SplitError::InvalidTrim {
+                line: i + 1,
+                text: cut.text.clone(),
+                message,
+            }
+        })?;
+        trims.push(Trim {
+            start,
+            end,
+            line: i + 1,
+            text: cut.text.clone(),
+            to_end: false,
+            start_from_end: false,
+            end_from_end: false,
+        });
+    }
+    Ok(trims)
+}
+
+fn cut_value(value: &Json) -> Option<String> {
+    match *value {
+        Json::String(ref value) => Some(value.clone()),
+        Json::Int(_) | Json::Float(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// Loads a job file holding `contents`
+    fn load(name: &str, contents: &str) -> Result<Job, SplitError> {
+        let path = env::temp_dir().join(format!("split_aud-job-{}-{}.json", name, process::id()));
+        fs::write(&path, contents).unwrap();
+        let job = load_job(&path);
+        fs::remove_file(&path).unwrap();
+        job
+    }
+
+    fn invalid_message(result: Result<Job, SplitError>) -> String {
+        match result {
+            Err(SplitError::InvalidArgument(message)) => message,
+            other => panic!("expected an invalid argument, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_a_script_job() {
+        let job = load(
+            "script",
+            r#"{"input": "Ep01.mka", "script": "Ep01.avs", "delay": -50, "framerate": 25}"#,
+        )
+        .unwrap();
+        assert_eq!(job.input, Path::new("Ep01.mka"));
+        assert_eq!(job.script.as_deref(), Some(Path::new("Ep01.avs")));
+        assert_eq!(job.output, None);
+        assert_eq!(job.delay, Some(-50));
+        assert_eq!(job.framerate.as_deref(), Some("25"));
+    }
+
+    #[test]
+    fn input_is_required() {
+        let message = invalid_message(load("no-input", r#"{"script": "Ep01.avs"}"#));
+        assert!(message.contains("`input` is required"), "{}", message);
+        let message = invalid_message(load("no-output", r#"{"input": "a", "cuts": []}"#));
+        assert!(message.contains("`output` is required"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_keys_are_an_error() {
+        let message = invalid_message(load(
+            "unknown-key",
+            r#"{"input": "a", "script": "b", "scirpt": "c"}"#,
+        ));
+        assert!(message.contains("unknown key `scirpt`"), "{}", message);
+    }
+
+    #[test]
+    fn exactly_one_source_of_cuts_is_required() {
+        for contents in &[
+            r#"{"input": "a", "output": "b"}"#,
+            r#"{"input": "a", "output": "b", "script": "c", "cuts_file": "d"}"#,
+            r#"{"input": "a", "output": "b", "cuts_file": "d", "cuts": [[0, 1]]}"#,
+        ] {
+            let message = invalid_message(load("sources", contents));
+            assert!(message.contains("exactly one of"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn cuts_may_be_numbers_or_strings() {
+        let job = load(
+            "cuts",
+            r#"{"input": "a", "output": "b", "cuts_unit": "timecode",
+                "cuts": [[0, 1.5], ["00:00:02.000", "00:00:03.000"]]}"#,
+        )
+        .unwrap();
+        let cuts = job.cuts.unwrap();
+        assert_eq!((cuts[0].start.as_str(), cuts[0].end.as_str()), ("0", "1.5"));
+        assert_eq!(cuts[1].end, "00:00:03.000");
+        assert_eq!(job.cuts_unit, CutsUnit::Timecode);
+        let trims =
+            cut_trims(&cuts[1..], CutsUnit::Timecode, Rational { num: 25, den: 1 }).unwrap();
+        assert_eq!(
+            (trims[0].start, trims[0].end),
+            (2_000_000_000, 3_000_000_000)
+        );
+    }
+
+    #[test]
+    fn a_cut_must_be_a_pair() {
+        let result = load(
+            "cut-pair",
+            r#"{"input": "a", "output": "b", "cuts": [[0, 1], [2, 3, 4]]}"#,
+        );
+        assert!(matches!(
+            result,
+            Err(SplitError::InvalidTrim { line: 2, .. })
+        ));
+        let result = load(
+            "cut-value",
+            r#"{"input": "a", "output": "b", "cuts": [[0, true]]}"#,
+        );
+        assert!(matches!(
+            result,
+            Err(SplitError::InvalidTrim { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn trims_are_read_as_start_and_end() {
+        let cut = JobCut::parse_trim("200 - 300").unwrap();
+        assert_eq!((cut.start.as_str(), cut.end.as_str()), ("200", "300"));
+        assert_eq!(cut.text, "--trim 200 - 300");
+        // The colons and dot of timecodes don't get in the way of the dash between them
+        let cut = JobCut::parse_trim("0:01:00.000-0:02:30.500").unwrap();
+        assert_eq!(
+            (cut.start.as_str(), cut.end.as_str()),
+            ("0:01:00.000", "0:02:30.500")
+        );
+        let trims = cut_trims(&[cut], CutsUnit::Timecode, Rational::NTSC).unwrap();
+        assert_eq!(
+            (trims[0].start, trims[0].end),
+            (60_000_000_000, 150_500_000_000)
+        );
+    }
+
+    #[test]
+    fn trims_need_exactly_one_dash() {
+        for trim in ["100", "0-100-200", "-5-100"] {
+            match JobCut::parse_trim(trim) {
+                Err(SplitError::InvalidArgument(message)) => {
+                    assert_eq!(
+                        message,
+                        format!("Invalid --trim {}, expected START-END", trim)
+                    )
+                }
+                other => panic!("expected {} to be rejected, got {:?}", trim, other),
+            }
+        }
+        // An empty half is caught once the cut is read
+        let cut = JobCut::parse_trim("0:01:00-").unwrap();
+        assert!(cut_trims(&[cut], CutsUnit::Timecode, Rational::NTSC).is_err());
+    }
+}
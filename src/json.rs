@@ -0,0 +1,414 @@
+use std::fmt::{self, Write};
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// A minimal JSON value, enough for the reports this tool writes and the jobs it reads.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object<K: Into<String>>(fields: Vec<(K, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    pub fn string<S: Into<String>>(value: S) -> Json {
+        Json::String(value.into())
+    }
+
+    /// Parses a complete JSON document, describing where it went wrong if it isn't one.
+    pub fn parse(input: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: input.char_indices().peekable(),
+            input,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            Some((pos, _)) => Err(format!("unexpected trailing characters at {}", pos)),
+            None => Ok(value),
+        }
+    }
+
+    /// The value of `key` when this is an object that has it
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref fields) => fields
+                .iter()
+                .find(|(field, _)| field == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0).unwrap();
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, indent: usize) -> fmt::Result {
+        match *self {
+            Json::Array(ref items) if !items.is_empty() => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    write_indent(out, indent + 1);
+                    item.write_pretty(out, indent + 1)?;
+                    if i + 1 < items.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                write_indent(out, indent);
+                out.push(']');
+                Ok(())
+            }
+            Json::Object(ref fields) if !fields.is_empty() => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    write_indent(out, indent + 1);
+                    write_string(out, key)?;
+                    out.push_str(": ");
+                    value.write_pretty(out, indent + 1)?;
+                    if i + 1 < fields.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                write_indent(out, indent);
+                out.push('}');
+                Ok(())
+            }
+            _ => write!(out, "{}", self),
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    input: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((pos, c)) => Err(format!("expected `{}` at {}, found `{}`", expected, pos, c)),
+            None => Err(format!(
+                "expected `{}`, found the end of the input",
+                expected
+            )),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some((_, '{')) => self.parse_object(),
+            Some((_, '[')) => self.parse_array(),
+            Some((_, '"')) => self.parse_string().map(Json::String),
+            Some((_, c)) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some((pos, c)) if c.is_ascii_alphabetic() => {
+                let word = self.take_while(|c| c.is_ascii_alphabetic());
+                match word {
+                    "null" => Ok(Json::Null),
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    _ => Err(format!("unexpected `{}` at {}", word, pos)),
+                }
+            }
+            Some((pos, c)) => Err(format!("unexpected `{}` at {}", c, pos)),
+            None => Err("expected a value, found the end of the input".to_owned()),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if let Some(&(_, '}')) = self.chars.peek() {
+            self.chars.next();
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            fields.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, '}')) => return Ok(Json::Object(fields)),
+                Some((pos, c)) => {
+                    return Err(format!("expected `,` or `}}` at {}, found `{}`", pos, c))
+                }
+                None => return Err("unterminated object".to_owned()),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if let Some(&(_, ']')) = self.chars.peek() {
+            self.chars.next();
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, ']')) => return Ok(Json::Array(items)),
+                Some((pos, c)) => {
+                    return Err(format!("expected `,` or `]` at {}, found `{}`", pos, c))
+                }
+                None => return Err("unterminated array".to_owned()),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(value),
+                Some((pos, '\\')) => match self.chars.next() {
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    Some((_, '/')) => value.push('/'),
+                    Some((_, 'b')) => value.push('\u{8}'),
+                    Some((_, 'f')) => value.push('\u{c}'),
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'u')) => {
+                        let invalid = || format!("invalid unicode escape at {}", pos);
+                        let mut code = self.parse_hex4().ok_or_else(invalid)?;
+                        // Characters outside the BMP are written as a UTF-16 surrogate
+                        // pair, like `\uD83D\uDE00`
+                        if (0xD800..0xDC00).contains(&code) {
+                            let low = match (self.chars.next(), self.chars.next()) {
+                                (Some((_, '\\')), Some((_, 'u'))) => self.parse_hex4(),
+                                _ => None,
+                            }
+                            .filter(|low| (0xDC00..0xE000).contains(low))
+                            .ok_or_else(invalid)?;
+                            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                        }
+                        value.push(char::from_u32(code).ok_or_else(invalid)?);
+                    }
+                    _ => return Err(format!("invalid escape at {}", pos)),
+                },
+                Some((_, c)) => value.push(c),
+                None => return Err("unterminated string".to_owned()),
+            }
+        }
+    }
+
+    /// Reads the four hex digits of a `\u` escape
+    fn parse_hex4(&mut self) -> Option<u32> {
+        let hex: String = (0..4)
+            .filter_map(|_| self.chars.next())
+            .map(|(_, c)| c)
+            .collect();
+        if hex.len() != 4 {
+            return None;
+        }
+        u32::from_str_radix(&hex, 16).ok()
+    }
+
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let pos = self.chars.peek().map_or(self.input.len(), |&(pos, _)| pos);
+        let number = self.take_while(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+        if let Ok(value) = number.parse::<i64>() {
+            return Ok(Json::Int(value));
+        }
+        number
+            .parse::<f64>()
+            .map(Json::Float)
+            .map_err(|_| format!("invalid number `{}` at {}", number, pos))
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, accept: F) -> &'a str {
+        let start = self.chars.peek().map_or(self.input.len(), |&(pos, _)| pos);
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !accept(c) {
+                break;
+            }
+            self.chars.next();
+        }
+        let end = self.chars.peek().map_or(self.input.len(), |&(pos, _)| pos);
+        &self.input[start..end]
+    }
+}
+
+fn write_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str("  ");
+    }
+}
+
+fn write_string<W: Write>(out: &mut W, value: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Int(value) => write!(f, "{}", value),
+            Json::Float(value) if value.is_finite() => write!(f, "{}", value),
+            Json::Float(_) => f.write_str("null"),
+            Json::String(ref value) => write_string(f, value),
+            Json::Array(ref items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(ref fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Json {
+        Json::Int(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json {
+        Json::Int(value as i64)
+    }
+}
+
+impl From<isize> for Json {
+    fn from(value: isize) -> Json {
+        Json::Int(value as i64)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Json {
+        Json::Float(value)
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl<'a> From<&'a str> for Json {
+    fn from(value: &'a str) -> Json {
+        Json::String(value.to_owned())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(value: Vec<T>) -> Json {
+        Json::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_escapes_including_surrogate_pairs() {
+        assert_eq!(
+            Json::parse(r#""a\"b\\c\n\u00e9\uD83D\uDE00""#),
+            Ok(Json::string("a\"b\\c\né\u{1F600}"))
+        );
+        // Python's json.dumps writes anything outside ASCII this way
+        assert_eq!(
+            Json::parse(r#"{"input": "\ud83c\udfb5 Ep01.mka"}"#)
+                .unwrap()
+                .get("input"),
+            Some(&Json::string("\u{1F3B5} Ep01.mka"))
+        );
+    }
+
+    #[test]
+    fn rejects_a_lone_surrogate() {
+        assert!(Json::parse(r#""\uD83D""#).is_err());
+        assert!(Json::parse(r#""\uD83Dx""#).is_err());
+        assert!(Json::parse(r#""\uD83DA""#).is_err());
+        assert!(Json::parse(r#""\uDE00""#).is_err());
+        assert!(Json::parse(r#""\u00""#).is_err());
+    }
+
+    #[test]
+    fn round_trips_what_it_writes() {
+        let json = Json::object(vec![
+            ("name", Json::string("a \"quoted\"\tname")),
+            ("values", vec![1i64, -2].into()),
+            ("ratio", 0.5.into()),
+            ("none", Json::Null),
+        ]);
+        assert_eq!(Json::parse(&json.to_string()), Ok(json.clone()));
+        assert_eq!(Json::parse(&json.to_pretty_string()), Ok(json));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::error::SplitError;
+use crate::trims::Trim;
+
+/// The first line of an Aegisub keyframes file
+const KEYFRAMES_HEADER: &str = "# keyframe format v1";
+
+/// Reads an Aegisub keyframes file into one trim for each stretch between keyframes,
+/// with the last running to the end of the audio.
+///
+/// The file starts with `# keyframe format v1` and an `fps` line, which is ignored in
+/// favour of the framerate in use, followed by one frame number per line.
+pub fn parse_keyframes_file(contents: &str) -> Result<Vec<Trim>, SplitError> {
+    let mut lines = contents.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.trim() == KEYFRAMES_HEADER => (),
+        _ => {
+            return Err(SplitError::InvalidArgument(format!(
+                "Not an Aegisub keyframes file, expected it to start with `{}`",
+                KEYFRAMES_HEADER
+            )))
+        }
+    }
+
+    let mut keyframes: Vec<(usize, usize)> = Vec::new();
+    for (i, line) in lines {
+        let text = line.trim();
+        if text.is_empty() || text.starts_with('#') || text.starts_with("fps ") {
+            continue;
+        }
+        let frame = text.parse::<usize>().map_err(|_| SplitError::InvalidTrim {
+            line: i + 1,
+            text: text.to_owned(),
+            message: "expected a frame number".to_owned(),
+        })?;
+        if let Some(&(_, previous)) = keyframes.last() {
+            if frame <= previous {
+                return Err(SplitError::InvalidTrim {
+                    line: i + 1,
+                    text: text.to_owned(),
+                    message: format!("keyframe {} is not after keyframe {}", frame, previous),
+                });
+            }
+        }
+        keyframes.push((i + 1, frame));
+    }
+
+    Ok(keyframes
+        .iter()
+        .enumerate()
+        .map(|(i, &(line, start))| match keyframes.get(i + 1) {
+            Some(&(_, end)) => Trim {
+                start,
+                end,
+                line,
+                text: format!("keyframes {} to {}", start, end),
+                to_end: false,
+                start_from_end: false,
+                end_from_end: false,
+            },
+            None => Trim {
+                start,
+                end: 0,
+                line,
+                text: format!("keyframe {} to the end", start),
+                to_end: true,
+                start_from_end: false,
+                end_from_end: false,
+            },
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(trims: &[Trim]) -> Vec<(usize, usize, bool)> {
+        trims
+            .iter()
+            .map(|trim| (trim.start, trim.end, trim.to_end))
+            .collect()
+    }
+
+    #[test]
+    fn keyframes_become_the_stretches_between_them() {
+        let trims =
+            parse_keyframes_file("# keyframe format v1\nfps 23.976\n0\n240\n1000\n").unwrap();
+        assert_eq!(
+            ranges(&trims),
+            [(0, 240, false), (240, 1000, false), (1000, 0, true)]
+        );
+        assert_eq!(trims[0].line, 3);
+        assert_eq!(trims[2].text, "keyframe 1000 to the end");
+    }
+
+    #[test]
+    fn the_fps_line_may_be_left_out() {
+        // As with `fps 0`, which Aegisub writes when it doesn't know the rate
+        let without = parse_keyframes_file("# keyframe format v1\r\n0\r\n\r\n240\r\n").unwrap();
+        let with = parse_keyframes_file("# keyframe format v1\nfps 0\n0\n240\n").unwrap();
+        assert_eq!(ranges(&without), [(0, 240, false), (240, 0, true)]);
+        assert_eq!(ranges(&without), ranges(&with));
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(matches!(
+            parse_keyframes_file("0\n240\n"),
+            Err(SplitError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            parse_keyframes_file(""),
+            Err(SplitError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn bad_lines_are_reported_with_their_line_number() {
+        match parse_keyframes_file("# keyframe format v1\nfps 0\n0\n24o\n") {
+            Err(SplitError::InvalidTrim { line, text, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(text, "24o");
+            }
+            other => panic!("expected a bad line, got {:?}", other),
+        }
+        match parse_keyframes_file("# keyframe format v1\n240\n# a comment\n240\n") {
+            Err(SplitError::InvalidTrim { line, message, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(message, "keyframe 240 is not after keyframe 240");
+            }
+            other => panic!("expected keyframes out of order, got {:?}", other),
+        }
+    }
+}
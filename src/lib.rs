@@ -0,0 +1,4676 @@
+//! Keeps the parts of an audio file that an AviSynth or VapourSynth script trims to,
+//! without re-encoding it. [`split_audio`] runs a split from a [`Config`], which the
+//! `split_aud` binary builds from its flags, and [`trims::parse_trims`] reads the
+//! trims of a script on its own.
+
+#![warn(clippy::all)]
+
+pub mod backend;
+pub mod capabilities;
+pub mod captions;
+pub mod color;
+pub mod config_file;
+pub mod cuts_file;
+pub mod doctor;
+pub mod edl;
+pub mod error;
+pub mod expand;
+pub mod ffmpeg;
+pub mod ffprobe;
+pub mod framerate;
+pub mod hook;
+pub mod job;
+pub mod json;
+pub mod keyframes;
+pub mod marker;
+pub mod mediainfo;
+pub mod mkvmerge;
+pub mod output_name;
+pub mod pairs;
+pub mod report;
+pub mod segments;
+pub mod shell;
+pub mod stage;
+pub mod timecode;
+pub mod timings;
+pub mod trims;
+
+use crate::backend::{Backend, DelayMode, SplitBackend};
+use crate::capabilities::{check_ffprobe, check_mkvmerge};
+use crate::captions::{write_markers, CaptionFormat};
+use crate::color::{paint, ColorMode};
+use crate::cuts_file::{parse_cuts_file, CutsFileMode, CutsUnit};
+use crate::doctor::run_doctor;
+use crate::edl::write_edl;
+use crate::error::{warn, SplitError};
+use crate::ffmpeg::{check_copy_container, Transcode};
+use crate::ffprobe::{
+    probe_audio_streams, probe_chapters, probe_codec, probe_duration, probe_packet_times,
+    probe_sample_format, probe_start_time, probe_tag, probe_video_framerate, DurationSource,
+    SampleFormat,
+};
+use crate::framerate::{
+    detect_script_framerate, FramerateSource, Rational, ScriptFramerate,
+    DEFAULT_FRAMERATE_TOLERANCE,
+};
+use crate::hook::run_post_hook;
+use crate::job::{cut_trims, JobCut};
+use crate::json::Json;
+use crate::keyframes::parse_keyframes_file;
+use crate::marker::{read_marker, trims_hash, write_marker, Marker};
+use crate::mkvmerge::{identify, mkvmerge_version, run_mkvmerge, MkvmergeVersion};
+use crate::pairs::find_pairs;
+use crate::report::{Report, ReportTrim, ReportVerify};
+use crate::segments::{absorb_small_gaps, plan_segments, FirstSegment, Segment, SegmentPlan};
+use crate::shell::command_line;
+use crate::stage::{Cleanup, Stage};
+use crate::timecode::{format_nanos, frame_to_nanos, frame_to_timecode, parse_timecode};
+use crate::trims::{
+    format_avs_trims, has_statements, parse_trims, parse_trims_comment, select_edit,
+    uses_audio_dub, Trim,
+};
+use regex::Regex;
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub framerate: Rational,
+    pub framerate_source: FramerateSource,
+    pub audio_framerate: Option<Rational>,
+    pub framerate_from_script: bool,
+    /// A video to read the framerate from with ffprobe, for `--fps-from`
+    pub fps_from: Option<PathBuf>,
+    pub no_default_framerate: bool,
+    pub input_aud: PathBuf,
+    pub output_aud: PathBuf,
+    pub input_avs: Option<PathBuf>,
+    pub cuts_file: Option<PathBuf>,
+    pub cuts: Option<Vec<JobCut>>,
+    pub cuts_unit: CutsUnit,
+    pub cuts_file_mode: CutsFileMode,
+    pub split_at_chapters: bool,
+    pub cuts_tag: Option<String>,
+    pub keyframes_file: Option<PathBuf>,
+    pub pair_dir: Option<PathBuf>,
+    /// `(script, audio)` pairs for `--compile`, joined in this order
+    pub compile: Vec<(PathBuf, PathBuf)>,
+    pub post_hook: Option<String>,
+    pub summary_json: bool,
+    pub incremental: bool,
+    pub also_split: Option<PathBuf>,
+    pub mkvmerge: PathBuf,
+    pub ffprobe: PathBuf,
+    pub ffmpeg: PathBuf,
+    pub backend: Backend,
+    /// Splits and joins in place of `backend`, for library users with a tool of their own
+    pub custom_backend: Option<Arc<dyn SplitBackend>>,
+    pub copy_codec: bool,
+    pub transcode: Option<Transcode>,
+    pub stream: bool,
+    pub snap_to_packet: bool,
+    pub timestamp_scale: Option<u64>,
+    pub deterministic: bool,
+    pub delay: Option<isize>,
+    pub delay_mode: DelayMode,
+    pub delay_log: Option<PathBuf>,
+    pub edit_compensation: bool,
+    pub normalize_delay: bool,
+    pub fields: bool,
+    pub invert: bool,
+    pub no_merge: bool,
+    pub name_template: String,
+    pub keep_indices: Option<Vec<usize>>,
+    pub widen_short_trims: bool,
+    pub max_gap_merge: Option<usize>,
+    pub end_tolerance: Option<usize>,
+    pub first_segment: FirstSegment,
+    pub start_frame: usize,
+    pub trim_regex: Option<Regex>,
+    pub trim_end_is_count: bool,
+    pub edit: Option<String>,
+    pub trims_keyword: String,
+    pub passthrough_on_no_trims: bool,
+    pub reference_video: Option<PathBuf>,
+    pub duration_tolerance: f64,
+    pub duration_source: DurationSource,
+    pub audio_track: Option<usize>,
+    /// Copies every track of the input with mkvmerge whatever comes first, as
+    /// `--also-split` does for the video it cuts alongside the audio
+    pub all_tracks: bool,
+    pub strict: bool,
+    pub warn_as_error: bool,
+    pub force: bool,
+    pub mark_output: bool,
+    pub verify: bool,
+    pub preview: bool,
+    pub report: Option<PathBuf>,
+    pub output_edl: Option<PathBuf>,
+    pub output_markers: Option<(PathBuf, CaptionFormat)>,
+    pub output_avs_trims: Option<PathBuf>,
+    pub command_log: Option<PathBuf>,
+    /// The directory `--temp-dir` makes each run's work directory in, rather than next
+    /// to the output
+    pub temp_dir: Option<PathBuf>,
+    /// Where this run writes its split pieces and other intermediates, a directory of
+    /// its own
+    pub work_dir: PathBuf,
+    pub timings: bool,
+    pub mkdir: bool,
+    pub preserve_mtime: bool,
+    pub list_segments: bool,
+    pub run: bool,
+    pub dump_plan: bool,
+    pub dry_run: bool,
+    pub stop_after: Stage,
+    pub cleanup: Cleanup,
+    /// Keeps the work directory and every intermediate in it, for `--keep-temp`
+    pub keep_temp: bool,
+    pub interactive: bool,
+    pub yes: bool,
+    pub print_framerate: bool,
+    pub audio_info: bool,
+    pub identify: bool,
+    pub dump_script: bool,
+    pub print_trims: bool,
+    pub color: ColorMode,
+    pub doctor: bool,
+    pub verbose: bool,
+    pub quiet: bool,
+}
+
+/// The settings of a run that no flag or config file has changed
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            framerate: Rational::NTSC,
+            framerate_source: FramerateSource::Default,
+            audio_framerate: None,
+            framerate_from_script: false,
+            fps_from: None,
+            no_default_framerate: false,
+            input_aud: PathBuf::new(),
+            output_aud: PathBuf::new(),
+            input_avs: None,
+            cuts_file: None,
+            cuts: None,
+            cuts_unit: CutsUnit::Frames,
+            cuts_file_mode: CutsFileMode::Range,
+            split_at_chapters: false,
+            cuts_tag: None,
+            keyframes_file: None,
+            pair_dir: None,
+            compile: Vec::new(),
+            post_hook: None,
+            summary_json: false,
+            incremental: false,
+            also_split: None,
+            mkvmerge: PathBuf::from("mkvmerge"),
+            ffprobe: PathBuf::from("ffprobe"),
+            ffmpeg: PathBuf::from("ffmpeg"),
+            backend: Backend::Mkvmerge,
+            custom_backend: None,
+            copy_codec: false,
+            transcode: None,
+            stream: false,
+            snap_to_packet: false,
+            timestamp_scale: None,
+            deterministic: false,
+            delay: None,
+            delay_mode: DelayMode::Sync,
+            delay_log: None,
+            edit_compensation: true,
+            normalize_delay: false,
+            fields: false,
+            invert: false,
+            no_merge: false,
+            name_template: "{index}".to_owned(),
+            keep_indices: None,
+            widen_short_trims: false,
+            max_gap_merge: None,
+            end_tolerance: None,
+            first_segment: FirstSegment::Auto,
+            start_frame: 0,
+            trim_regex: None,
+            trim_end_is_count: false,
+            edit: None,
+            trims_keyword: "TRIMS".to_owned(),
+            passthrough_on_no_trims: false,
+            reference_video: None,
+            duration_tolerance: 3f64,
+            duration_source: DurationSource::Auto,
+            audio_track: None,
+            all_tracks: false,
+            strict: false,
+            warn_as_error: false,
+            force: false,
+            mark_output: false,
+            verify: false,
+            preview: false,
+            report: None,
+            output_edl: None,
+            output_markers: None,
+            output_avs_trims: None,
+            command_log: None,
+            keep_temp: false,
+            temp_dir: None,
+            work_dir: PathBuf::new(),
+            timings: false,
+            mkdir: true,
+            preserve_mtime: false,
+            list_segments: false,
+            dump_plan: false,
+            dry_run: false,
+            run: false,
+            stop_after: Stage::Merge,
+            cleanup: Cleanup::OnSuccess,
+            interactive: false,
+            yes: false,
+            print_framerate: false,
+            audio_info: false,
+            identify: false,
+            dump_script: false,
+            print_trims: false,
+            color: ColorMode::Auto,
+            doctor: false,
+            verbose: false,
+            quiet: false,
+        }
+    }
+}
+
+/// The rate that trim numbers are counted in
+fn timing_framerate(opts: &Config) -> Rational {
+    if opts.fields {
+        opts.framerate.doubled()
+    } else {
+        opts.framerate
+    }
+}
+
+/// Which audio stream of the input is split and probed, counting from 0
+fn audio_track(opts: &Config) -> usize {
+    opts.audio_track.unwrap_or(0)
+}
+
+/// The ffmpeg `-map` for the audio stream being split
+fn audio_map(opts: &Config) -> String {
+    format!("0:a:{}", audio_track(opts))
+}
+
+/// Finds a `Delay: -50ms` style line in a log, like the one eac3to writes
+fn parse_delay_log(contents: &str) -> Option<isize> {
+    static LOG_DELAY_REGEX: OnceLock<Regex> = OnceLock::new();
+    let log_delay_regex = LOG_DELAY_REGEX
+        .get_or_init(|| Regex::new(r"(?i)\bdelay\s*[:=]?\s*([+-]?\d+)\s*ms").unwrap());
+    log_delay_regex
+        .captures_iter(contents)
+        .filter_map(|captures| captures[1].parse::<isize>().ok())
+        .last()
+}
+
+/// Logs next to the audio that might state its delay: `Ep01.log`, `Ep01.txt`,
+/// and eac3to's `Ep01 - Log.txt`
+fn delay_log_candidates(audio: &Path) -> Vec<PathBuf> {
+    let stem = audio
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    vec![
+        audio.with_extension("log"),
+        audio.with_extension("txt"),
+        audio.with_file_name(format!("{} - Log.txt", stem)),
+    ]
+}
+
+/// Returns the delay to apply and where it came from.
+///
+/// In order of precedence: `--delay`, then `--delay-log`, then a `DELAY -50ms` marker
+/// in the audio's filename, then a log with the same basename as the audio, then the
+/// container's start time compensation, then 0.
+/// When the filename has more than one marker, the first one is used.
+fn detect_delay(opts: &Config) -> Result<(isize, &'static str), SplitError> {
+    // An explicit delay always wins over anything we could detect
+    if let Some(delay) = opts.delay {
+        return Ok((delay, "--delay"));
+    }
+
+    if let Some(ref log) = opts.delay_log {
+        let contents = fs::read_to_string(log).map_err(|e| SplitError::Io(log.clone(), e))?;
+        return match parse_delay_log(&contents) {
+            Some(delay) => Ok((delay, "--delay-log")),
+            None => Err(SplitError::InvalidArgument(format!(
+                "No delay found in {}",
+                log.display()
+            ))),
+        };
+    }
+
+    static DELAY_REGEX: OnceLock<Regex> = OnceLock::new();
+    let delay_regex = DELAY_REGEX.get_or_init(|| Regex::new(r"DELAY (-?\d+)ms").unwrap());
+    let name = opts.input_aud.to_string_lossy();
+    let markers: Vec<isize> = delay_regex
+        .captures_iter(&name)
+        .map(|captures| {
+            captures[1].parse::<isize>().map_err(|_| {
+                SplitError::InvalidArgument(format!(
+                    "The DELAY {}ms marker in the filename is out of range",
+                    &captures[1]
+                ))
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    if let Some(&delay) = markers.first() {
+        if markers.len() > 1 {
+            let found: Vec<String> = markers.iter().map(|m| format!("{}ms", m)).collect();
+            warn(
+                opts.warn_as_error,
+                format!(
+                    "found more than one DELAY marker in the filename ({}), \
+                     using the first, {}ms. Pass --delay to use another",
+                    found.join(", "),
+                    delay
+                ),
+            )?;
+        }
+        return Ok((delay, "filename"));
+    }
+
+    for log in delay_log_candidates(&opts.input_aud) {
+        if let Some(delay) = fs::read_to_string(&log)
+            .ok()
+            .and_then(|contents| parse_delay_log(&contents))
+        {
+            eprintln!("Using the delay from {}", log.display());
+            return Ok((delay, "log"));
+        }
+    }
+
+    if opts.edit_compensation {
+        let compensation = edit_compensation(opts)?;
+        if compensation != 0 {
+            return Ok((compensation, "start_time"));
+        }
+    }
+
+    Ok((0isize, "default"))
+}
+
+/// The delay that undoes an encoder delay or edit list, in milliseconds.
+///
+/// Some sources (notably AAC in MP4) report a non-zero start time for the audio
+/// stream because of priming samples. Splitting by wall-clock time ignores this, so
+/// the start time is used as a negative sync offset to line the audio back up, when
+/// nothing else gave a delay.
+fn edit_compensation(opts: &Config) -> Result<isize, SplitError> {
+    let start_time = match probe_start_time(&opts.ffprobe, &opts.input_aud, audio_track(opts)) {
+        Ok(start_time) => start_time.unwrap_or(0f64),
+        Err(SplitError::ProcessNotFound(_)) => {
+            warn(
+                opts.warn_as_error,
+                "ffprobe not found, not checking for an edit list".to_owned(),
+            )?;
+            return Ok(0);
+        }
+        Err(e) => return Err(e),
+    };
+    let compensation = (-start_time * 1000f64).round() as isize;
+    if compensation != 0 {
+        eprintln!(
+            "Audio starts at {:.3}s, adjusting delay by {}ms",
+            start_time, compensation
+        );
+    }
+    Ok(compensation)
+}
+
+/// Rounds a delay to the nearest whole number of frames, for `--normalize-delay`
+fn normalize_delay(delay: isize, framerate: Rational) -> isize {
+    let frame_ms = 1000f64 * framerate.den as f64 / framerate.num as f64;
+    let frames = (delay as f64 / frame_ms).round();
+    let normalized = (frames * frame_ms).round() as isize;
+    if normalized != delay {
+        eprintln!(
+            "Rounding the delay from {}ms to {}ms, the nearest whole frame at {}",
+            delay, normalized, framerate
+        );
+    }
+    normalized
+}
+
+fn read_script(path: &Path) -> Result<String, SplitError> {
+    let mut avs_file = File::open(path).map_err(|e| SplitError::Script(path.to_path_buf(), e))?;
+    let mut avs_contents = String::new();
+    avs_file
+        .read_to_string(&mut avs_contents)
+        .map_err(|e| SplitError::Script(path.to_path_buf(), e))?;
+    Ok(avs_contents)
+}
+
+/// Reads the script and narrows it to the `--edit` being split, giving the text the
+/// trim patterns run against
+fn script_for_trims(opts: &Config) -> Result<String, SplitError> {
+    let avs_contents = read_script(opts.input_avs.as_ref().unwrap())?;
+    if avs_contents.trim().is_empty() {
+        return Err(SplitError::EmptyScript);
+    }
+    match opts.edit {
+        Some(ref name) => select_edit(&avs_contents, name),
+        None => Ok(avs_contents),
+    }
+}
+
+/// Finds the trims in the script, and says what was searched for in case there are none
+fn script_trims(
+    opts: &Config,
+    framerate: Rational,
+) -> Result<(Vec<Trim>, &'static str), SplitError> {
+    let avs_contents = script_for_trims(opts)?;
+    if uses_audio_dub(&avs_contents) {
+        warn(
+            opts.warn_as_error,
+            format!(
+                "the script uses AudioDub, so its audio may not match the video. \
+                 Trims are cut at {} fps, check that this is right for {}",
+                framerate,
+                opts.input_aud.display()
+            ),
+        )?;
+    }
+    if opts.verbose {
+        let lines = avs_contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .count();
+        eprintln!("Scanned {} non-comment lines for trims", lines);
+    }
+    // A list of ranges in a comment is the source of truth for the code below it
+    if let Some(trims) = parse_trims_comment(&avs_contents, &opts.trims_keyword)? {
+        if opts.verbose {
+            eprintln!(
+                "Using the ranges from the `# {}:` comment",
+                opts.trims_keyword
+            );
+        }
+        Ok((trims, "the trims comment"))
+    } else {
+        let checked = if opts.trim_regex.is_some() {
+            "--trim-regex"
+        } else {
+            "AviSynth Trim and Python slice syntax"
+        };
+        let trims = parse_trims(
+            &avs_contents,
+            opts.trim_regex.as_ref(),
+            opts.trim_end_is_count,
+            opts.warn_as_error,
+        )?;
+        // Nothing but comments usually means the wrong file, rather than a script
+        // that has no trims
+        if trims.is_empty() && !has_statements(&avs_contents) {
+            return Err(SplitError::NoStatements);
+        }
+        Ok((trims, checked))
+    }
+}
+
+/// Picks up the framerate of the `--fps-from` video, or one declared in the script,
+/// when none was given on the command line
+pub fn resolve_framerate(opts: &mut Config) -> Result<(), SplitError> {
+    if let FramerateSource::Argument | FramerateSource::MediaInfo | FramerateSource::Video =
+        opts.framerate_source
+    {
+        return Ok(());
+    }
+    if let Some(video) = opts.fps_from.clone() {
+        opts.framerate = video_framerate(opts, &video)?;
+        opts.framerate_source = FramerateSource::Video;
+        return Ok(());
+    }
+    let script = match opts.input_avs {
+        Some(ref path) => read_script(path)?,
+        None if opts.framerate_from_script => return Err(SplitError::NoFramerate),
+        None => return default_framerate(opts),
+    };
+    match detect_script_framerate(&script) {
+        Some(ScriptFramerate {
+            framerate,
+            resampled_by,
+        }) => {
+            if let Some(call) = resampled_by {
+                warn(
+                    opts.warn_as_error,
+                    format!(
+                        "the script resamples its frames with {}, so trims are cut at \
+                         the converted rate of {}. Pass --framerate if the trims count \
+                         frames of the clip before the conversion",
+                        call, framerate
+                    ),
+                )?;
+            }
+            opts.framerate = framerate;
+            opts.framerate_source = FramerateSource::Script;
+        }
+        None if opts.framerate_from_script => return Err(SplitError::NoFramerate),
+        None => return default_framerate(opts),
+    }
+    Ok(())
+}
+
+/// The frame rate ffprobe gives the first video stream of `video`
+fn video_framerate(opts: &Config, video: &Path) -> Result<Rational, SplitError> {
+    let rate = probe_video_framerate(&opts.ffprobe, video)?.ok_or_else(|| {
+        SplitError::InvalidArgument(format!("No video stream found in {}", video.display()))
+    })?;
+    // ffprobe gives 0/0 when it can't tell the rate, which Rational refuses
+    Some(&rate)
+        .filter(|rate| rate.contains('/'))
+        .and_then(|rate| Rational::parse(rate, DEFAULT_FRAMERATE_TOLERANCE).ok())
+        .ok_or_else(|| {
+            SplitError::InvalidArgument(format!(
+                "ffprobe gives {} a frame rate of `{}`, pass --framerate instead",
+                video.display(),
+                rate
+            ))
+        })
+}
+
+/// Falls back to the default framerate, which is wrong for anything that isn't NTSC,
+/// so say so unless the trims are counted in time rather than frames
+fn default_framerate(opts: &Config) -> Result<(), SplitError> {
+    // Cuts in seconds, timecodes or chapters don't count frames at all
+    if opts.input_avs.is_none() && (opts.cuts_unit != CutsUnit::Frames || opts.split_at_chapters) {
+        return Ok(());
+    }
+    if opts.no_default_framerate {
+        return Err(match opts.input_avs {
+            Some(_) => SplitError::NoFramerate,
+            None => SplitError::InvalidArgument(
+                "No framerate given, pass one with --framerate".to_owned(),
+            ),
+        });
+    }
+    if !opts.quiet {
+        warn(
+            opts.warn_as_error,
+            format!(
+                "no framerate given, assuming {}. Pass --framerate or add AssumeFPS \
+                 to the script if that's wrong",
+                opts.framerate
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// How many frames at `framerate` the input audio lasts, counting a partial frame at the end
+pub fn get_total_frames(opts: &Config, framerate: Rational) -> Result<usize, SplitError> {
+    let probe = |source| probe_duration(&opts.ffprobe, &opts.input_aud, source, audio_track(opts));
+    let duration = match (opts.duration_source, opts.audio_track) {
+        // A track that doesn't exist would otherwise get the container's duration
+        (source, Some(track)) => {
+            let count = probe_audio_streams(&opts.ffprobe, &opts.input_aud)?.len();
+            if track >= count {
+                return Err(track_out_of_range(opts, track, count));
+            }
+            match source {
+                // The container lasts as long as its longest stream, which may not be
+                // the chosen one, so that track's own duration comes first
+                DurationSource::Auto => {
+                    probe(DurationSource::Stream).or_else(|_| probe(DurationSource::Format))?
+                }
+                source => probe(source)?,
+            }
+        }
+        (source, None) => probe(source)?,
+    };
+    Ok(frames_in_duration(duration, framerate))
+}
+
+/// How many frames at `framerate` last `duration` seconds, counting a partial frame at
+/// the end, which is how [`get_total_frames`] counts the input's
+pub fn frames_in_duration(duration: f64, framerate: Rational) -> usize {
+    (duration * framerate.num as f64 / framerate.den as f64).ceil() as usize
+}
+
+/// The error for an `--audio-track` past the `count` audio tracks of the input
+fn track_out_of_range(opts: &Config, track: usize, count: usize) -> SplitError {
+    SplitError::InvalidArgument(format!(
+        "--audio-track {} is out of range, {} has {} audio tracks",
+        track,
+        opts.input_aud.display(),
+        count
+    ))
+}
+
+/// The ranges listed in the input's `--cuts-from-tags` tag, like `100-200,300-400`,
+/// each read like a `--trim` and counted in the `--cuts-unit`
+fn tag_trims(opts: &Config, framerate: Rational) -> Result<Vec<Trim>, SplitError> {
+    let name = opts.cuts_tag.as_ref().unwrap();
+    let value = match probe_tag(&opts.ffprobe, &opts.input_aud, name)? {
+        Some(value) if !value.trim().is_empty() => value,
+        _ => {
+            return Err(SplitError::InvalidArgument(format!(
+                "{} has no {} tag to read cuts from",
+                opts.input_aud.display(),
+                name
+            )))
+        }
+    };
+    let mut cuts = Vec::new();
+    for (i, range) in value.split(',').map(str::trim).enumerate() {
+        let text = range.to_owned();
+        match range.split_once('-') {
+            Some((start, end)) if !start.trim().is_empty() && !end.trim().is_empty() => {
+                cuts.push(JobCut {
+                    start: start.trim().to_owned(),
+                    end: end.trim().to_owned(),
+                    text,
+                })
+            }
+            _ => {
+                return Err(SplitError::InvalidTrim {
+                    line: i + 1,
+                    text,
+                    message: format!("expected START-END in the {} tag", name),
+                })
+            }
+        }
+    }
+    cut_trims(&cuts, opts.cuts_unit, framerate)
+}
+
+/// One trim per chapter of the input, counted in nanoseconds, for `--split-at-chapters`
+fn chapter_trims(opts: &Config) -> Result<Vec<Trim>, SplitError> {
+    let chapters = probe_chapters(&opts.ffprobe, &opts.input_aud)?;
+    let mut trims = Vec::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        let text = match chapter.title {
+            Some(ref title) => format!("chapter {} ({})", i + 1, title),
+            None => format!("chapter {}", i + 1),
+        };
+        let (start, end) = match (usize::try_from(chapter.start), usize::try_from(chapter.end)) {
+            (Ok(start), Ok(end)) if start <= end => (start, end),
+            _ => {
+                return Err(SplitError::InvalidTrim {
+                    line: i + 1,
+                    text,
+                    message: "the chapter ends before it starts".to_owned(),
+                })
+            }
+        };
+        trims.push(Trim {
+            start,
+            end,
+            line: i + 1,
+            text,
+            to_end: false,
+            start_from_end: false,
+            end_from_end: false,
+        });
+    }
+    Ok(trims)
+}
+
+/// Moves the end of the trim that ends last to `total_frames` when it is within
+/// `tolerance` frames of it either way, for `--end-tolerance-frames`
+fn snap_to_end(trims: &mut [Trim], total_frames: usize, tolerance: usize) {
+    let last = match trims.iter_mut().max_by_key(|trim| trim.end) {
+        Some(last) => last,
+        None => return,
+    };
+    if last.end != total_frames && last.end.abs_diff(total_frames) <= tolerance {
+        eprintln!(
+            "Trim `{}` on line {} ends {} frames from the end of the audio, moving it to the end",
+            last.text,
+            last.line,
+            last.end.abs_diff(total_frames)
+        );
+        last.end = total_frames;
+    }
+}
+
+/// Warns about trims that run past the end of the audio, which mkvmerge would
+/// quietly cut short, and drops the ones that start after it ends.
+fn check_trims_in_audio(
+    opts: &Config,
+    trims: &mut Vec<Trim>,
+    total_frames: usize,
+) -> Result<(), SplitError> {
+    for trim in trims.iter().filter(|trim| trim.end > total_frames) {
+        let message = format!(
+            "Trim `{}` on line {} runs to frame {}, but the audio only has {} frames",
+            trim.text,
+            trim.line,
+            trim.end - 1,
+            total_frames
+        );
+        if opts.strict {
+            return Err(SplitError::CheckFailed(message));
+        }
+        if trim.start >= total_frames {
+            warn(opts.warn_as_error, format!("{}, skipping it", message))?;
+        } else {
+            warn(
+                opts.warn_as_error,
+                format!("{}, so it will be shorter than expected", message),
+            )?;
+        }
+    }
+    trims.retain(|trim| trim.start < total_frames);
+    Ok(())
+}
+
+/// Moves trims counted in `framerate` back by `--start-frame`, for audio that starts that
+/// far into the script's clip. Trims that end before it are skipped, and ones that start
+/// before it are cut from the start of the audio, with a warning either way.
+fn shift_to_start_frame(
+    opts: &Config,
+    trims: Vec<Trim>,
+    framerate: Rational,
+) -> Result<Vec<Trim>, SplitError> {
+    // Cuts in seconds or timecodes are counted in nanoseconds rather than frames
+    let offset = if framerate == timing_framerate(opts) {
+        opts.start_frame
+    } else {
+        frame_to_nanos(opts.start_frame, timing_framerate(opts)) as usize
+    };
+    let mut shifted = Vec::new();
+    for mut trim in trims {
+        if !trim.needs_length() && trim.end <= offset {
+            warn(
+                opts.warn_as_error,
+                format!(
+                    "trim `{}` on line {} ends before --start-frame {}, skipping it",
+                    trim.text, trim.line, opts.start_frame
+                ),
+            )?;
+            continue;
+        }
+        if trim.shift_back(offset) {
+            warn(
+                opts.warn_as_error,
+                format!(
+                    "trim `{}` on line {} starts before --start-frame {}, \
+                     cutting it from the start of the audio",
+                    trim.text, trim.line, opts.start_frame
+                ),
+            )?;
+        }
+        shifted.push(trim);
+    }
+    Ok(shifted)
+}
+
+/// The ranges of frames the trims keep, counted in `framerate`.
+///
+/// A trim that spans no time is skipped with a warning, or with `--widen-short-trims`
+/// widened to the smallest range that does.
+fn trim_ranges(
+    opts: &Config,
+    trims: &[Trim],
+    framerate: Rational,
+) -> Result<Vec<(usize, usize)>, SplitError> {
+    let mut ranges = Vec::new();
+    for trim in trims {
+        // Only a trim that keeps no frames at all, like `clip[100:100]`, spans no time
+        if frame_to_nanos(trim.start, framerate) < frame_to_nanos(trim.end, framerate) {
+            ranges.push((trim.start, trim.end));
+        } else if opts.widen_short_trims {
+            warn(
+                opts.warn_as_error,
+                format!(
+                    "trim `{}` on line {} is zero-length, widening it to one frame",
+                    trim.text, trim.line
+                ),
+            )?;
+            ranges.push((trim.start, trim.start + 1));
+        } else {
+            warn(
+                opts.warn_as_error,
+                format!(
+                    "skipping zero-length trim `{}` on line {}",
+                    trim.text, trim.line
+                ),
+            )?;
+        }
+    }
+    Ok(ranges)
+}
+
+/// Warns when the audio and reference video lengths are too far apart,
+/// which usually means the wrong audio file was picked
+fn check_durations(opts: &Config) -> Result<(), SplitError> {
+    let video = match opts.reference_video {
+        Some(ref video) => video,
+        None => return Ok(()),
+    };
+    let audio_duration = probe_duration(
+        &opts.ffprobe,
+        &opts.input_aud,
+        opts.duration_source,
+        audio_track(opts),
+    )?;
+    let video_duration = probe_duration(&opts.ffprobe, video, DurationSource::Auto, 0)?;
+    if (audio_duration - video_duration).abs() > opts.duration_tolerance {
+        let message = format!(
+            "Audio duration ({:.3}s) and video duration ({:.3}s) differ by more than {}s",
+            audio_duration, video_duration, opts.duration_tolerance
+        );
+        if opts.strict {
+            return Err(SplitError::CheckFailed(message));
+        }
+        warn(opts.warn_as_error, message)?;
+    }
+    Ok(())
+}
+
+/// The directory the output goes in. A bare file name has an empty parent,
+/// which means the current directory.
+fn output_dir(opts: &Config) -> &Path {
+    match opts.output_aud.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Makes sure the directory the output goes in exists, creating it unless `--no-mkdir`
+fn prepare_output_dir(opts: &Config) -> Result<(), SplitError> {
+    let dir = output_dir(opts);
+    if dir.is_dir() {
+        return Ok(());
+    }
+    // Otherwise creating it fails with no more than "File exists" or "Not a directory"
+    if let Some(file) = dir.ancestors().find(|path| path.is_file()) {
+        return Err(SplitError::InvalidArgument(format!(
+            "Cannot use {} as the output directory, {} is a file",
+            dir.display(),
+            file.display()
+        )));
+    }
+    if !opts.mkdir {
+        return Err(SplitError::InvalidArgument(format!(
+            "Output directory {} does not exist",
+            dir.display()
+        )));
+    }
+    fs::create_dir_all(dir).map_err(|e| SplitError::Io(dir.to_path_buf(), e))
+}
+
+/// The file the `index`th split piece is written to
+fn piece_path(opts: &Config, index: usize) -> PathBuf {
+    opts.work_dir
+        .join(format!("split-{:03}.{}", index, piece_extension(opts)))
+}
+
+/// A split intermediate other than the pieces, like the `split.mka` mkvmerge is
+/// pointed at or the `split.txt` list given to ffmpeg
+fn temporary_path(opts: &Config, extension: &str) -> PathBuf {
+    opts.work_dir.join(format!("split.{}", extension))
+}
+
+/// Gives the run a work directory that no other file or run is using: `OUTPUT.split-PID`
+/// next to the output or in `--temp-dir`, numbered when an earlier split in this
+/// process left one behind. Every intermediate of the run is written in it and nowhere
+/// else, so none of them can land on a file of the same name, and runs side by side
+/// don't share any.
+///
+/// With `--deterministic` the PID is left out, so that two runs write the same paths.
+///
+/// The directory is only named here; [`create_work_dir`] makes it.
+fn with_work_dir(opts: &Config) -> Config {
+    let name = if opts.deterministic {
+        opts.output_aud.with_extension("split")
+    } else {
+        opts.output_aud
+            .with_extension(format!("split-{}", process::id()))
+    };
+    let base = match opts.temp_dir {
+        Some(ref dir) => dir.join(name.file_name().unwrap_or_default()),
+        None => name,
+    };
+    let mut work_dir = base.clone();
+    for number in 2.. {
+        if !work_dir.exists() {
+            break;
+        }
+        let mut numbered = base.clone().into_os_string();
+        numbered.push(format!("-{}", number));
+        work_dir = PathBuf::from(numbered);
+    }
+    Config {
+        work_dir,
+        ..opts.clone()
+    }
+}
+
+/// Makes the run's work directory, and `--temp-dir` with it if need be
+fn create_work_dir(opts: &Config) -> Result<(), SplitError> {
+    fs::create_dir_all(&opts.work_dir).map_err(|e| SplitError::Io(opts.work_dir.clone(), e))
+}
+
+/// Removes the run's work directory once it is empty, unless `--keep-temp` is given.
+/// Only an empty directory is removed, so nothing the run didn't track is ever deleted.
+fn remove_work_dir(opts: &Config) -> Result<(), SplitError> {
+    if opts.keep_temp {
+        return Ok(());
+    }
+    match fs::remove_dir(&opts.work_dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => warn(
+            opts.warn_as_error,
+            format!("could not remove {}: {}", opts.work_dir.display(), e),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Removes an intermediate once the run is done with it, unless `--keep-temp` is given
+fn remove_intermediate(opts: &Config, path: &Path) {
+    if !opts.keep_temp {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Moves `from` to `to`, copying it when they are on different filesystems,
+/// as they can be with `--temp-dir`
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to).or_else(|_| fs::copy(from, to).and_then(|_| fs::remove_file(from)))
+}
+
+fn piece_extension(opts: &Config) -> String {
+    // Pieces share the output's container when ffmpeg is copying the codec into it
+    match opts.output_aud.extension() {
+        Some(extension) if opts.copy_codec => extension.to_string_lossy().into_owned(),
+        _ => "mka".to_owned(),
+    }
+}
+
+/// Where `--no-merge` puts a kept piece: the output path with the `--name-template`
+/// filled in before the extension, like `episode.f500-1000.mka`
+fn kept_piece_path(opts: &Config, segment: &Segment, position: usize) -> PathBuf {
+    let name = opts
+        .name_template
+        .replace("{index}", &position.to_string())
+        .replace("{start}", &segment.start.to_string())
+        .replace(
+            "{end}",
+            &segment
+                .end
+                .map_or_else(|| "end".to_owned(), |end| end.to_string()),
+        );
+    opts.output_aud
+        .with_extension(format!("{}.{}", name, piece_extension(opts)))
+}
+
+/// Keeps the pieces as separate files for `--no-merge`, in join order,
+/// returning where they ended up
+fn keep_pieces(
+    opts: &Config,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+) -> Result<Vec<PathBuf>, SplitError> {
+    let mut moved: Vec<(usize, PathBuf)> = Vec::new();
+    for (i, &index) in merge_order.iter().enumerate() {
+        let target = kept_piece_path(opts, &plan.segments[index - 1], i + 1);
+        // A piece that more than one trim uses has already been moved once
+        let result = match moved.iter().find(|(moved_index, _)| *moved_index == index) {
+            Some((_, first)) if *first == target => Ok(()),
+            Some((_, first)) => fs::copy(first, &target).map(|_| ()),
+            None => move_file(&piece_path(opts, index), &target),
+        };
+        result.map_err(|e| SplitError::Io(target.clone(), e))?;
+        println!("Wrote {}", target.display());
+        moved.push((index, target));
+    }
+    Ok(moved.into_iter().map(|(_, target)| target).collect())
+}
+
+/// Prints where each kept piece lands in the output, then extracts two second clips
+/// of the output around its start, every join and its end for `--preview`.
+///
+/// The clips are named `OUTPUT.preview-start.mka`, `OUTPUT.preview-join-01.mka` and
+/// so on, and `OUTPUT.preview-end.mka`. They need ffmpeg, whichever backend split.
+fn preview_output(
+    opts: &Config,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    framerate: Rational,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    const CLIP_NANOS: u128 = 2_000_000_000;
+
+    let mut position = 0u128;
+    let mut joins = Vec::new();
+    let mut total = Some(0u128);
+    for (i, &index) in merge_order.iter().enumerate() {
+        let segment = &plan.segments[index - 1];
+        let start = frame_to_nanos(segment.start, framerate);
+        let end = segment.end.map(|end| frame_to_nanos(end, framerate));
+        if i > 0 {
+            joins.push(position);
+        }
+        println!(
+            "Preview: piece {} ({} to {}) starts the output at {}",
+            index,
+            format_nanos(start),
+            end.map_or_else(|| "end".to_owned(), format_nanos),
+            format_nanos(position)
+        );
+        match end {
+            Some(end) => position += end - start,
+            None => total = None,
+        }
+    }
+    total = total.map(|_| position);
+
+    let clip_path = |name: String| {
+        opts.output_aud
+            .with_extension(format!("preview-{}.{}", name, piece_extension(opts)))
+    };
+    let mut clips = vec![(clip_path("start".to_owned()), (0, Some(CLIP_NANOS)))];
+    for (i, &join) in joins.iter().enumerate() {
+        let span = (
+            join.saturating_sub(CLIP_NANOS / 2),
+            Some(join + CLIP_NANOS / 2),
+        );
+        clips.push((clip_path(format!("join-{:02}", i + 1)), span));
+    }
+    match total {
+        Some(total) => clips.push((
+            clip_path("end".to_owned()),
+            (total.saturating_sub(CLIP_NANOS), None),
+        )),
+        None => warn(
+            opts.warn_as_error,
+            "the output's length isn't known, not previewing its end".to_owned(),
+        )?,
+    }
+
+    for (path, span) in clips {
+        let result = ffmpeg::extract_segment(
+            &opts.ffmpeg,
+            &opts.output_aud,
+            span,
+            "0:a:0",
+            &path,
+            ffmpeg_format(opts, opts.copy_codec),
+            report,
+        );
+        match result {
+            Ok(()) => println!("Wrote preview {}", path.display()),
+            Err(SplitError::ProcessNotFound(_)) => {
+                warn(
+                    opts.warn_as_error,
+                    "ffmpeg not found, not writing preview clips".to_owned(),
+                )?;
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the output is as long as the kept segments add up to
+fn verify_output(
+    opts: &Config,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    framerate: Rational,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let frame_duration = framerate.den as f64 / framerate.num as f64;
+    let mut expected_total = 0f64;
+    for &index in merge_order {
+        let segment = &plan.segments[index - 1];
+        let end = match segment.end {
+            Some(end) => end,
+            None => {
+                warn(
+                    opts.warn_as_error,
+                    format!(
+                        "cannot verify the output, segment {} runs to the end of the audio",
+                        index
+                    ),
+                )?;
+                return Ok(());
+            }
+        };
+        let expected = (end - segment.start) as f64 * frame_duration;
+        expected_total += expected;
+        // A lone piece has already been moved into place as the output,
+        // and streaming writes no pieces at all
+        if opts.verbose && merge_order.len() > 1 && !opts.stream {
+            let measured = probe_duration(
+                &opts.ffprobe,
+                &piece_path(opts, index),
+                opts.duration_source,
+                0,
+            )?;
+            eprintln!(
+                "Segment {}: expected {:.3}s, measured {:.3}s",
+                index, expected, measured
+            );
+        }
+    }
+
+    let measured_total = probe_duration(&opts.ffprobe, &opts.output_aud, opts.duration_source, 0)?;
+    // Count in video frames even when the cuts were given in seconds
+    let video_rate = timing_framerate(opts);
+    let to_frames =
+        |seconds: f64| (seconds * video_rate.num as f64 / video_rate.den as f64).round() as usize;
+    let (expected_frames, output_frames) = (to_frames(expected_total), to_frames(measured_total));
+    report.verify = Some(ReportVerify {
+        expected_duration: expected_total,
+        output_duration: measured_total,
+        expected_frames,
+        output_frames,
+    });
+    // Each piece can be up to a frame off where it was cut
+    if expected_frames.abs_diff(output_frames) > merge_order.len() {
+        return Err(SplitError::CheckFailed(format!(
+            "Output is {:.3}s ({} frames) long but the kept segments add up to {:.3}s ({} frames)",
+            measured_total, output_frames, expected_total, expected_frames
+        )));
+    }
+    eprintln!(
+        "Verified output duration: {:.3}s, {} frames (expected {:.3}s, {} frames)",
+        measured_total, output_frames, expected_total, expected_frames
+    );
+    Ok(())
+}
+
+/// Writes a copy of the input with `delay` milliseconds of silence in front of it,
+/// for `--delay-mode silence`, and returns where it went
+fn pad_with_silence(
+    opts: &Config,
+    delay: isize,
+    report: &mut Report,
+) -> Result<PathBuf, SplitError> {
+    eprintln!("Padding the audio with {}ms of silence", delay);
+    let track = audio_track(opts);
+    let codec = probe_codec(&opts.ffprobe, &opts.input_aud, track)?;
+    let SampleFormat {
+        sample_rate,
+        channel_layout,
+        ..
+    } = probe_sample_format(&opts.ffprobe, &opts.input_aud, track)?;
+    let silence = opts.work_dir.join("silence.mka");
+    let padded = padded_path(opts);
+    // The concat demuxer joins streams in order, so pull a later track out on its own first
+    let source = if track == 0 {
+        opts.input_aud.clone()
+    } else {
+        let source = opts.work_dir.join("track.mka");
+        ffmpeg::remux(
+            &opts.ffmpeg,
+            &opts.input_aud,
+            0,
+            &audio_map(opts),
+            &source,
+            ffmpeg_format(opts, false),
+            report,
+        )?;
+        source
+    };
+    ffmpeg::generate_silence(
+        &opts.ffmpeg,
+        &codec,
+        &sample_rate,
+        &channel_layout,
+        delay,
+        &silence,
+        report,
+    )?;
+    let result = ffmpeg::concat(
+        &opts.ffmpeg,
+        &[silence.clone(), source.clone()],
+        &opts.work_dir.join("padded.txt"),
+        &padded,
+        ffmpeg_format(opts, false),
+        report,
+    );
+    remove_intermediate(opts, &silence);
+    if source != opts.input_aud {
+        remove_intermediate(opts, &source);
+    }
+    result.map(|_| padded)
+}
+
+fn passthrough_audio(opts: &Config, delay: isize, report: &mut Report) -> Result<(), SplitError> {
+    prepare_output_dir(opts)?;
+    if opts.backend == Backend::Ffmpeg {
+        return ffmpeg::remux(
+            &opts.ffmpeg,
+            &opts.input_aud,
+            delay,
+            &audio_map(opts),
+            &opts.output_aud,
+            ffmpeg_format(opts, opts.copy_codec),
+            report,
+        );
+    }
+    let output = run_mkvmerge(&opts.mkvmerge, mkvmerge_copy_args(opts, delay)?, report)?;
+    println!("{}", String::from_utf8_lossy(&output.stdout));
+    Ok(())
+}
+
+/// The arguments mkvmerge copies the whole input to the output with
+fn mkvmerge_copy_args(opts: &Config, delay: isize) -> Result<Vec<String>, SplitError> {
+    let mut args = vec![
+        "-o".to_owned(),
+        opts.output_aud.to_string_lossy().into_owned(),
+    ];
+    args.extend(timestamp_scale_args(opts)?);
+    args.extend(deterministic_args(opts));
+    args.extend(mkvmerge_track_args(opts, delay)?);
+    args.push(opts.input_aud.to_string_lossy().into_owned());
+    Ok(args)
+}
+
+/// Where `pad_with_silence` writes the padded copy of the input
+fn padded_path(opts: &Config) -> PathBuf {
+    opts.work_dir.join("padded.mka")
+}
+
+/// Keeps the whole input, for a script without trims or trims that cover all of it,
+/// by remuxing it with the delay applied instead of splitting and joining it
+fn copy_whole_input(opts: &Config, delay: isize, report: &mut Report) -> Result<(), SplitError> {
+    let split_start = Instant::now();
+    let result = if opts.delay_mode == DelayMode::Silence && delay > 0 {
+        let opts = &with_work_dir(opts);
+        prepare_output_dir(opts)?;
+        create_work_dir(opts)?;
+        let result = pad_with_silence(opts, delay, report).and_then(|padded| {
+            let padded_opts = Config {
+                input_aud: padded.clone(),
+                audio_track: None,
+                ..opts.clone()
+            };
+            let result = passthrough_audio(&padded_opts, 0, report);
+            remove_intermediate(opts, &padded);
+            result
+        });
+        result.and(remove_work_dir(opts))
+    } else {
+        passthrough_audio(opts, delay, report)
+    };
+    if result.is_ok() && opts.preserve_mtime {
+        copy_mtime(&opts.input_aud, &opts.output_aud)?;
+    }
+    report.timings.add("split", split_start.elapsed());
+    result
+}
+
+/// Prints every piece the split produces and whether it is kept
+fn list_segments(plan: &SegmentPlan, merge_order: &[usize], framerate: Rational) {
+    println!(
+        "{:>7}  {:<7}  {:>11}  {:>11}  {:<18}  End",
+        "Segment", "Action", "Start frame", "End frame", "Start"
+    );
+    for segment in &plan.segments {
+        println!(
+            "{:>7}  {:<7}  {:>11}  {:>11}  {:<18}  {}",
+            segment.index,
+            if segment.keep { "keep" } else { "drop" },
+            segment.start,
+            segment
+                .end
+                .map_or_else(|| "end".to_owned(), |end| end.to_string()),
+            frame_to_timecode(segment.start, framerate),
+            segment
+                .end
+                .map_or_else(|| "end".to_owned(), |end| frame_to_timecode(end, framerate)),
+        );
+    }
+    let order: Vec<String> = merge_order.iter().map(ToString::to_string).collect();
+    println!("Join order: {}", order.join(", "));
+}
+
+/// Shows what is about to happen and asks whether to go ahead
+fn confirm_plan(opts: &Config, plan: &SegmentPlan, cut_times: &[String]) -> Result<(), SplitError> {
+    println!("Output: {}", opts.output_aud.display());
+    println!("Cut times: {}", cut_times.join(", "));
+    println!(
+        "Temporary files (created in {}, then deleted):",
+        opts.work_dir.display()
+    );
+    for piece in split_pieces_created(opts, plan) {
+        println!("    {}", piece.display());
+    }
+    if opts.backend == Backend::Ffmpeg {
+        println!("    {}", temporary_path(opts, "txt").display());
+    }
+    print!("Continue? [y/N] ");
+    io::stdout().flush().ok();
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| SplitError::Io(PathBuf::from("<stdin>"), e))?;
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(SplitError::Cancelled),
+    }
+}
+
+/// Splits the whole input at every cut, producing every piece in the plan
+fn split_with_mkvmerge(
+    opts: &Config,
+    plan: &SegmentPlan,
+    cut_times: &[String],
+    delay: isize,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let version = mkvmerge_version(&opts.mkvmerge)?;
+    let args = mkvmerge_split_args(opts, cut_times, delay, version)?;
+    let output = run_mkvmerge(&opts.mkvmerge, args, report)?;
+    println!("{}", String::from_utf8_lossy(&output.stdout));
+    renumber_mkvmerge_pieces(opts, plan.segments.len())
+}
+
+/// The arguments mkvmerge splits the input at `cut_times` with
+fn mkvmerge_split_args(
+    opts: &Config,
+    cut_times: &[String],
+    delay: isize,
+    version: MkvmergeVersion,
+) -> Result<Vec<String>, SplitError> {
+    let mut args = vec![
+        "-o".to_owned(),
+        temporary_path(opts, "mka").to_string_lossy().into_owned(),
+    ];
+    // The pieces get the scale too, since one may be moved into place as the output
+    args.extend(timestamp_scale_args(opts)?);
+    args.extend(deterministic_args(opts));
+    args.extend(mkvmerge_track_args(opts, delay)?);
+    args.push(opts.input_aud.to_string_lossy().into_owned());
+    args.push("--split".to_owned());
+    args.push(format!("{}:{}", version.split_mode(), cut_times.join(",")));
+    Ok(args)
+}
+
+/// Lines up the pieces mkvmerge wrote with the `expected` number of pieces.
+///
+/// Some mkvmerge versions write an empty piece before a cut at the very start of the
+/// file, which shifts every piece after it up by one. Rather than guess from the
+/// version or the cut times, this counts the pieces that were actually written, and
+/// drops the leading one if there is exactly one piece too many.
+fn renumber_mkvmerge_pieces(opts: &Config, expected: usize) -> Result<(), SplitError> {
+    let written = (1..)
+        .take_while(|&index| piece_path(opts, index).exists())
+        .count();
+    if written == expected + 1 {
+        if opts.verbose {
+            println!("mkvmerge wrote an extra piece at the start, dropping it");
+        }
+        let leading = piece_path(opts, 1);
+        fs::remove_file(&leading).map_err(|e| SplitError::Io(leading, e))?;
+        for index in 1..=expected {
+            let (from, to) = (piece_path(opts, index + 1), piece_path(opts, index));
+            fs::rename(&from, &to).map_err(|e| SplitError::Io(to, e))?;
+        }
+    } else if written != expected {
+        warn(
+            opts.warn_as_error,
+            format!(
+                "mkvmerge wrote {} pieces where {} were expected, \
+                 so the kept pieces may not line up with the trims",
+                written, expected
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// The mkvmerge options for `--timestamp-scale`, which apply to the file being written
+fn timestamp_scale_args(opts: &Config) -> Result<Vec<String>, SplitError> {
+    match opts.timestamp_scale {
+        Some(scale) => {
+            let version = mkvmerge_version(&opts.mkvmerge)?;
+            Ok(vec![
+                version.timestamp_scale_option().to_owned(),
+                scale.to_string(),
+            ])
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// mkvmerge's `--deterministic` for `--deterministic`, which leaves out the muxing date
+/// and seeds the UIDs it writes from the inputs rather than at random
+fn deterministic_args(opts: &Config) -> Vec<String> {
+    if opts.deterministic {
+        vec![
+            "--deterministic".to_owned(),
+            deterministic_seed(opts).to_string(),
+        ]
+    } else {
+        Vec::new()
+    }
+}
+
+/// An FNV-1a hash of the script and the input's name and length, so that different
+/// sources still get different UIDs. The input is not read, as it may be very large.
+fn deterministic_seed(opts: &Config) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    if let Some(script) = opts.input_avs.as_ref().and_then(|path| fs::read(path).ok()) {
+        feed(&script);
+    }
+    if let Some(name) = opts.input_aud.file_name() {
+        feed(name.to_string_lossy().as_bytes());
+    }
+    if let Ok(metadata) = fs::metadata(&opts.input_aud) {
+        feed(&metadata.len().to_le_bytes());
+    }
+    hash
+}
+
+/// How ffmpeg writes an output, Matroska unless `follow_extension`
+fn ffmpeg_format(opts: &Config, follow_extension: bool) -> ffmpeg::OutputFormat {
+    ffmpeg::OutputFormat {
+        follow_extension,
+        bitexact: opts.deterministic,
+    }
+}
+
+/// The mkvmerge options that pick the input's tracks and delay the audio by `delay`.
+///
+/// Without `--audio-track` every track is copied and the first one is delayed, as long
+/// as the first track is audio. With it, or when the input is something like a movie
+/// with its video first, only that audio track (or the first) is copied, found by the
+/// stream index ffprobe gives it, which is mkvmerge's track ID for Matroska input.
+/// `all_tracks` skips the track selection and copies everything.
+fn mkvmerge_track_args(opts: &Config, delay: isize) -> Result<Vec<String>, SplitError> {
+    let all_tracks = || Ok(vec!["--sync".to_owned(), format!("0:{}", delay)]);
+    if opts.all_tracks {
+        return all_tracks();
+    }
+    let (track, streams) = match opts.audio_track {
+        Some(track) => (track, probe_audio_streams(&opts.ffprobe, &opts.input_aud)?),
+        None => match probe_audio_streams(&opts.ffprobe, &opts.input_aud) {
+            // Any other track 0 would be delayed in place of the audio
+            Ok(streams) if streams.first().is_some_and(|stream| stream.index != 0) => {
+                if opts.verbose {
+                    println!(
+                        "The first track of {} isn't audio, keeping only its first audio track",
+                        opts.input_aud.display()
+                    );
+                }
+                (0, streams)
+            }
+            Ok(ref streams) if streams.is_empty() => {
+                return Err(SplitError::CheckFailed(format!(
+                    "No audio streams found in {}",
+                    opts.input_aud.display()
+                )))
+            }
+            _ => return all_tracks(),
+        },
+    };
+    let id = match streams.get(track) {
+        Some(stream) => stream.index,
+        None => return Err(track_out_of_range(opts, track, streams.len())),
+    };
+    Ok(vec![
+        "--audio-tracks".to_owned(),
+        id.to_string(),
+        "--no-video".to_owned(),
+        "--no-subtitles".to_owned(),
+        "--sync".to_owned(),
+        format!("{}:{}", id, delay),
+    ])
+}
+
+fn merge_with_mkvmerge(
+    opts: &Config,
+    merge_files: &[PathBuf],
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let merge_args = mkvmerge_merge_args(opts, merge_files)?;
+    let output = run_mkvmerge(&opts.mkvmerge, merge_args, report)?;
+    println!("{}", String::from_utf8_lossy(&output.stdout));
+    Ok(())
+}
+
+/// The arguments mkvmerge appends `merge_files` into the output with
+fn mkvmerge_merge_args(opts: &Config, merge_files: &[PathBuf]) -> Result<Vec<String>, SplitError> {
+    let mut merge_args = vec![
+        "-o".to_owned(),
+        opts.output_aud.to_string_lossy().into_owned(),
+    ];
+    merge_args.extend(timestamp_scale_args(opts)?);
+    merge_args.extend(deterministic_args(opts));
+    merge_args.extend(merge_files.iter().enumerate().map(|(i, x)| {
+        if i == 0 {
+            x.to_string_lossy().into_owned()
+        } else {
+            format!("+{}", x.to_string_lossy())
+        }
+    }));
+    Ok(merge_args)
+}
+
+/// Where `segment` lies in the input, in nanoseconds, snapped to the nearest of
+/// `packets` when there are any.
+///
+/// Delaying the audio by `delay` means output time t comes from input time t - delay.
+fn input_span(
+    segment: &Segment,
+    framerate: Rational,
+    delay: isize,
+    packets: &[u128],
+) -> (u128, Option<u128>) {
+    let shift = |frame: usize| {
+        let nanos = frame_to_nanos(frame, framerate) as i128 - delay as i128 * 1_000_000;
+        snap_to_packet(nanos.max(0) as u128, packets)
+    };
+    (shift(segment.start), segment.end.map(shift))
+}
+
+/// The packet starts the ffmpeg backend cuts on for `--snap-to-packet`, or none
+fn packet_times(opts: &Config) -> Result<Vec<u128>, SplitError> {
+    if !opts.snap_to_packet {
+        return Ok(Vec::new());
+    }
+    let packets = probe_packet_times(&opts.ffprobe, &opts.input_aud, audio_track(opts))?;
+    if packets.is_empty() {
+        warn(
+            opts.warn_as_error,
+            format!(
+                "ffprobe listed no packets for {}, cutting at the exact times",
+                opts.input_aud.display()
+            ),
+        )?;
+    } else if opts.verbose {
+        let longest = packets.windows(2).map(|w| w[1] - w[0]).max().unwrap_or(0);
+        println!(
+            "Snapping cuts to {} packets, moving each cut by at most {}ms",
+            packets.len(),
+            longest / 2 / 1_000_000
+        );
+    }
+    Ok(packets)
+}
+
+/// The packet start nearest to `nanos`, taking the earlier one on a tie
+fn snap_to_packet(nanos: u128, packets: &[u128]) -> u128 {
+    let after = packets.partition_point(|&packet| packet < nanos);
+    let before = after.checked_sub(1).map(|i| packets[i]);
+    match (before, packets.get(after).cloned()) {
+        (Some(before), Some(after)) if after - nanos < nanos - before => after,
+        (Some(before), _) => before,
+        (None, Some(after)) => after,
+        (None, None) => nanos,
+    }
+}
+
+/// Extracts only the pieces that will be kept, since ffmpeg cuts one range at a time
+fn split_with_ffmpeg(
+    opts: &Config,
+    plan: &SegmentPlan,
+    framerate: Rational,
+    delay: isize,
+    streams: &str,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let packets = packet_times(opts)?;
+    for segment in plan.segments.iter().filter(|segment| segment.keep) {
+        ffmpeg::extract_segment(
+            &opts.ffmpeg,
+            &opts.input_aud,
+            input_span(segment, framerate, delay, &packets),
+            streams,
+            &piece_path(opts, segment.index),
+            ffmpeg_format(opts, opts.copy_codec),
+            report,
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the kept segments in `merge_order` straight into the output, for `--stream`
+fn stream_with_ffmpeg(
+    opts: &Config,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    framerate: Rational,
+    delay: isize,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let packets = packet_times(opts)?;
+    let spans: Vec<(u128, Option<u128>)> = merge_order
+        .iter()
+        .map(|&index| input_span(&plan.segments[index - 1], framerate, delay, &packets))
+        .collect();
+    let list = temporary_path(opts, "txt");
+    ffmpeg::concat_spans(
+        &opts.ffmpeg,
+        &ffmpeg::spans_list(&opts.input_aud, &spans, &list),
+        &list,
+        &audio_map(opts),
+        &opts.output_aud,
+        ffmpeg_format(opts, opts.copy_codec),
+        report,
+    )
+}
+
+/// Splits `opts.input_aud` as `opts` asks and returns the output it wrote, or would
+/// have written for the modes that stop early.
+///
+/// The framerate is used as it is given, so call [`resolve_framerate`] first to have
+/// it read from the script. The `--report`, `--summary-json` and `--command-log`
+/// files are written whether or not the split succeeds.
+pub fn split_audio(opts: &Config) -> Result<PathBuf, SplitError> {
+    let mut report = Report {
+        framerate: opts.framerate.to_string(),
+        framerate_source: opts.framerate_source.to_string(),
+        ..Report::default()
+    };
+    let start = Instant::now();
+    let result = run_split(opts, &mut report);
+    report.timings.add("total", start.elapsed());
+    if opts.timings {
+        report.timings.print();
+    }
+    report.error = result.as_ref().err().map(ToString::to_string);
+    if let Some(ref path) = opts.report {
+        report.write(path)?;
+    }
+    if opts.summary_json {
+        println!(
+            "{}",
+            report.summary_json(&opts.output_aud, &opts.backend.to_string())
+        );
+    }
+    if let Some(ref path) = opts.command_log {
+        let log: String = report
+            .commands
+            .iter()
+            .map(|command| command_line(&command.args) + "\n")
+            .collect();
+        fs::write(path, log).map_err(|e| SplitError::Io(path.clone(), e))?;
+    }
+    result.map(|_| opts.output_aud.clone())
+}
+
+fn run_split(opts: &Config, report: &mut Report) -> Result<(), SplitError> {
+    // A build without an option the run needs is caught before any work is done,
+    // rather than by an obscure error partway through
+    check_ffprobe(&opts.ffprobe)?;
+    // A missing or too old mkvmerge is caught here too, rather than after the probing,
+    // unless the run stops before anything is split. Some runs can do without ffprobe,
+    // so a missing one is only reported when it is needed
+    let splits = opts.stop_after != Stage::Probe && (!opts.list_segments || opts.run);
+    if opts.backend == Backend::Mkvmerge && opts.custom_backend.is_none() && splits {
+        check_mkvmerge(&opts.mkvmerge, mkvmerge_version(&opts.mkvmerge)?)?;
+    }
+    let probe_start = Instant::now();
+    check_durations(opts)?;
+    if opts.copy_codec {
+        let codec = probe_codec(&opts.ffprobe, &opts.input_aud, audio_track(opts))?;
+        let extension = opts
+            .output_aud
+            .extension()
+            .map_or_else(String::new, |ext| ext.to_string_lossy().into_owned());
+        check_copy_container(&codec, &extension)?;
+    }
+    if let Some(ref transcode) = opts.transcode {
+        let extension = opts
+            .output_aud
+            .extension()
+            .map_or_else(String::new, |ext| ext.to_string_lossy().into_owned());
+        transcode.check_container(&extension)?;
+    }
+
+    // Determine if we should apply a delay to the audio
+    let (delay, delay_source) = detect_delay(opts)?;
+    let compensation = if delay_source == "start_time" {
+        delay
+    } else {
+        0
+    };
+    let delay = if opts.normalize_delay {
+        normalize_delay(delay, opts.audio_framerate.unwrap_or(opts.framerate))
+    } else {
+        delay
+    };
+    report.timings.add("ffprobe", probe_start.elapsed());
+    report.delay = delay;
+    report.delay_source = delay_source.to_owned();
+    report.edit_compensation = compensation;
+
+    // Field-based scripts count fields rather than frames, so use the field rate
+    let framerate = timing_framerate(opts);
+
+    // Determine where to trim
+    // Trims come back in the order they should be joined
+    let (mut trims, framerate, checked) = match (&opts.cuts, &opts.cuts_file) {
+        _ if opts.keyframes_file.is_some() => {
+            let path = opts.keyframes_file.as_ref().unwrap();
+            let contents = fs::read_to_string(path).map_err(|e| SplitError::Io(path.clone(), e))?;
+            (
+                parse_keyframes_file(&contents)?,
+                framerate,
+                "the keyframes file",
+            )
+        }
+        _ if opts.split_at_chapters => (
+            chapter_trims(opts)?,
+            CutsUnit::Seconds.timebase(framerate),
+            "the input's chapters",
+        ),
+        _ if opts.cuts_tag.is_some() => (
+            tag_trims(opts, framerate)?,
+            opts.cuts_unit.timebase(framerate),
+            "the input's tag",
+        ),
+        (Some(cuts), _) => (
+            cut_trims(cuts, opts.cuts_unit, framerate)?,
+            opts.cuts_unit.timebase(framerate),
+            "the given cuts",
+        ),
+        (None, Some(path)) => {
+            let contents = fs::read_to_string(path).map_err(|e| SplitError::Io(path.clone(), e))?;
+            (
+                parse_cuts_file(&contents, opts.cuts_unit, opts.cuts_file_mode, framerate)?,
+                opts.cuts_unit.timebase(framerate),
+                "the cuts file",
+            )
+        }
+        (None, None) => {
+            let (trims, checked) = script_trims(opts, framerate)?;
+            (trims, framerate, checked)
+        }
+    };
+    if trims.is_empty() {
+        if opts.passthrough_on_no_trims {
+            if opts.stop_after == Stage::Probe {
+                println!("No trims found, the audio would be copied unchanged");
+                return Ok(());
+            }
+            eprintln!("No trims found, copying audio file with {} delay", delay);
+            copy_whole_input(opts, delay, report)?;
+            return post_hook(opts);
+        }
+        return Err(SplitError::NoTrims(checked.to_owned()));
+    }
+    if opts.start_frame > 0 {
+        trims = shift_to_start_frame(opts, trims, framerate)?;
+    }
+    // Trim(start, 0) and negative slices need the length of the clip to know where they are,
+    // and any other trim is checked against it
+    let total_frames = match get_total_frames(opts, framerate) {
+        Ok(total_frames) => Some(total_frames),
+        Err(SplitError::ProcessNotFound(_)) if !trims.iter().any(Trim::needs_length) => {
+            warn(
+                opts.warn_as_error,
+                "ffprobe not found, not checking trims against the audio length".to_owned(),
+            )?;
+            None
+        }
+        Err(e) => return Err(e),
+    };
+    if let Some(total_frames) = total_frames {
+        for trim in &mut trims {
+            trim.resolve_length(total_frames)?;
+        }
+        if let Some(tolerance) = opts.end_tolerance {
+            snap_to_end(&mut trims, total_frames, tolerance);
+        }
+        check_trims_in_audio(opts, &mut trims, total_frames)?;
+    }
+    for trim in &trims {
+        report.trims.push(ReportTrim {
+            start: trim.start,
+            end: trim.end,
+            line: trim.line,
+            start_time: frame_to_timecode(trim.start, framerate),
+            end_time: frame_to_timecode(trim.end, framerate),
+        });
+    }
+
+    let mut ranges = trim_ranges(opts, &trims, framerate)?;
+    if let Some(max_gap) = opts.max_gap_merge {
+        // The gap is given in frames, but cuts files may count in nanoseconds
+        let max_gap = frame_to_nanos(max_gap, timing_framerate(opts));
+        ranges = absorb_small_gaps(&ranges, |end, start| {
+            let gap = frame_to_nanos(start, framerate) - frame_to_nanos(end, framerate);
+            if gap < max_gap {
+                eprintln!(
+                    "Keeping the gap between {} and {} to join the trims around it",
+                    frame_to_timecode(end, framerate),
+                    frame_to_timecode(start, framerate)
+                );
+            }
+            gap < max_gap
+        });
+    }
+    let mut plan = plan_segments(&ranges, total_frames);
+    // Neighbouring chapters and keyframe ranges always share an edge,
+    // so that isn't worth a warning
+    let shared_edges = opts.split_at_chapters || opts.keyframes_file.is_some();
+    if !plan.duplicates.is_empty() && !shared_edges {
+        let frames: Vec<String> = plan.duplicates.iter().map(ToString::to_string).collect();
+        warn(
+            opts.warn_as_error,
+            format!("dropping duplicate cuts at frames {}", frames.join(", ")),
+        )?;
+    }
+    if opts.invert {
+        plan.invert();
+    }
+    let mut merge_order = plan.select_pieces(&ranges, opts.invert, opts.keep_indices.as_deref())?;
+    plan.force_first(opts.first_segment, &mut merge_order);
+    let output_nanos: Option<u128> = merge_order
+        .iter()
+        .map(|&index| {
+            let segment = &plan.segments[index - 1];
+            segment.end.map(|end| {
+                frame_to_nanos(end, framerate) - frame_to_nanos(segment.start, framerate)
+            })
+        })
+        .sum();
+    if let Some(nanos) = output_nanos {
+        let video = timing_framerate(opts);
+        report.output_duration = Some(nanos as f64 / 1e9);
+        report.output_frames = Some(
+            ((nanos * video.num as u128 + video.den as u128 * 500_000_000)
+                / (video.den as u128 * 1_000_000_000)) as usize,
+        );
+    }
+    if merge_order.is_empty() {
+        return Err(SplitError::CheckFailed(
+            "Nothing is left to keep after splitting".to_owned(),
+        ));
+    }
+    // A vector of timestamps for trimming
+    let cut_times: Vec<String> = plan
+        .cuts
+        .iter()
+        .map(|&frame| frame_to_timecode(frame, framerate))
+        .collect();
+
+    let kept_spans: Vec<(u128, Option<u128>)> = merge_order
+        .iter()
+        .map(|&index| {
+            let segment = &plan.segments[index - 1];
+            (
+                frame_to_nanos(segment.start, framerate),
+                segment.end.map(|end| frame_to_nanos(end, framerate)),
+            )
+        })
+        .collect();
+    let hash = trims_hash(&kept_spans);
+    check_marker(opts, &hash)?;
+    if opts.dump_plan {
+        let opts = &with_work_dir(opts);
+        let commands = planned_commands(opts, &plan, &merge_order, &cut_times, framerate, delay)?;
+        let json = plan_json(
+            opts,
+            report,
+            &plan,
+            &merge_order,
+            &cut_times,
+            framerate,
+            commands,
+        );
+        println!("{}", json.to_pretty_string());
+        return Ok(());
+    }
+    if opts.dry_run {
+        let opts = &with_work_dir(opts);
+        let commands = planned_commands(opts, &plan, &merge_order, &cut_times, framerate, delay)?;
+        println!("Delay: {}ms ({})", delay, delay_source);
+        println!("Cut times: {}", cut_times.join(","));
+        for command in commands {
+            println!("{}", command_line(&command));
+        }
+        return Ok(());
+    }
+
+    if let Some(ref path) = opts.output_edl {
+        output_edl(opts, path, &plan, &merge_order, framerate, total_frames)?;
+    }
+    if let Some(ref path) = opts.output_avs_trims {
+        output_avs_trims(opts, path, &plan, &merge_order, framerate)?;
+    }
+    if let Some((ref path, format)) = opts.output_markers {
+        write_markers(path, format, &kept_spans)?;
+    }
+    if opts.list_segments {
+        list_segments(&plan, &merge_order, framerate);
+        if !opts.run {
+            return Ok(());
+        }
+    }
+    if opts.stop_after == Stage::Probe {
+        println!("Cut times: {}", cut_times.join(","));
+        return Ok(());
+    }
+
+    if copies_whole_input(opts, &plan, &merge_order) {
+        eprintln!(
+            "The trims keep the whole input, copying it with {} delay instead of splitting",
+            delay
+        );
+        copy_whole_input(opts, delay, report)?;
+        let written = [opts.output_aud.clone()];
+        finish_output(opts, &plan, &merge_order, framerate, hash, &written, report)?;
+        return post_hook(opts);
+    }
+    let opts = &with_work_dir(opts);
+    if opts.interactive && !opts.yes {
+        confirm_plan(opts, &plan, &cut_times)?;
+    }
+
+    prepare_output_dir(opts)?;
+    create_work_dir(opts)?;
+
+    let result = match split_and_join(
+        opts,
+        &plan,
+        &merge_order,
+        &cut_times,
+        framerate,
+        delay,
+        report,
+    ) {
+        Ok(Some(written)) => {
+            finish_output(opts, &plan, &merge_order, framerate, hash, &written, report)
+        }
+        // Stopped after the split, so the pieces are what was asked for
+        Ok(None) => return Ok(()),
+        Err(e) => Err(e),
+    };
+    let mut cleaned = Ok(());
+    // Streaming writes no pieces, so there is nothing to leave behind
+    if (opts.stream && !opts.keep_temp) || opts.cleanup.removes_pieces(result.is_ok()) {
+        println!("Cleaning temporary files...");
+        let cleanup_start = Instant::now();
+        cleaned = clean_temporary_files(opts, &plan);
+        report.timings.add("cleanup", cleanup_start.elapsed());
+    } else {
+        let kept = if opts.keep_temp {
+            "temporary files"
+        } else {
+            "split pieces"
+        };
+        println!("Leaving the {} in {}", kept, opts.work_dir.display());
+    }
+    result?;
+    cleaned?;
+
+    if let Some(ref also) = opts.also_split {
+        also_split(
+            opts,
+            also,
+            &plan,
+            &merge_order,
+            &cut_times,
+            framerate,
+            report,
+        )?;
+    }
+
+    post_hook(opts)
+}
+
+/// Whether the plan keeps the whole input, which is then copied rather than split.
+///
+/// With no cuts there is nothing to split or join. Separate pieces, a transcode and
+/// `--also-split` still go the usual way, since copying does none of them.
+fn copies_whole_input(opts: &Config, plan: &SegmentPlan, merge_order: &[usize]) -> bool {
+    plan.cuts.is_empty()
+        && merge_order == [1]
+        && !opts.no_merge
+        && opts.transcode.is_none()
+        && opts.also_split.is_none()
+}
+
+/// The commands a run of `plan` goes on to run, for `--dump-plan`, each starting
+/// with the program.
+///
+/// Padding the audio for `--delay-mode silence` isn't listed, nor is anything run
+/// after the output is written. The commands after the padding read the padded copy,
+/// and aren't snapped to its packets since it doesn't exist yet.
+fn planned_commands(
+    opts: &Config,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    cut_times: &[String],
+    framerate: Rational,
+    delay: isize,
+) -> Result<Vec<Vec<String>>, SplitError> {
+    let mkvmerge = |args: Vec<String>| {
+        let mut command = vec![opts.mkvmerge.to_string_lossy().into_owned()];
+        command.extend(args);
+        command
+    };
+    let padded_opts;
+    let (opts, delay, packets) = if opts.delay_mode == DelayMode::Silence && delay > 0 {
+        padded_opts = Config {
+            input_aud: padded_path(opts),
+            audio_track: None,
+            ..opts.clone()
+        };
+        (&padded_opts, 0, Vec::new())
+    } else {
+        (opts, delay, packet_times(opts)?)
+    };
+    let streams = audio_map(opts);
+    let format = ffmpeg_format(opts, opts.copy_codec);
+    let mut commands = Vec::new();
+    if opts.stop_after == Stage::Probe {
+        return Ok(commands);
+    }
+    if copies_whole_input(opts, plan, merge_order) {
+        commands.push(match opts.backend {
+            Backend::Mkvmerge => mkvmerge(mkvmerge_copy_args(opts, delay)?),
+            Backend::Ffmpeg => ffmpeg::command_line(
+                &opts.ffmpeg,
+                ffmpeg::remux_args(&opts.input_aud, delay, &streams, &opts.output_aud, format),
+            ),
+        });
+        return Ok(commands);
+    }
+
+    if opts.stream {
+        commands.push(ffmpeg::command_line(
+            &opts.ffmpeg,
+            ffmpeg::concat_args(
+                &temporary_path(opts, "txt"),
+                Some(&streams),
+                &opts.output_aud,
+                format,
+            ),
+        ));
+    } else {
+        match opts.backend {
+            Backend::Mkvmerge => {
+                let version = mkvmerge_version(&opts.mkvmerge)?;
+                commands.push(mkvmerge(mkvmerge_split_args(
+                    opts, cut_times, delay, version,
+                )?));
+            }
+            Backend::Ffmpeg => {
+                for segment in plan.segments.iter().filter(|segment| segment.keep) {
+                    commands.push(ffmpeg::command_line(
+                        &opts.ffmpeg,
+                        ffmpeg::extract_segment_args(
+                            &opts.input_aud,
+                            input_span(segment, framerate, delay, &packets),
+                            &streams,
+                            &piece_path(opts, segment.index),
+                            format,
+                        ),
+                    ));
+                }
+            }
+        }
+        if opts.stop_after == Stage::Split {
+            return Ok(commands);
+        }
+        let merge_files: Vec<PathBuf> = merge_order
+            .iter()
+            .map(|&index| piece_path(opts, index))
+            .collect();
+        if !opts.no_merge && single_piece_moved(opts, &merge_files).is_none() {
+            commands.push(match opts.backend {
+                Backend::Mkvmerge => mkvmerge(mkvmerge_merge_args(opts, &merge_files)?),
+                Backend::Ffmpeg => ffmpeg::command_line(
+                    &opts.ffmpeg,
+                    ffmpeg::concat_args(
+                        &temporary_path(opts, "txt"),
+                        None,
+                        &opts.output_aud,
+                        format,
+                    ),
+                ),
+            });
+        }
+    }
+    if let Some(ref transcode) = opts.transcode {
+        commands.push(ffmpeg::command_line(
+            &opts.ffmpeg,
+            ffmpeg::transcode_args(
+                &transcode_source(opts),
+                transcode,
+                &opts.output_aud,
+                ffmpeg_format(opts, true),
+            ),
+        ));
+    }
+    Ok(commands)
+}
+
+/// Everything a run of `plan` resolved, and the commands it would run, for `--dump-plan`
+fn plan_json(
+    opts: &Config,
+    report: &Report,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    cut_times: &[String],
+    framerate: Rational,
+    commands: Vec<Vec<String>>,
+) -> Json {
+    let path = |path: &Path| Json::string(path.to_string_lossy());
+    let segments = plan
+        .segments
+        .iter()
+        .map(|segment| {
+            Json::object(vec![
+                ("index", segment.index.into()),
+                ("start_frame", segment.start.into()),
+                ("end_frame", segment.end.into()),
+                (
+                    "start_time",
+                    Json::string(frame_to_timecode(segment.start, framerate)),
+                ),
+                (
+                    "end_time",
+                    segment
+                        .end
+                        .map(|end| frame_to_timecode(end, framerate))
+                        .into(),
+                ),
+                ("keep", segment.keep.into()),
+            ])
+        })
+        .collect();
+    Json::object(vec![
+        ("input", path(&opts.input_aud)),
+        ("output", path(&opts.output_aud)),
+        ("backend", Json::string(opts.backend.to_string())),
+        ("framerate", Json::string(report.framerate.as_str())),
+        (
+            "framerate_source",
+            Json::string(report.framerate_source.as_str()),
+        ),
+        ("delay", report.delay.into()),
+        ("delay_source", Json::string(report.delay_source.as_str())),
+        ("edit_compensation", report.edit_compensation.into()),
+        ("trims", report.trims_json()),
+        ("segments", Json::Array(segments)),
+        ("cut_times", cut_times.to_vec().into()),
+        ("join_order", merge_order.to_vec().into()),
+        ("output_duration", report.output_duration.into()),
+        ("output_frames", report.output_frames.into()),
+        ("commands", commands.into()),
+    ])
+}
+
+/// Runs the `--post-hook`, if there is one, once the output is written
+fn post_hook(opts: &Config) -> Result<(), SplitError> {
+    match opts.post_hook {
+        Some(ref template) => run_post_hook(
+            template,
+            &opts.output_aud,
+            Some(&opts.input_aud),
+            opts.input_avs.as_deref(),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Splits the input and joins the kept pieces, returning the files written, or `None`
+/// when stopping after the split
+fn split_and_join(
+    opts: &Config,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    cut_times: &[String],
+    framerate: Rational,
+    delay: isize,
+    report: &mut Report,
+) -> Result<Option<Vec<PathBuf>>, SplitError> {
+    // Split the audio file apart
+    let split_start = Instant::now();
+    let padded = if opts.delay_mode == DelayMode::Silence && delay > 0 {
+        Some(pad_with_silence(opts, delay, report)?)
+    } else {
+        None
+    };
+    let split_result = match padded {
+        // The silence already delays the audio, so split the padded copy as it is
+        Some(ref padded) => {
+            let padded_opts = Config {
+                input_aud: padded.clone(),
+                audio_track: None,
+                ..opts.clone()
+            };
+            if opts.stream {
+                eprintln!("Streaming kept segments of padded audio file");
+                stream_with_ffmpeg(&padded_opts, plan, merge_order, framerate, 0, report)
+            } else {
+                eprintln!("Splitting padded audio file");
+                split_pieces(&padded_opts, plan, cut_times, framerate, 0, "0:a:0", report)
+            }
+        }
+        None if opts.stream => {
+            eprintln!("Streaming kept segments with {} delay", delay);
+            stream_with_ffmpeg(opts, plan, merge_order, framerate, delay, report)
+        }
+        None => {
+            eprintln!("Splitting audio file with {} delay", delay);
+            split_pieces(
+                opts,
+                plan,
+                cut_times,
+                framerate,
+                delay,
+                &audio_map(opts),
+                report,
+            )
+        }
+    };
+    if let Some(ref padded) = padded {
+        remove_intermediate(opts, padded);
+    }
+    split_result?;
+    report.timings.add("split", split_start.elapsed());
+    if opts.stop_after == Stage::Split {
+        println!("Stopping after the split, leaving the pieces:");
+        for piece in split_pieces_created(opts, plan) {
+            println!("{}", piece.display());
+        }
+        return Ok(None);
+    }
+
+    // Put it back together, in the order the script joins the trims
+    for segment in &plan.segments {
+        if segment.keep {
+            report.kept_segments.push(segment.index);
+        } else {
+            report.discarded_segments.push(segment.index);
+        }
+    }
+    let written = if opts.stream {
+        // Streaming already wrote the output while splitting
+        if opts.preserve_mtime {
+            copy_mtime(&opts.input_aud, &opts.output_aud)?;
+        }
+        vec![opts.output_aud.clone()]
+    } else {
+        let merge_start = Instant::now();
+        let written = write_output(opts, plan, merge_order, report)?;
+        report.timings.add("merge", merge_start.elapsed());
+        written
+    };
+    if let Some(ref transcode) = opts.transcode {
+        let transcode_start = Instant::now();
+        transcode_output(opts, transcode, report)?;
+        report.timings.add("transcode", transcode_start.elapsed());
+    }
+    Ok(Some(written))
+}
+
+/// Marks, verifies and previews the finished output, as asked
+fn finish_output(
+    opts: &Config,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    framerate: Rational,
+    hash: String,
+    written: &[PathBuf],
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    if opts.mark_output {
+        let marker = Marker {
+            source: opts.input_aud.to_string_lossy().into_owned(),
+            trims_hash: hash,
+        };
+        for path in written {
+            write_marker(path, &marker)?;
+        }
+    }
+
+    if opts.verify {
+        let verify_start = Instant::now();
+        verify_output(opts, plan, merge_order, framerate, report)?;
+        report.timings.add("ffprobe", verify_start.elapsed());
+    }
+    if opts.preview {
+        preview_output(opts, plan, merge_order, framerate, report)?;
+    }
+    Ok(())
+}
+
+/// Cuts `other` at the same points as the audio and joins the same pieces,
+/// without the audio's delay, so that a video stays in step with its audio.
+fn also_split(
+    opts: &Config,
+    other: &Path,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    cut_times: &[String],
+    framerate: Rational,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let other_opts = with_work_dir(&Config {
+        input_aud: other.to_path_buf(),
+        output_aud: other.with_extension("trimmed.mkv"),
+        copy_codec: false,
+        audio_track: None,
+        all_tracks: true,
+        ..opts.clone()
+    });
+    eprintln!(
+        "Splitting {} at the same cut times",
+        other_opts.input_aud.display()
+    );
+    let result = create_work_dir(&other_opts)
+        .and_then(|_| split_pieces(&other_opts, plan, cut_times, framerate, 0, "0", report))
+        .and_then(|_| write_output(&other_opts, plan, merge_order, report));
+    let cleaned = if opts.cleanup.removes_pieces(result.is_ok()) {
+        clean_temporary_files(&other_opts, plan)
+    } else {
+        Ok(())
+    };
+    result.and(cleaned)
+}
+
+/// The frame at `framerate` nearest to `position`, which counts in `timebase`
+fn nearest_frame(position: usize, timebase: Rational, framerate: Rational) -> usize {
+    if timebase == framerate {
+        return position;
+    }
+    let nanos = frame_to_nanos(position, timebase);
+    let scale = framerate.den as u128 * 1_000_000_000;
+    ((nanos * framerate.num as u128 + scale / 2) / scale) as usize
+}
+
+/// Writes the pieces to join as `Trim` calls, joining pieces that follow on from each
+/// other back into one trim.
+///
+/// Cuts counted in time rather than frames are put on the nearest frame.
+fn output_avs_trims(
+    opts: &Config,
+    path: &Path,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    timebase: Rational,
+) -> Result<(), SplitError> {
+    let framerate = timing_framerate(opts);
+    let mut ranges: Vec<(usize, Option<usize>)> = Vec::new();
+    for &index in merge_order {
+        let segment = &plan.segments[index - 1];
+        let start = nearest_frame(segment.start, timebase, framerate);
+        let end = segment
+            .end
+            .map(|end| nearest_frame(end, timebase, framerate));
+        match ranges.last_mut() {
+            Some(&mut (_, ref mut last_end)) if *last_end == Some(start) => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    fs::write(path, format_avs_trims(&ranges)).map_err(|e| SplitError::Io(path.to_path_buf(), e))
+}
+
+/// Writes the pieces to join as an EDL against the input audio.
+///
+/// Cuts counted in time rather than frames are put on the nearest frame.
+fn output_edl(
+    opts: &Config,
+    path: &Path,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    timebase: Rational,
+    total_frames: Option<usize>,
+) -> Result<(), SplitError> {
+    let framerate = timing_framerate(opts);
+    let to_frame = |position: usize| nearest_frame(position, timebase, framerate);
+    let mut ranges = Vec::new();
+    for &index in merge_order {
+        let segment = &plan.segments[index - 1];
+        match segment.end.or(total_frames) {
+            Some(end) => ranges.push((to_frame(segment.start), to_frame(end))),
+            None => warn(
+                opts.warn_as_error,
+                format!(
+                    "leaving piece {} out of the EDL, the length of the audio isn't known",
+                    index
+                ),
+            )?,
+        }
+    }
+    let title = opts
+        .output_aud
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    write_edl(path, &title, &opts.input_aud, &ranges, framerate)
+}
+
+/// Joins the pieces in `merge_order` into the output, or keeps them as they are for
+/// `--no-merge`, and returns the files it wrote
+fn write_output(
+    opts: &Config,
+    plan: &SegmentPlan,
+    merge_order: &[usize],
+    report: &mut Report,
+) -> Result<Vec<PathBuf>, SplitError> {
+    let written = if opts.no_merge {
+        keep_pieces(opts, plan, merge_order)?
+    } else {
+        merge_pieces(opts, merge_order, report)?;
+        vec![opts.output_aud.clone()]
+    };
+    if opts.preserve_mtime {
+        for path in &written {
+            copy_mtime(&opts.input_aud, path)?;
+        }
+    }
+    Ok(written)
+}
+
+/// Where `transcode_output` moves the joined output to be encoded from
+fn transcode_source(opts: &Config) -> PathBuf {
+    opts.work_dir.join("transcode.mka")
+}
+
+/// Encodes the joined output for `--transcode`, by moving it aside and writing the
+/// encoded audio in its place
+fn transcode_output(
+    opts: &Config,
+    transcode: &Transcode,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let joined = transcode_source(opts);
+    move_file(&opts.output_aud, &joined).map_err(|e| SplitError::Io(joined.clone(), e))?;
+    println!("Transcoding output to {}", transcode.codec);
+    let result = ffmpeg::transcode(
+        &opts.ffmpeg,
+        &joined,
+        transcode,
+        &opts.output_aud,
+        ffmpeg_format(opts, true),
+        report,
+    );
+    remove_intermediate(opts, &joined);
+    result?;
+    if opts.preserve_mtime {
+        copy_mtime(&opts.input_aud, &opts.output_aud)?;
+    }
+    Ok(())
+}
+
+/// Stops a run on audio that this tool already split, unless `--force` is given.
+/// `hash` is the [`trims_hash`] of what this run would keep.
+fn check_marker(opts: &Config, hash: &str) -> Result<(), SplitError> {
+    let marker = match read_marker(&opts.input_aud) {
+        Some(marker) => marker,
+        None => return Ok(()),
+    };
+    let message = if marker.trims_hash == hash {
+        format!(
+            "{} was already split from {} with these same trims",
+            opts.input_aud.display(),
+            marker.source
+        )
+    } else {
+        format!(
+            "{} was already split from {}",
+            opts.input_aud.display(),
+            marker.source
+        )
+    };
+    if opts.force {
+        warn(
+            opts.warn_as_error,
+            format!("{}, splitting it again anyway", message),
+        )?;
+        Ok(())
+    } else {
+        Err(SplitError::CheckFailed(format!(
+            "{}, pass --force to split it again",
+            message
+        )))
+    }
+}
+
+/// Gives `target` the same modification time as `source`, for `--preserve-mtime`
+fn copy_mtime(source: &Path, target: &Path) -> Result<(), SplitError> {
+    let modified = fs::metadata(source)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| SplitError::Io(source.to_path_buf(), e))?;
+    File::options()
+        .write(true)
+        .open(target)
+        .and_then(|file| file.set_modified(modified))
+        .map_err(|e| SplitError::Io(target.to_path_buf(), e))
+}
+
+/// Splits the input with whichever backend is in use. `streams` is the ffmpeg `-map`
+/// for the streams to keep; mkvmerge always keeps them all.
+fn split_pieces(
+    opts: &Config,
+    plan: &SegmentPlan,
+    cut_times: &[String],
+    framerate: Rational,
+    delay: isize,
+    streams: &str,
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    if let Some(ref backend) = opts.custom_backend {
+        return split_with_custom(backend.as_ref(), opts, plan, cut_times, delay);
+    }
+    match opts.backend {
+        Backend::Mkvmerge => split_with_mkvmerge(opts, plan, cut_times, delay, report),
+        Backend::Ffmpeg => split_with_ffmpeg(opts, plan, framerate, delay, streams, report),
+    }
+}
+
+/// Splits with a [`SplitBackend`] of the library user's, and moves the pieces it returns
+/// to where the rest of the run looks for them
+fn split_with_custom(
+    backend: &dyn SplitBackend,
+    opts: &Config,
+    plan: &SegmentPlan,
+    cut_times: &[String],
+    delay: isize,
+) -> Result<(), SplitError> {
+    let pieces = backend.split(&opts.input_aud, cut_times, delay, &opts.work_dir)?;
+    if pieces.len() != plan.segments.len() {
+        return Err(SplitError::CheckFailed(format!(
+            "The custom backend returned {} pieces where {} were expected",
+            pieces.len(),
+            plan.segments.len()
+        )));
+    }
+    for (index, piece) in (1..).zip(&pieces) {
+        let target = piece_path(opts, index);
+        if *piece != target {
+            move_file(piece, &target).map_err(|e| SplitError::Io(target, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Joins the pieces in `merge_order` into the output
+fn merge_pieces(
+    opts: &Config,
+    merge_order: &[usize],
+    report: &mut Report,
+) -> Result<(), SplitError> {
+    let merge_files: Vec<PathBuf> = merge_order
+        .iter()
+        .map(|&index| piece_path(opts, index))
+        .collect();
+    if let Some(piece) = single_piece_moved(opts, &merge_files) {
+        if opts.verbose {
+            println!("Only one piece to join, moving it to the output");
+        }
+        return move_file(piece, &opts.output_aud)
+            .map_err(|e| SplitError::Io(opts.output_aud.clone(), e));
+    }
+    check_pieces_match(opts, merge_order, &merge_files)?;
+    join_files(opts, &merge_files, report)
+}
+
+/// The piece to move into place as the output, when there is only one to join.
+///
+/// Joining a single piece is just a remux, so it is moved instead, unless ffmpeg
+/// would have converted it to the output's format.
+fn single_piece_moved<'a>(opts: &Config, merge_files: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    match merge_files {
+        // A custom backend may do more than remux when it joins
+        _ if opts.custom_backend.is_some() => None,
+        [piece] if opts.backend == Backend::Mkvmerge => Some(piece),
+        [piece] if piece.extension() == opts.output_aud.extension() => Some(piece),
+        _ => None,
+    }
+}
+
+/// Checks that every piece has the sample rate, channel layout and sample format of the
+/// first, which the join needs and a source that changes format partway through would break
+fn check_pieces_match(
+    opts: &Config,
+    merge_order: &[usize],
+    merge_files: &[PathBuf],
+) -> Result<(), SplitError> {
+    let mut formats = Vec::new();
+    for (&index, piece) in merge_order.iter().zip(merge_files) {
+        // Pieces only hold the audio being split, so it is always the first audio stream
+        match probe_sample_format(&opts.ffprobe, piece, 0) {
+            Ok(format) => formats.push((index, piece.as_path(), format)),
+            Err(SplitError::ProcessNotFound(_)) => {
+                warn(
+                    opts.warn_as_error,
+                    "ffprobe not found, not checking the pieces match".to_owned(),
+                )?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    compare_piece_formats(&formats)
+}
+
+/// Compares the probed format of each piece, given with its index and path, to the first
+fn compare_piece_formats(pieces: &[(usize, &Path, SampleFormat)]) -> Result<(), SplitError> {
+    let describe = |format: &SampleFormat| match format.sample_fmt {
+        Some(ref sample_fmt) => format!(
+            "{}Hz {} {}",
+            format.sample_rate, format.channel_layout, sample_fmt
+        ),
+        None => format!("{}Hz {}", format.sample_rate, format.channel_layout),
+    };
+    let (first_index, first_format) = match pieces.first() {
+        Some((index, _, format)) => (*index, format),
+        None => return Ok(()),
+    };
+    match pieces.iter().find(|(_, _, format)| format != first_format) {
+        Some(&(index, piece, ref format)) => Err(SplitError::CheckFailed(format!(
+            "Piece {} ({}) is {}, but piece {} is {}, so they can't be joined",
+            index,
+            piece.display(),
+            describe(format),
+            first_index,
+            describe(first_format)
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Joins `files` into the output, in order
+fn join_files(opts: &Config, files: &[PathBuf], report: &mut Report) -> Result<(), SplitError> {
+    if let Some(ref backend) = opts.custom_backend {
+        return backend.merge(files, &opts.output_aud);
+    }
+    match opts.backend {
+        Backend::Mkvmerge => merge_with_mkvmerge(opts, files, report),
+        Backend::Ffmpeg => ffmpeg::concat(
+            &opts.ffmpeg,
+            files,
+            &temporary_path(opts, "txt"),
+            &opts.output_aud,
+            ffmpeg_format(opts, opts.copy_codec),
+            report,
+        ),
+    }
+}
+
+/// The mkvmerge backend as a [`SplitBackend`], for a custom backend to fall back on or to
+/// be compared with. `opts` gives the tool paths and every other setting of the split.
+#[derive(Debug, Clone)]
+pub struct MkvmergeBackend {
+    pub opts: Config,
+}
+
+impl SplitBackend for MkvmergeBackend {
+    fn split(
+        &self,
+        input: &Path,
+        cut_times: &[String],
+        delay: isize,
+        out_dir: &Path,
+    ) -> Result<Vec<PathBuf>, SplitError> {
+        let opts = Config {
+            input_aud: input.to_path_buf(),
+            work_dir: out_dir.to_path_buf(),
+            ..self.opts.clone()
+        };
+        let version = mkvmerge_version(&opts.mkvmerge)?;
+        let args = mkvmerge_split_args(&opts, cut_times, delay, version)?;
+        run_mkvmerge(&opts.mkvmerge, args, &mut Report::default())?;
+        let expected = cut_times.len() + 1;
+        renumber_mkvmerge_pieces(&opts, expected)?;
+        Ok((1..=expected)
+            .map(|index| piece_path(&opts, index))
+            .collect())
+    }
+
+    fn merge(&self, pieces: &[PathBuf], output: &Path) -> Result<(), SplitError> {
+        let opts = Config {
+            output_aud: output.to_path_buf(),
+            ..self.opts.clone()
+        };
+        run_mkvmerge(
+            &opts.mkvmerge,
+            mkvmerge_merge_args(&opts, pieces)?,
+            &mut Report::default(),
+        )
+        .map(|_| ())
+    }
+}
+
+/// The ffmpeg backend as a [`SplitBackend`], like [`MkvmergeBackend`]. Unlike a run
+/// with `--backend ffmpeg`, which only extracts the kept pieces, it extracts every one.
+#[derive(Debug, Clone)]
+pub struct FfmpegBackend {
+    pub opts: Config,
+}
+
+impl SplitBackend for FfmpegBackend {
+    fn split(
+        &self,
+        input: &Path,
+        cut_times: &[String],
+        delay: isize,
+        out_dir: &Path,
+    ) -> Result<Vec<PathBuf>, SplitError> {
+        let opts = Config {
+            input_aud: input.to_path_buf(),
+            work_dir: out_dir.to_path_buf(),
+            ..self.opts.clone()
+        };
+        let packets = packet_times(&opts)?;
+        // Output time t comes from input time t - delay, as in input_span
+        let shift = |cut: &String| {
+            let nanos = parse_timecode(cut)
+                .ok_or_else(|| SplitError::InvalidArgument(format!("Invalid cut time: {}", cut)))?;
+            let nanos = nanos as i128 - delay as i128 * 1_000_000;
+            Ok(snap_to_packet(nanos.max(0) as u128, &packets))
+        };
+        let mut starts = vec![shift(&format_nanos(0))?];
+        for cut in cut_times {
+            starts.push(shift(cut)?);
+        }
+        let mut pieces = Vec::new();
+        for (i, &start) in starts.iter().enumerate() {
+            let piece = piece_path(&opts, i + 1);
+            ffmpeg::extract_segment(
+                &opts.ffmpeg,
+                &opts.input_aud,
+                (start, starts.get(i + 1).cloned()),
+                &audio_map(&opts),
+                &piece,
+                ffmpeg_format(&opts, opts.copy_codec),
+                &mut Report::default(),
+            )?;
+            pieces.push(piece);
+        }
+        Ok(pieces)
+    }
+
+    fn merge(&self, pieces: &[PathBuf], output: &Path) -> Result<(), SplitError> {
+        let opts = Config {
+            output_aud: output.to_path_buf(),
+            ..self.opts.clone()
+        };
+        // The list goes with the pieces, in the directory of the run that split them
+        let list = match pieces.first() {
+            Some(piece) => piece.with_file_name("split.txt"),
+            None => {
+                return Err(SplitError::InvalidArgument(
+                    "There are no pieces to join".to_owned(),
+                ))
+            }
+        };
+        ffmpeg::concat(
+            &opts.ffmpeg,
+            pieces,
+            &list,
+            &opts.output_aud,
+            ffmpeg_format(&opts, opts.copy_codec),
+            &mut Report::default(),
+        )
+    }
+}
+
+/// The split pieces a run writes: mkvmerge and a custom backend write every piece,
+/// ffmpeg only the kept ones, and streaming none at all
+fn split_pieces_created(opts: &Config, plan: &SegmentPlan) -> Vec<PathBuf> {
+    let every_piece = opts.custom_backend.is_some() || opts.backend == Backend::Mkvmerge;
+    plan.segments
+        .iter()
+        .filter(|_| !opts.stream)
+        .filter(|segment| every_piece || segment.keep)
+        .map(|segment| piece_path(opts, segment.index))
+        .collect()
+}
+
+/// Deletes the split pieces this run wrote, and nothing else
+fn clean_temporary_files(opts: &Config, plan: &SegmentPlan) -> Result<(), SplitError> {
+    // Every piece is tried even once one couldn't be removed
+    let mut result = Ok(());
+    for piece in split_pieces_created(opts, plan) {
+        if let Err(e) = fs::remove_file(&piece) {
+            if e.kind() != io::ErrorKind::NotFound {
+                result = result.and(warn(
+                    opts.warn_as_error,
+                    format!("could not remove {}: {}", piece.display(), e),
+                ));
+            }
+        }
+    }
+    result.and(remove_work_dir(opts))
+}
+
+/// Prints the framerate cuts are counted at for `--print-framerate`, and the field rate
+/// as well with `--fields`
+fn print_framerate(opts: &Config) -> Result<(), SplitError> {
+    println!(
+        "Framerate: {} ({:.6} fps)",
+        opts.framerate,
+        opts.framerate.as_f32()
+    );
+    if opts.fields {
+        let field_rate = timing_framerate(opts);
+        println!(
+            "Field rate: {} ({:.6} fps)",
+            field_rate,
+            field_rate.as_f32()
+        );
+    }
+    Ok(())
+}
+
+/// Prints the trims found in the script, without needing the audio unless a trim counts
+/// from the end of the clip
+fn print_trims(opts: &Config) -> Result<(), SplitError> {
+    let framerate = timing_framerate(opts);
+    let (mut trims, checked) = script_trims(opts, framerate)?;
+    if trims.is_empty() {
+        return Err(SplitError::NoTrims(checked.to_owned()));
+    }
+    if opts.input_aud.as_os_str().is_empty() {
+        if let Some(trim) = trims.iter().find(|trim| trim.needs_length()) {
+            return Err(SplitError::InvalidTrim {
+                line: trim.line,
+                text: trim.text.clone(),
+                message: "counts from the end of the clip, so -i is needed to place it".to_owned(),
+            });
+        }
+    } else {
+        let total_frames = get_total_frames(opts, framerate)?;
+        for trim in &mut trims {
+            trim.resolve_length(total_frames)?;
+        }
+    }
+    for trim in &trims {
+        println!(
+            "Line {}: frames {}-{}, {} - {} ({})",
+            trim.line,
+            trim.start,
+            trim.end.saturating_sub(1),
+            frame_to_timecode(trim.start, framerate),
+            frame_to_timecode(trim.end, framerate),
+            trim.text
+        );
+    }
+    Ok(())
+}
+
+fn print_audio_info(opts: &Config) -> Result<(), SplitError> {
+    let streams = probe_audio_streams(&opts.ffprobe, &opts.input_aud)?;
+    if streams.is_empty() {
+        return Err(SplitError::CheckFailed(format!(
+            "No audio streams found in {}",
+            opts.input_aud.display()
+        )));
+    }
+    let unknown = || "unknown".to_owned();
+    for stream in &streams {
+        println!("Stream #{}", stream.index);
+        match stream.codec_long_name {
+            Some(ref long_name) => println!("  Codec:       {} ({})", stream.codec, long_name),
+            None => println!("  Codec:       {}", stream.codec),
+        }
+        println!(
+            "  Sample rate: {}",
+            stream
+                .sample_rate
+                .as_ref()
+                .map_or_else(unknown, |rate| format!("{} Hz", rate))
+        );
+        println!(
+            "  Channels:    {}{}",
+            stream
+                .channels
+                .map_or_else(unknown, |channels| channels.to_string()),
+            stream
+                .channel_layout
+                .as_ref()
+                .map_or_else(String::new, |layout| format!(" ({})", layout))
+        );
+        println!(
+            "  Bit depth:   {}{}",
+            stream
+                .bit_depth
+                .map_or_else(unknown, |depth| depth.to_string()),
+            stream
+                .sample_format
+                .as_ref()
+                .map_or_else(String::new, |format| format!(" (sample format {})", format))
+        );
+        if let Some(ref language) = stream.language {
+            println!("  Language:    {}", language);
+        }
+    }
+    Ok(())
+}
+
+/// Splits the audio of each `--compile` pair by its script and joins the results into
+/// one output, in the order the pairs were given.
+///
+/// The audio files are checked up front to all have the same format, since the join
+/// copies the streams as they are. Each pair is written to the run's work directory as
+/// `compile-001.mka` and so on, which are removed afterwards unless `--cleanup` says
+/// otherwise. Unlike `--pair-dir`, the first pair to fail stops the run.
+fn split_compilation(opts: &Config) -> Result<(), SplitError> {
+    let mut first: Option<(&Path, (String, String, String))> = None;
+    for (_, audio) in &opts.compile {
+        let track = audio_track(opts);
+        let codec = probe_codec(&opts.ffprobe, audio, track)?;
+        let SampleFormat {
+            sample_rate,
+            channel_layout,
+            ..
+        } = probe_sample_format(&opts.ffprobe, audio, track)?;
+        let format = (codec, sample_rate, channel_layout);
+        match first {
+            Some((first_audio, ref first_format)) if *first_format != format => {
+                return Err(SplitError::InvalidArgument(format!(
+                    "Can't join {} {}Hz {} audio from {} with {} {}Hz {} audio from {}",
+                    format.0,
+                    format.1,
+                    format.2,
+                    audio.display(),
+                    first_format.0,
+                    first_format.1,
+                    first_format.2,
+                    first_audio.display()
+                )));
+            }
+            Some(_) => (),
+            None => first = Some((audio, format)),
+        }
+    }
+
+    let opts = &with_work_dir(opts);
+    prepare_output_dir(opts)?;
+    create_work_dir(opts)?;
+    let mut parts = Vec::new();
+    let mut result = Ok(());
+    for (i, (script, audio)) in opts.compile.iter().enumerate() {
+        eprintln!(
+            "Compiling part {} from {} and {}",
+            i + 1,
+            audio.display(),
+            script.display()
+        );
+        let mut part_opts = opts.clone();
+        part_opts.input_aud = audio.clone();
+        part_opts.input_avs = Some(script.clone());
+        part_opts.output_aud = opts.work_dir.join(format!("compile-{:03}.mka", i + 1));
+        part_opts.compile = Vec::new();
+        // Only the joined output is encoded and handed to the hook
+        part_opts.transcode = None;
+        part_opts.post_hook = None;
+        result = resolve_framerate(&mut part_opts)
+            .and_then(|_| split_audio(&part_opts))
+            .map(|_| ());
+        if part_opts.output_aud.exists() {
+            parts.push(part_opts.output_aud);
+        }
+        if result.is_err() {
+            break;
+        }
+    }
+
+    if result.is_ok() {
+        println!(
+            "Joining {} parts into {}",
+            parts.len(),
+            opts.output_aud.display()
+        );
+        let mut report = Report::default();
+        result = join_files(opts, &parts, &mut report);
+        if result.is_ok() {
+            if let Some(ref transcode) = opts.transcode {
+                result = transcode_output(opts, transcode, &mut report);
+            }
+        }
+    }
+    if opts.cleanup.removes_pieces(result.is_ok()) {
+        for part in &parts {
+            let _ = fs::remove_file(part);
+        }
+        result = result.and(remove_work_dir(opts));
+    }
+    result?;
+    match opts.post_hook {
+        Some(ref template) => run_post_hook(template, &opts.output_aud, None, None),
+        None => Ok(()),
+    }
+}
+
+/// Splits every audio file in the `--pair-dir` that has a matching script.
+///
+/// A failed pair doesn't stop the others; the first error is returned once all
+/// of them have been tried.
+fn split_pairs(opts: &Config, dir: &Path) -> Result<(), SplitError> {
+    let (pairs, unmatched) = find_pairs(dir)?;
+    let mut first_error = None;
+    let mut failed = 0;
+    let mut skipped = 0;
+    for pair in &pairs {
+        let output = opts.output_aud.join(format!("{}.mka", pair.name));
+        if opts.incremental && !opts.force && is_up_to_date(&output, &[&pair.audio, &pair.script]) {
+            if opts.verbose {
+                eprintln!("Skipping {}, {} is up to date", pair.name, output.display());
+            }
+            skipped += 1;
+            continue;
+        }
+        eprintln!(
+            "Pairing {} with {}",
+            pair.audio.display(),
+            pair.script.display()
+        );
+        let mut pair_opts = opts.clone();
+        pair_opts.input_aud = pair.audio.clone();
+        pair_opts.input_avs = Some(pair.script.clone());
+        pair_opts.output_aud = output;
+        let result = resolve_framerate(&mut pair_opts)
+            .and_then(|_| split_audio(&pair_opts))
+            .map(|_| ());
+        if let Err(e) = result {
+            eprintln!("{} {}: {}", error_label(opts), pair.name, e);
+            failed += 1;
+            first_error = first_error.or(Some(e));
+        }
+    }
+
+    if opts.incremental {
+        eprintln!(
+            "Split {} of {} pairs, skipped {} that were up to date",
+            pairs.len() - skipped - failed,
+            pairs.len() - skipped,
+            skipped
+        );
+    } else {
+        eprintln!("Split {} of {} pairs", pairs.len() - failed, pairs.len());
+    }
+    for path in &unmatched {
+        warn(
+            opts.warn_as_error,
+            format!("no match for {}", path.display()),
+        )?;
+    }
+    if pairs.is_empty() {
+        return Err(SplitError::InvalidArgument(format!(
+            "No audio and script pairs found in {}",
+            dir.display()
+        )));
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Whether `output` exists and was modified no earlier than any of `inputs`.
+/// An output given its input's time by `--preserve-mtime` counts as up to date.
+fn is_up_to_date(output: &Path, inputs: &[&Path]) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    let output_time = match modified(output) {
+        Ok(time) => time,
+        Err(_) => return false,
+    };
+    inputs
+        .iter()
+        .all(|input| modified(input).is_ok_and(|time| time <= output_time))
+}
+
+/// The `Error:` that starts an error message, in red when stderr is coloured
+pub fn error_label(opts: &Config) -> String {
+    paint(opts.color.enabled(io::stderr()), "31", "Error:")
+}
+
+/// Runs whatever mode the flags asked for
+pub fn run(options: &mut Config) -> Result<(), SplitError> {
+    if options.doctor {
+        return run_doctor(
+            &options.mkvmerge,
+            &options.ffprobe,
+            &options.ffmpeg,
+            options.backend,
+            options.color.enabled(io::stdout()),
+        );
+    }
+    if let Some(dir) = options.pair_dir.clone() {
+        return split_pairs(options, &dir);
+    }
+    if !options.compile.is_empty() {
+        return split_compilation(options);
+    }
+    if options.identify {
+        println!(
+            "{}",
+            identify(&options.mkvmerge, &options.input_aud)?.to_pretty_string()
+        );
+        return Ok(());
+    }
+    if options.audio_info {
+        return print_audio_info(options);
+    }
+    if options.dump_script {
+        print!("{}", script_for_trims(options)?);
+        return Ok(());
+    }
+    resolve_framerate(options)?;
+    if options.print_trims {
+        return print_trims(options);
+    }
+    if options.print_framerate {
+        print_framerate(options)
+    } else {
+        split_audio(options).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segments::plan_segments;
+    use std::env;
+
+    /// An empty directory of the test's own under the system temp directory
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("split_aud-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Options writing into `dir`, with tools that aren't there unless a test gives them
+    fn test_opts(dir: &Path) -> Config {
+        Config {
+            output_aud: dir.join("out.mka"),
+            work_dir: dir.join("out.split"),
+            ffprobe: dir.join("no-ffprobe"),
+            ffmpeg: dir.join("no-ffmpeg"),
+            mkvmerge: dir.join("no-mkvmerge"),
+            ..Config::default()
+        }
+    }
+
+    /// Options for splitting by the trims of `script`, written into `dir`
+    fn script_opts(dir: &Path, script: &str) -> Config {
+        let input_avs = dir.join("in.avs");
+        fs::write(&input_avs, script).unwrap();
+        Config {
+            input_avs: Some(input_avs),
+            ..test_opts(dir)
+        }
+    }
+
+    /// Writes a shell script into `dir` to stand in for a tool
+    #[cfg(unix)]
+    fn fake_tool(dir: &Path, name: &str, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Writes `pieces` numbered files of its own naming, and joins nothing
+    #[derive(Debug)]
+    struct TouchBackend {
+        pieces: usize,
+    }
+
+    impl SplitBackend for TouchBackend {
+        fn split(
+            &self,
+            _input: &Path,
+            _cut_times: &[String],
+            _delay: isize,
+            out_dir: &Path,
+        ) -> Result<Vec<PathBuf>, SplitError> {
+            (1..=self.pieces)
+                .map(|index| {
+                    let piece = out_dir.join(format!("part{}.bin", index));
+                    fs::write(&piece, index.to_string())
+                        .map_err(|e| SplitError::Io(piece.clone(), e))?;
+                    Ok(piece)
+                })
+                .collect()
+        }
+
+        fn merge(&self, _pieces: &[PathBuf], _output: &Path) -> Result<(), SplitError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_backend_pieces_are_moved_into_place() {
+        let dir = test_dir("custom-backend");
+        let opts = test_opts(&dir);
+        create_work_dir(&opts).unwrap();
+        let plan = plan_segments(&[(10, 20)], None);
+        let backend = TouchBackend { pieces: 3 };
+        split_with_custom(&backend, &opts, &plan, &[], 0).unwrap();
+        for index in 1..=3 {
+            let piece = fs::read_to_string(piece_path(&opts, index)).unwrap();
+            assert_eq!(piece, index.to_string());
+        }
+        assert_eq!(
+            split_pieces_created(
+                &Config {
+                    custom_backend: Some(Arc::new(TouchBackend { pieces: 3 })),
+                    ..opts.clone()
+                },
+                &plan
+            )
+            .len(),
+            3
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn custom_backend_must_return_every_piece() {
+        let dir = test_dir("custom-backend-count");
+        let opts = test_opts(&dir);
+        create_work_dir(&opts).unwrap();
+        let plan = plan_segments(&[(10, 20)], None);
+        let result = split_with_custom(&TouchBackend { pieces: 2 }, &opts, &plan, &[], 0);
+        assert!(matches!(result, Err(SplitError::CheckFailed(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Writes pieces `1..=count` into the work directory, each holding its number
+    fn write_pieces(opts: &Config, count: usize) {
+        create_work_dir(opts).unwrap();
+        for index in 1..=count {
+            fs::write(piece_path(opts, index), index.to_string()).unwrap();
+        }
+    }
+
+    #[test]
+    fn an_extra_leading_mkvmerge_piece_is_dropped() {
+        let dir = test_dir("leading-piece");
+        let opts = test_opts(&dir);
+        write_pieces(&opts, 4);
+        renumber_mkvmerge_pieces(&opts, 3).unwrap();
+        for index in 1..=3 {
+            let piece = fs::read_to_string(piece_path(&opts, index)).unwrap();
+            assert_eq!(piece, (index + 1).to_string());
+        }
+        assert!(!piece_path(&opts, 4).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pieces_without_a_leading_one_are_left_alone() {
+        let dir = test_dir("no-leading-piece");
+        let opts = test_opts(&dir);
+        write_pieces(&opts, 3);
+        renumber_mkvmerge_pieces(&opts, 3).unwrap();
+        for index in 1..=3 {
+            let piece = fs::read_to_string(piece_path(&opts, index)).unwrap();
+            assert_eq!(piece, index.to_string());
+        }
+        // Any other count is only warned about, unless warnings are errors
+        write_pieces(&opts, 5);
+        assert!(renumber_mkvmerge_pieces(&opts, 3).is_ok());
+        let strict = Config {
+            warn_as_error: true,
+            ..opts.clone()
+        };
+        assert!(renumber_mkvmerge_pieces(&strict, 3).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_explicit_delay_wins_over_everything_detected() {
+        let dir = test_dir("delay-precedence");
+        let input_aud = dir.join("Ep01 DELAY -50ms.mka");
+        fs::write(dir.join("Ep01 DELAY -50ms.log"), "Delay: 30ms").unwrap();
+        let log = dir.join("given.log");
+        fs::write(&log, "eac3to\nDelay: +120ms\n").unwrap();
+        let opts = Config {
+            input_aud,
+            delay: Some(-7),
+            delay_log: Some(log),
+            // The container says the audio starts 21ms in, which is only a fallback
+            ffprobe: fake_tool(&dir, "ffprobe", "echo 0.021000"),
+            ..test_opts(&dir)
+        };
+        assert_eq!(detect_delay(&opts).unwrap(), (-7, "--delay"));
+        let opts = Config {
+            delay: None,
+            ..opts
+        };
+        assert_eq!(detect_delay(&opts).unwrap(), (120, "--delay-log"));
+        let opts = Config {
+            delay_log: None,
+            ..opts
+        };
+        assert_eq!(detect_delay(&opts).unwrap(), (-50, "filename"));
+        let opts = Config {
+            input_aud: dir.join("Ep01.mka"),
+            ..opts
+        };
+        assert_eq!(detect_delay(&opts).unwrap(), (-21, "start_time"));
+        let opts = Config {
+            edit_compensation: false,
+            ..opts
+        };
+        assert_eq!(detect_delay(&opts).unwrap(), (0, "default"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_log_next_to_the_audio_comes_last() {
+        let dir = test_dir("delay-log");
+        let opts = Config {
+            input_aud: dir.join("Ep01.mka"),
+            ..test_opts(&dir)
+        };
+        assert_eq!(detect_delay(&opts).unwrap(), (0, "default"));
+        fs::write(dir.join("Ep01 - Log.txt"), "Delay: -12ms").unwrap();
+        assert_eq!(detect_delay(&opts).unwrap(), (-12, "log"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_first_of_several_filename_markers_is_used() {
+        let opts = Config {
+            input_aud: PathBuf::from("Ep01 DELAY 10ms DELAY -20ms.mka"),
+            ..Config::default()
+        };
+        assert_eq!(detect_delay(&opts).unwrap(), (10, "filename"));
+        let strict = Config {
+            warn_as_error: true,
+            ..opts
+        };
+        assert!(detect_delay(&strict).is_err());
+    }
+
+    #[test]
+    fn a_delay_log_without_a_delay_is_an_error() {
+        let dir = test_dir("delay-log-empty");
+        let log = dir.join("given.log");
+        fs::write(&log, "no delay here").unwrap();
+        let opts = Config {
+            delay_log: Some(log),
+            ..test_opts(&dir)
+        };
+        assert!(matches!(
+            detect_delay(&opts),
+            Err(SplitError::InvalidArgument(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cleanup_removes_only_the_pieces_it_wrote() {
+        let dir = test_dir("cleanup");
+        let opts = Config {
+            backend: Backend::Ffmpeg,
+            ..test_opts(&dir)
+        };
+        let plan = plan_segments(&[(100, 200), (300, 400)], Some(500));
+        write_pieces(&opts, 5);
+        // A file named like a piece next to the output isn't the run's
+        let unrelated = dir.join("out.split-001.mka");
+        fs::write(&unrelated, "keep").unwrap();
+        clean_temporary_files(&opts, &plan).unwrap();
+        // ffmpeg only wrote the kept pieces, so the dropped ones aren't the run's either
+        assert!(!piece_path(&opts, 2).exists());
+        assert!(!piece_path(&opts, 4).exists());
+        for index in [1, 3, 5] {
+            assert!(piece_path(&opts, index).exists());
+        }
+        assert!(opts.work_dir.exists());
+        assert_eq!(fs::read_to_string(&unrelated).unwrap(), "keep");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cleanup_removes_the_work_directory_once_empty() {
+        let dir = test_dir("cleanup-dir");
+        let opts = test_opts(&dir);
+        let plan = plan_segments(&[(100, 200)], Some(500));
+        write_pieces(&opts, 3);
+        clean_temporary_files(&opts, &plan).unwrap();
+        assert!(!opts.work_dir.exists());
+        // A piece that is already gone isn't an error
+        create_work_dir(&opts).unwrap();
+        clean_temporary_files(&opts, &plan).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn streaming_writes_no_pieces_to_clean() {
+        let opts = Config {
+            backend: Backend::Ffmpeg,
+            stream: true,
+            ..Config::default()
+        };
+        let plan = plan_segments(&[(100, 200)], Some(500));
+        assert!(split_pieces_created(&opts, &plan).is_empty());
+    }
+
+    #[test]
+    fn a_lone_kept_piece_is_moved_into_place() {
+        let dir = test_dir("lone-piece");
+        let opts = test_opts(&dir);
+        write_pieces(&opts, 3);
+        merge_pieces(&opts, &[2], &mut Report::default()).unwrap();
+        assert_eq!(fs::read_to_string(&opts.output_aud).unwrap(), "2");
+        assert!(!piece_path(&opts, 2).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_lone_piece_is_only_moved_when_joining_would_just_remux_it() {
+        let pieces = vec![PathBuf::from("split-001.mka")];
+        let mkvmerge = Config {
+            output_aud: PathBuf::from("out.mka"),
+            ..Config::default()
+        };
+        assert!(single_piece_moved(&mkvmerge, &pieces).is_some());
+        assert!(single_piece_moved(&mkvmerge, &[pieces[0].clone(), pieces[0].clone()]).is_none());
+        let ffmpeg = Config {
+            backend: Backend::Ffmpeg,
+            ..mkvmerge.clone()
+        };
+        assert!(single_piece_moved(&ffmpeg, &pieces).is_some());
+        let converted = Config {
+            output_aud: PathBuf::from("out.opus"),
+            ..ffmpeg
+        };
+        assert!(single_piece_moved(&converted, &pieces).is_none());
+        let custom = Config {
+            custom_backend: Some(Arc::new(TouchBackend { pieces: 1 })),
+            ..mkvmerge
+        };
+        assert!(single_piece_moved(&custom, &pieces).is_none());
+    }
+
+    #[test]
+    fn work_directories_never_land_on_an_existing_file() {
+        let dir = test_dir("work-dir");
+        let opts = test_opts(&dir);
+        let first = with_work_dir(&opts);
+        assert_eq!(
+            first.work_dir,
+            dir.join(format!("out.split-{}", process::id()))
+        );
+        create_work_dir(&first).unwrap();
+        let second = with_work_dir(&opts);
+        assert_eq!(
+            second.work_dir,
+            dir.join(format!("out.split-{}-2", process::id()))
+        );
+        // Nor on a file of that name
+        fs::write(&second.work_dir, "").unwrap();
+        assert_eq!(
+            with_work_dir(&opts).work_dir,
+            dir.join(format!("out.split-{}-3", process::id()))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn temp_dir_holds_the_work_directory_and_every_intermediate() {
+        let opts = with_work_dir(&Config {
+            output_aud: PathBuf::from("/videos/out.mka"),
+            temp_dir: Some(PathBuf::from("/scratch/nonexistent")),
+            ..Config::default()
+        });
+        let work_dir = PathBuf::from(format!("/scratch/nonexistent/out.split-{}", process::id()));
+        assert_eq!(opts.work_dir, work_dir);
+        for path in [
+            piece_path(&opts, 1),
+            temporary_path(&opts, "txt"),
+            padded_path(&opts),
+            transcode_source(&opts),
+        ] {
+            assert_eq!(path.parent(), Some(work_dir.as_path()));
+        }
+    }
+
+    const FILM: Rational = Rational {
+        num: 24000,
+        den: 1001,
+    };
+
+    #[test]
+    fn a_single_frame_trim_keeps_its_frame() {
+        let opts = Config {
+            warn_as_error: true,
+            ..Config::default()
+        };
+        let trims = parse_trims("Trim(100, 100)", None, false, false).unwrap();
+        assert_eq!(trim_ranges(&opts, &trims, FILM).unwrap(), vec![(100, 101)]);
+        assert_ne!(frame_to_timecode(100, FILM), frame_to_timecode(101, FILM));
+    }
+
+    #[test]
+    fn an_empty_trim_is_skipped_or_widened() {
+        let trims = parse_trims("clip = clip[100:100]", None, false, false).unwrap();
+        assert_eq!(
+            trim_ranges(&Config::default(), &trims, FILM).unwrap(),
+            vec![]
+        );
+        let opts = Config {
+            widen_short_trims: true,
+            ..Config::default()
+        };
+        assert_eq!(trim_ranges(&opts, &trims, FILM).unwrap(), vec![(100, 101)]);
+        let opts = Config {
+            warn_as_error: true,
+            ..Config::default()
+        };
+        assert!(trim_ranges(&opts, &trims, FILM).is_err());
+    }
+
+    #[test]
+    fn trims_are_placed_in_a_clip_of_the_audio_length() {
+        let script = "src = BlankClip()\nsrc.Trim(0, 99) ++ src.Trim(400, 0)\n";
+        let mut trims = parse_trims(script, None, false, false).unwrap();
+        // 20.85 seconds runs into a 500th frame
+        let total_frames = frames_in_duration(20.85, FILM);
+        assert_eq!(total_frames, 500);
+        for trim in &mut trims {
+            trim.resolve_length(total_frames).unwrap();
+        }
+        let ranges: Vec<_> = trims.iter().map(|trim| (trim.start, trim.end)).collect();
+        assert_eq!(ranges, vec![(0, 100), (400, 500)]);
+        assert_eq!(frames_in_duration(0.0, FILM), 0);
+    }
+
+    #[test]
+    fn a_script_without_trims_parses_to_none() {
+        assert_eq!(
+            parse_trims("src = BlankClip()\nreturn src\n", None, false, false).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn the_last_trim_snaps_to_the_end_within_the_tolerance() {
+        let ends = |script: &str| {
+            let mut trims = parse_trims(script, None, false, false).unwrap();
+            snap_to_end(&mut trims, 500, 3);
+            trims.iter().map(|trim| trim.end).collect::<Vec<_>>()
+        };
+        // Three frames short of the end, and three past it
+        assert_eq!(ends("Trim(0, 99) ++ Trim(400, 496)"), vec![100, 500]);
+        assert_eq!(ends("Trim(400, 502) ++ Trim(0, 99)"), vec![500, 100]);
+        // Four is too far, and only the trim that ends last moves
+        assert_eq!(ends("Trim(0, 99) ++ Trim(400, 495)"), vec![100, 496]);
+        assert_eq!(ends("Trim(0, 496) ++ Trim(0, 99)"), vec![500, 100]);
+        assert_eq!(ends("Trim(0, 99)"), vec![100]);
+    }
+
+    #[test]
+    fn a_trims_comment_wins_over_the_trims_in_the_code() {
+        let dir = test_dir("trims-comment");
+        let opts = script_opts(
+            &dir,
+            "# TRIMS: 10-20, 30-40\nsrc.Trim(0, 100) ++ src.Trim(200, 300)\n",
+        );
+        let (trims, checked) = script_trims(&opts, FILM).unwrap();
+        let ranges: Vec<_> = trims.iter().map(|trim| (trim.start, trim.end)).collect();
+        assert_eq!(ranges, vec![(10, 21), (30, 41)]);
+        assert_eq!(checked, "the trims comment");
+        let opts = Config {
+            trims_keyword: "CUTS".to_owned(),
+            ..opts
+        };
+        let (trims, _) = script_trims(&opts, FILM).unwrap();
+        assert_eq!(trims[0].start, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn audio_dub_in_the_script_is_warned_about() {
+        let dir = test_dir("audio-dub");
+        let opts = Config {
+            warn_as_error: true,
+            ..script_opts(&dir, "AudioDub(v, a)\nTrim(0, 100)\n")
+        };
+        assert!(matches!(
+            script_trims(&opts, FILM),
+            Err(SplitError::CheckFailed(ref message)) if message.contains("AudioDub")
+        ));
+        let opts = script_opts(&dir, "# AudioDub(v, a)\nTrim(0, 100)\n");
+        let opts = Config {
+            warn_as_error: true,
+            ..opts
+        };
+        assert!(script_trims(&opts, FILM).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn written_avs_trims_join_pieces_that_follow_on() {
+        let dir = test_dir("avs-trims");
+        let opts = test_opts(&dir);
+        let ranges = [(0, 100), (100, 200), (300, 400)];
+        let mut plan = plan_segments(&ranges, Some(500));
+        let merge_order = plan.select_pieces(&ranges, false, None).unwrap();
+        let path = dir.join("trims.avs");
+        let timebase = timing_framerate(&opts);
+        output_avs_trims(&opts, &path, &plan, &merge_order, timebase).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert_eq!(written, "Trim(0, 199) ++ \\\nTrim(300, 399)\n");
+        let read: Vec<_> = parse_trims(&written, None, false, false)
+            .unwrap()
+            .iter()
+            .map(|trim| (trim.start, trim.end))
+            .collect();
+        assert_eq!(read, vec![(0, 200), (300, 400)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn trims_move_back_by_the_start_frame() {
+        let opts = Config {
+            start_frame: 1000,
+            framerate: FILM,
+            ..Config::default()
+        };
+        let trims = parse_trims(
+            "Trim(500, 999) ++ Trim(900, 1099) ++ Trim(1200, 1299) ++ Trim(1500, 0)",
+            None,
+            false,
+            false,
+        )
+        .unwrap();
+        let shifted = shift_to_start_frame(&opts, trims.clone(), FILM).unwrap();
+        let ranges: Vec<_> = shifted
+            .iter()
+            .map(|trim| (trim.start, trim.end, trim.to_end))
+            .collect();
+        // The first is skipped, the second clamped and the rest moved back
+        assert_eq!(
+            ranges,
+            vec![(0, 100, false), (200, 300, false), (500, 0, true)]
+        );
+        // Skipping and clamping are both warned about
+        let strict = Config {
+            warn_as_error: true,
+            ..opts.clone()
+        };
+        assert!(shift_to_start_frame(&strict, trims[2..].to_vec(), FILM).is_ok());
+        assert!(shift_to_start_frame(&strict, trims[..1].to_vec(), FILM).is_err());
+        assert!(shift_to_start_frame(&strict, trims[1..2].to_vec(), FILM).is_err());
+    }
+
+    #[test]
+    fn the_start_frame_is_timed_for_cuts_in_seconds() {
+        let opts = Config {
+            start_frame: 24,
+            framerate: Rational { num: 24, den: 1 },
+            ..Config::default()
+        };
+        let trims = cut_trims(
+            &[JobCut {
+                start: "1.5".to_owned(),
+                end: "2.5".to_owned(),
+                text: "1.5-2.5".to_owned(),
+            }],
+            CutsUnit::Seconds,
+            opts.framerate,
+        )
+        .unwrap();
+        let nanos = CutsUnit::Seconds.timebase(opts.framerate);
+        let shifted = shift_to_start_frame(&opts, trims, nanos).unwrap();
+        assert_eq!(
+            (shifted[0].start, shifted[0].end),
+            (500_000_000, 1_500_000_000)
+        );
+    }
+
+    #[test]
+    fn silence_replaces_a_positive_delay() {
+        let dir = test_dir("silence-positive");
+        let opts = Config {
+            input_aud: dir.join("in.mka"),
+            backend: Backend::Ffmpeg,
+            delay_mode: DelayMode::Silence,
+            ..test_opts(&dir)
+        };
+        let plan = plan_segments(&[(0, 500)], Some(500));
+        let commands = planned_commands(&opts, &plan, &[1], &[], FILM, 250).unwrap();
+        // The padded copy already starts with the delay, so it is copied on as it is
+        let args = &commands[0];
+        assert!(args.windows(2).any(|pair| pair == ["-itsoffset", "0.000"]));
+        let padded = padded_path(&opts).to_string_lossy().into_owned();
+        assert!(args
+            .windows(2)
+            .any(|pair| pair[0] == "-i" && pair[1] == padded));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_negative_delay_is_still_applied_as_an_offset_in_silence_mode() {
+        let dir = test_dir("silence-negative");
+        let opts = Config {
+            input_aud: dir.join("in.mka"),
+            backend: Backend::Ffmpeg,
+            delay_mode: DelayMode::Silence,
+            ..test_opts(&dir)
+        };
+        let plan = plan_segments(&[(0, 500)], Some(500));
+        let commands = planned_commands(&opts, &plan, &[1], &[], FILM, -50).unwrap();
+        // Silence can't take audio away, so the input is shifted earlier instead
+        let args = &commands[0];
+        assert!(args.windows(2).any(|pair| pair == ["-itsoffset", "-0.050"]));
+        let input = opts.input_aud.to_string_lossy().into_owned();
+        assert!(args
+            .windows(2)
+            .any(|pair| pair[0] == "-i" && pair[1] == input));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deterministic_runs_use_the_same_paths_and_arguments() {
+        let dir = test_dir("deterministic");
+        let opts = Config {
+            deterministic: true,
+            ..script_opts(&dir, "Trim(0, 99)")
+        };
+        let first = with_work_dir(&opts);
+        let second = with_work_dir(&opts);
+        assert_eq!(first.work_dir, dir.join("out.split"));
+        assert_eq!(first.work_dir, second.work_dir);
+        assert_eq!(piece_path(&first, 1), piece_path(&second, 1));
+        let args = deterministic_args(&first);
+        assert_eq!(args[0], "--deterministic");
+        assert_eq!(args, deterministic_args(&second));
+        assert!(ffmpeg_format(&opts, false).bitexact);
+        // A different script seeds the UIDs differently
+        let other_dir = test_dir("deterministic-other");
+        let other = Config {
+            deterministic: true,
+            ..script_opts(&other_dir, "Trim(0, 199)")
+        };
+        assert_ne!(deterministic_args(&other), args);
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&other_dir).unwrap();
+    }
+
+    /// Where each packet of 48kHz AAC starts, 1024 samples apiece, as ffprobe lists them
+    fn aac_packets(count: u128) -> Vec<u128> {
+        (0..count)
+            .map(|packet| (packet * 1024 * 1_000_000_000 + 24_000) / 48_000)
+            .collect()
+    }
+
+    #[test]
+    fn cuts_snap_to_the_nearest_packet() {
+        let packets = vec![0, 100, 200, 300];
+        // Closer to the packet before it
+        assert_eq!(snap_to_packet(140, &packets), 100);
+        // Closer to the packet after it
+        assert_eq!(snap_to_packet(160, &packets), 200);
+        // Halfway between, the earlier packet is taken
+        assert_eq!(snap_to_packet(250, &packets), 200);
+        assert_eq!(snap_to_packet(200, &packets), 200);
+        assert_eq!(snap_to_packet(400, &packets), 300);
+        // Without packets the cut stays where it is
+        assert_eq!(snap_to_packet(140, &[]), 140);
+    }
+
+    #[test]
+    fn segments_are_cut_on_packets_that_dont_line_up_with_frames() {
+        let packets = aac_packets(100);
+        let segment = Segment {
+            index: 2,
+            start: 12,
+            end: Some(24),
+            keep: true,
+        };
+        // Frame 12 is at 500.5ms, between packets 23 (490.667ms) and 24 (512ms), and
+        // frame 24 at 1001ms, between packets 46 (981.333ms) and 47 (1002.667ms)
+        assert_eq!(
+            input_span(&segment, FILM, 0, &packets),
+            (packets[23], Some(packets[47]))
+        );
+        assert_eq!(
+            input_span(&segment, FILM, 0, &[]),
+            (500_500_000, Some(1_001_000_000))
+        );
+        // The delay is taken off before snapping, so 470.5ms lands on packet 22
+        assert_eq!(input_span(&segment, FILM, 30, &packets).0, packets[22]);
+    }
+
+    #[test]
+    fn a_missing_output_directory_is_created() {
+        let dir = test_dir("output-dir");
+        let opts = Config {
+            output_aud: dir.join("some/dir/out.mka"),
+            ..test_opts(&dir)
+        };
+        prepare_output_dir(&opts).unwrap();
+        assert!(dir.join("some/dir").is_dir());
+        // Unless --no-mkdir is given
+        let opts = Config {
+            output_aud: dir.join("other/out.mka"),
+            mkdir: false,
+            ..test_opts(&dir)
+        };
+        assert!(matches!(
+            prepare_output_dir(&opts),
+            Err(SplitError::InvalidArgument(_))
+        ));
+        assert!(!dir.join("other").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_file_in_the_way_of_the_output_directory_is_named() {
+        let dir = test_dir("output-dir-file");
+        fs::write(dir.join("some"), "").unwrap();
+        for output in ["some/out.mka", "some/dir/out.mka"] {
+            let opts = Config {
+                output_aud: dir.join(output),
+                ..test_opts(&dir)
+            };
+            match prepare_output_dir(&opts) {
+                Err(SplitError::InvalidArgument(message)) => assert_eq!(
+                    message,
+                    format!(
+                        "Cannot use {} as the output directory, {} is a file",
+                        output_dir(&opts).display(),
+                        dir.join("some").display()
+                    )
+                ),
+                other => panic!("expected the file to be named, got {:?}", other),
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_bare_output_name_goes_in_the_current_directory() {
+        let opts = Config {
+            output_aud: PathBuf::from("out.mka"),
+            mkdir: false,
+            ..Config::default()
+        };
+        assert_eq!(output_dir(&opts), Path::new("."));
+        // Which always exists, even when it may not be created
+        prepare_output_dir(&opts).unwrap();
+        let opts = Config {
+            deterministic: true,
+            ..opts
+        };
+        assert_eq!(with_work_dir(&opts).work_dir, Path::new("out.split"));
+        assert_eq!(
+            output_dir(&Config {
+                output_aud: PathBuf::from("dir/out.mka"),
+                ..Config::default()
+            }),
+            Path::new("dir")
+        );
+    }
+
+    /// Whether the trims of `script`, in a clip of `total_frames`, keep the input as it is
+    fn copies_whole(opts: &Config, script: &str, total_frames: usize) -> bool {
+        let mut trims = parse_trims(script, None, false, false).unwrap();
+        for trim in &mut trims {
+            trim.resolve_length(total_frames).unwrap();
+        }
+        let ranges = trim_ranges(opts, &trims, FILM).unwrap();
+        let mut plan = plan_segments(&ranges, Some(total_frames));
+        let merge_order = plan.select_pieces(&ranges, false, None).unwrap();
+        copies_whole_input(opts, &plan, &merge_order)
+    }
+
+    #[test]
+    fn trims_covering_the_whole_clip_copy_the_input() {
+        let opts = Config::default();
+        assert!(copies_whole(&opts, "Trim(0, 0)", 500));
+        assert!(copies_whole(&opts, "Trim(0, 499)", 500));
+        // Dropping any frame needs a split
+        assert!(!copies_whole(&opts, "Trim(0, 498)", 500));
+        assert!(!copies_whole(&opts, "Trim(1, 0)", 500));
+        // As does re-encoding or writing something more than the one output
+        let transcode = Config {
+            transcode: Some(Transcode::parse("opus").unwrap()),
+            ..Config::default()
+        };
+        assert!(!copies_whole(&transcode, "Trim(0, 0)", 500));
+        let no_merge = Config {
+            no_merge: true,
+            ..Config::default()
+        };
+        assert!(!copies_whole(&no_merge, "Trim(0, 0)", 500));
+    }
+
+    fn sample_format(sample_rate: &str, channel_layout: &str, sample_fmt: &str) -> SampleFormat {
+        SampleFormat {
+            sample_rate: sample_rate.to_owned(),
+            channel_layout: channel_layout.to_owned(),
+            sample_fmt: Some(sample_fmt.to_owned()),
+        }
+    }
+
+    #[test]
+    fn matching_pieces_can_be_joined() {
+        let format = sample_format("48000", "stereo", "fltp");
+        let pieces = [
+            (1, Path::new("split-001.mka"), format.clone()),
+            (3, Path::new("split-003.mka"), format),
+        ];
+        compare_piece_formats(&pieces).unwrap();
+        compare_piece_formats(&[]).unwrap();
+    }
+
+    #[test]
+    fn pieces_in_another_format_are_caught() {
+        let first = sample_format("48000", "stereo", "fltp");
+        for (format, described) in [
+            (
+                sample_format("44100", "stereo", "fltp"),
+                "44100Hz stereo fltp",
+            ),
+            (sample_format("48000", "5.1", "fltp"), "48000Hz 5.1 fltp"),
+            (
+                sample_format("48000", "stereo", "s16"),
+                "48000Hz stereo s16",
+            ),
+        ] {
+            let pieces = [
+                (1, Path::new("split-001.mka"), first.clone()),
+                (3, Path::new("split-003.mka"), first.clone()),
+                (5, Path::new("split-005.mka"), format),
+            ];
+            match compare_piece_formats(&pieces) {
+                Err(SplitError::CheckFailed(message)) => assert_eq!(
+                    message,
+                    format!(
+                        "Piece 5 (split-005.mka) is {}, but piece 1 is 48000Hz stereo fltp, \
+                         so they can't be joined",
+                        described
+                    )
+                ),
+                other => panic!("expected {} to be caught, got {:?}", described, other),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn pieces_are_compared_as_ffprobe_reports_them() {
+        let dir = test_dir("pieces-match");
+        let opts = Config {
+            ffprobe: fake_tool(
+                &dir,
+                "ffprobe",
+                "case \"$*\" in *split-003*) echo sample_rate=44100 ;; *) echo sample_rate=48000 ;; esac\n\
+                 echo sample_fmt=fltp\necho channel_layout=stereo",
+            ),
+            ..test_opts(&dir)
+        };
+        let files: Vec<PathBuf> = [1, 3, 5]
+            .iter()
+            .map(|&index| piece_path(&opts, index))
+            .collect();
+        match check_pieces_match(&opts, &[1, 3, 5], &files) {
+            Err(SplitError::CheckFailed(message)) => {
+                assert!(message.starts_with("Piece 3 ("), "{}", message);
+                assert!(message.contains("44100Hz stereo fltp"), "{}", message);
+            }
+            other => panic!("expected piece 3 to be caught, got {:?}", other),
+        }
+        check_pieces_match(&opts, &[1, 5], &[files[0].clone(), files[2].clone()]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Writes a piece for each stretch between the cuts, labelled with the input's name
+    /// and its number, and joins pieces by writing their labels one after the other
+    #[derive(Debug)]
+    struct LabelBackend;
+
+    impl SplitBackend for LabelBackend {
+        fn split(
+            &self,
+            input: &Path,
+            cut_times: &[String],
+            _delay: isize,
+            out_dir: &Path,
+        ) -> Result<Vec<PathBuf>, SplitError> {
+            let name = input.file_stem().unwrap().to_string_lossy();
+            (1..=cut_times.len() + 1)
+                .map(|index| {
+                    let piece = out_dir.join(format!("{}-{}.bin", name, index));
+                    fs::write(&piece, format!("{}:{} ", name, index))
+                        .map_err(|e| SplitError::Io(piece.clone(), e))?;
+                    Ok(piece)
+                })
+                .collect()
+        }
+
+        fn merge(&self, pieces: &[PathBuf], output: &Path) -> Result<(), SplitError> {
+            let mut joined = String::new();
+            for piece in pieces {
+                joined +=
+                    &fs::read_to_string(piece).map_err(|e| SplitError::Io(piece.clone(), e))?;
+            }
+            fs::write(output, joined).map_err(|e| SplitError::Io(output.to_path_buf(), e))
+        }
+    }
+
+    /// A stand-in ffprobe that finds 10 seconds of 48kHz stereo AAC in every file
+    #[cfg(unix)]
+    fn aac_ffprobe(dir: &Path) -> PathBuf {
+        fake_tool(
+            dir,
+            "ffprobe",
+            "case \"$*\" in\n\
+             *codec_name*) echo aac ;;\n\
+             *sample_rate*) printf 'sample_rate=48000\\nchannel_layout=stereo\\n' ;;\n\
+             *duration*) echo 10.000000 ;;\n\
+             esac",
+        )
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compilations_join_each_pair_in_order() {
+        let dir = test_dir("compile");
+        let mut compile = Vec::new();
+        for (name, script) in [("a", "Trim(0, 99)"), ("b", "Trim(100, 199)")] {
+            let script_path = dir.join(format!("{}.avs", name));
+            let audio = dir.join(format!("{}.flac", name));
+            fs::write(&script_path, script).unwrap();
+            fs::write(&audio, "").unwrap();
+            compile.push((script_path, audio));
+        }
+        let opts = Config {
+            compile,
+            ffprobe: aac_ffprobe(&dir),
+            custom_backend: Some(Arc::new(LabelBackend)),
+            deterministic: true,
+            ..test_opts(&dir)
+        };
+        split_compilation(&opts).unwrap();
+        // The first trim keeps the first of a's pieces, the second the middle one of b's
+        assert_eq!(fs::read_to_string(&opts.output_aud).unwrap(), "a:1 b:2 ");
+        assert!(!dir.join("out.split").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compilations_of_different_formats_are_refused_up_front() {
+        let dir = test_dir("compile-formats");
+        let opts = Config {
+            compile: vec![
+                (dir.join("a.avs"), dir.join("a.flac")),
+                (dir.join("b.avs"), dir.join("b.flac")),
+            ],
+            ffprobe: fake_tool(
+                &dir,
+                "ffprobe",
+                "case \"$*\" in\n\
+                 *codec_name*) echo aac ;;\n\
+                 *sample_rate*b.flac) echo sample_rate=44100 ;;\n\
+                 *sample_rate*) echo sample_rate=48000 ;;\n\
+                 esac",
+            ),
+            custom_backend: Some(Arc::new(LabelBackend)),
+            ..test_opts(&dir)
+        };
+        match split_compilation(&opts) {
+            Err(SplitError::InvalidArgument(message)) => assert_eq!(
+                message,
+                format!(
+                    "Can't join aac 44100Hz stereo audio from {} with aac 48000Hz stereo audio from {}",
+                    dir.join("b.flac").display(),
+                    dir.join("a.flac").display()
+                )
+            ),
+            other => panic!("expected the formats to be refused, got {:?}", other),
+        }
+        // Nothing was split
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The trims of `script` that `check_trims_in_audio` keeps in a clip of `total_frames`
+    fn trims_in_audio(
+        opts: &Config,
+        script: &str,
+        total_frames: usize,
+    ) -> Result<Vec<(usize, usize)>, SplitError> {
+        let mut trims = parse_trims(script, None, false, false).unwrap();
+        check_trims_in_audio(opts, &mut trims, total_frames)?;
+        Ok(trims.iter().map(|trim| (trim.start, trim.end)).collect())
+    }
+
+    #[test]
+    fn trims_past_the_end_of_the_audio_are_kept_or_skipped() {
+        let opts = Config::default();
+        let script = "Trim(0, 99) ++ Trim(400, 599) ++ Trim(500, 549)";
+        // The trim running past the end is kept, to be cut short, and the one after it dropped
+        assert_eq!(
+            trims_in_audio(&opts, script, 500).unwrap(),
+            [(0, 100), (400, 600)]
+        );
+        assert_eq!(
+            trims_in_audio(&opts, "Trim(0, 499)", 500).unwrap(),
+            [(0, 500)]
+        );
+    }
+
+    #[test]
+    fn trims_past_the_end_of_the_audio_fail_under_strict() {
+        let strict = Config {
+            strict: true,
+            ..Config::default()
+        };
+        match trims_in_audio(&strict, "Trim(0, 99)\nTrim(400, 599)", 500) {
+            Err(SplitError::CheckFailed(message)) => assert_eq!(
+                message,
+                "Trim `Trim(400, 599)` on line 2 runs to frame 599, but the audio only has 500 frames"
+            ),
+            other => panic!("expected the trim to fail the check, got {:?}", other),
+        }
+        // --warn-as-error fails on the warning instead
+        let warn_as_error = Config {
+            warn_as_error: true,
+            ..Config::default()
+        };
+        match trims_in_audio(&warn_as_error, "Trim(500, 549)", 500) {
+            Err(SplitError::CheckFailed(message)) => assert_eq!(
+                message,
+                "Trim `Trim(500, 549)` on line 1 runs to frame 549, but the audio only has \
+                 500 frames, skipping it (failing because of --warn-as-error)"
+            ),
+            other => panic!("expected the warning to fail, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn the_default_framerate_is_used_with_a_warning() {
+        let dir = test_dir("default-framerate");
+        let mut opts = script_opts(&dir, "Trim(0, 99)");
+        resolve_framerate(&mut opts).unwrap();
+        assert_eq!(opts.framerate, Rational::NTSC);
+        assert_eq!(opts.framerate_source, FramerateSource::Default);
+        // The warning is what --warn-as-error fails on
+        let mut opts = Config {
+            warn_as_error: true,
+            ..script_opts(&dir, "Trim(0, 99)")
+        };
+        match resolve_framerate(&mut opts) {
+            Err(SplitError::CheckFailed(message)) => assert_eq!(
+                message,
+                "no framerate given, assuming 30000/1001. Pass --framerate or add AssumeFPS \
+                 to the script if that's wrong (failing because of --warn-as-error)"
+            ),
+            other => panic!("expected the fallback to fail, got {:?}", other),
+        }
+        // Unless it was kept quiet, or there are no frames to count
+        let quiet = Config {
+            quiet: true,
+            ..opts.clone()
+        };
+        default_framerate(&quiet).unwrap();
+        let seconds = Config {
+            input_avs: None,
+            cuts_unit: CutsUnit::Seconds,
+            ..opts.clone()
+        };
+        default_framerate(&seconds).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_default_framerate_can_be_refused() {
+        let refused = Config {
+            no_default_framerate: true,
+            ..Config::default()
+        };
+        assert!(matches!(
+            default_framerate(&refused),
+            Err(SplitError::InvalidArgument(_))
+        ));
+        let with_script = Config {
+            input_avs: Some(PathBuf::from("in.avs")),
+            ..refused
+        };
+        assert!(matches!(
+            default_framerate(&with_script),
+            Err(SplitError::NoFramerate)
+        ));
+    }
+
+    #[test]
+    fn delays_are_rounded_to_the_nearest_frame() {
+        // A frame at 24000/1001 is 41.708ms
+        assert_eq!(normalize_delay(50, FILM), 42);
+        assert_eq!(normalize_delay(-50, FILM), -42);
+        assert_eq!(normalize_delay(63, FILM), 83);
+        assert_eq!(normalize_delay(20, FILM), 0);
+        assert_eq!(normalize_delay(0, FILM), 0);
+        assert_eq!(normalize_delay(30, Rational { num: 25, den: 1 }), 40);
+        // A delay already on a frame is left alone
+        assert_eq!(normalize_delay(-80, Rational { num: 25, den: 1 }), -80);
+    }
+
+    /// A stand-in ffprobe that prints `output` whatever it is asked
+    #[cfg(unix)]
+    fn printing_ffprobe(dir: &Path, output: &str) -> PathBuf {
+        let printed = dir.join("ffprobe-output");
+        fs::write(&printed, output).unwrap();
+        fake_tool(dir, "ffprobe", &format!("cat '{}'", printed.display()))
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn chapters_become_trims_in_nanoseconds() {
+        let dir = test_dir("chapter-trims");
+        let opts = Config {
+            ffprobe: printing_ffprobe(
+                &dir,
+                r#"{"chapters": [
+                    {"id": 1, "start_time": "0.000000", "end_time": "90.500000", "tags": {"title": "Opening"}},
+                    {"id": 2, "start_time": "90.500000", "end_time": "1381.250000", "tags": {}}
+                ]}"#,
+            ),
+            ..test_opts(&dir)
+        };
+        let trims = chapter_trims(&opts).unwrap();
+        let ranges: Vec<(usize, usize)> = trims.iter().map(|trim| (trim.start, trim.end)).collect();
+        assert_eq!(
+            ranges,
+            [(0, 90_500_000_000), (90_500_000_000, 1_381_250_000_000)]
+        );
+        assert_eq!(trims[0].text, "chapter 1 (Opening)");
+        assert_eq!(trims[1].text, "chapter 2");
+        assert_eq!(trims[1].line, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_chapter_ending_before_it_starts_is_an_invalid_trim() {
+        let dir = test_dir("chapter-trims-reversed");
+        let opts = Config {
+            ffprobe: printing_ffprobe(
+                &dir,
+                r#"{"chapters": [{"start_time": "10.000000", "end_time": "5.000000"}]}"#,
+            ),
+            ..test_opts(&dir)
+        };
+        match chapter_trims(&opts) {
+            Err(SplitError::InvalidTrim { line, message, .. }) => {
+                assert_eq!(line, 1);
+                assert_eq!(message, "the chapter ends before it starts");
+            }
+            other => panic!("expected an invalid chapter, got {:?}", other),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Options reading cuts from the `cuts` tag of a file whose tags are `tags`
+    #[cfg(unix)]
+    fn tag_opts(dir: &Path, tags: &str) -> Config {
+        Config {
+            ffprobe: printing_ffprobe(dir, &format!(r#"{{"format": {{"tags": {}}}}}"#, tags)),
+            cuts_tag: Some("cuts".to_owned()),
+            ..test_opts(dir)
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn tags_are_read_as_ranges() {
+        let dir = test_dir("tag-trims");
+        let opts = tag_opts(&dir, r#"{"ENCODER": "x", "CUTS": "100-200, 300 - 400"}"#);
+        let trims = tag_trims(&opts, FILM).unwrap();
+        let ranges: Vec<(usize, usize)> = trims.iter().map(|trim| (trim.start, trim.end)).collect();
+        // The tag is matched without regard to case, and each range keeps its end frame
+        assert_eq!(ranges, [(100, 201), (300, 401)]);
+        assert_eq!(trims[1].text, "300 - 400");
+        let opts = Config {
+            cuts_unit: CutsUnit::Seconds,
+            ..tag_opts(&dir, r#"{"cuts": "1.5-3"}"#)
+        };
+        let trims = tag_trims(&opts, FILM).unwrap();
+        assert_eq!(
+            (trims[0].start, trims[0].end),
+            (1_500_000_000, 3_000_000_000)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_missing_or_bad_tag_is_an_error() {
+        let dir = test_dir("tag-trims-bad");
+        for tags in [r#"{"ENCODER": "x"}"#, r#"{"CUTS": " "}"#] {
+            match tag_trims(&tag_opts(&dir, tags), FILM) {
+                Err(SplitError::InvalidArgument(message)) => {
+                    assert!(
+                        message.ends_with("has no cuts tag to read cuts from"),
+                        "{}",
+                        message
+                    )
+                }
+                other => panic!("expected no tag for {}, got {:?}", tags, other),
+            }
+        }
+        match tag_trims(&tag_opts(&dir, r#"{"CUTS": "100-200,300"}"#), FILM) {
+            Err(SplitError::InvalidTrim {
+                line,
+                text,
+                message,
+            }) => {
+                assert_eq!((line, text.as_str()), (2, "300"));
+                assert_eq!(message, "expected START-END in the cuts tag");
+            }
+            other => panic!("expected a bad range, got {:?}", other),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn the_chosen_audio_track_is_probed_and_extracted() {
+        let dir = test_dir("audio-track");
+        let log = dir.join("ffprobe.log");
+        let opts = Config {
+            input_aud: dir.join("in.mkv"),
+            audio_track: Some(2),
+            backend: Backend::Ffmpeg,
+            snap_to_packet: true,
+            ffprobe: fake_tool(
+                &dir,
+                "ffprobe",
+                &format!(
+                    "echo \"$*\" >> '{}'\n\
+                     case \"$*\" in\n\
+                     *show_streams*) echo '{{\"streams\": [{{}}, {{}}, {{}}]}}' ;;\n\
+                     *duration*) echo 10.000000 ;;\n\
+                     *pts_time*) printf '0.000000\\n0.021333\\n' ;;\n\
+                     esac",
+                    log.display()
+                ),
+            ),
+            ..test_opts(&dir)
+        };
+        assert_eq!(get_total_frames(&opts, FILM).unwrap(), 240);
+        assert_eq!(packet_times(&opts).unwrap(), [0, 21_333_000]);
+        let probed = fs::read_to_string(&log).unwrap();
+        assert!(probed.contains("-select_streams a:2 -show_entries stream=duration"));
+        assert!(probed.contains("-select_streams a:2 -show_entries packet=pts_time"));
+
+        let opts = Config {
+            snap_to_packet: false,
+            ..opts
+        };
+        let ranges = [(24, 48)];
+        let mut plan = plan_segments(&ranges, Some(240));
+        let merge_order = plan.select_pieces(&ranges, false, None).unwrap();
+        let commands = planned_commands(&opts, &plan, &merge_order, &[], FILM, 0).unwrap();
+        assert!(!commands.is_empty());
+        for command in &commands {
+            assert!(command.windows(2).any(|pair| pair == ["-map", "0:a:2"]));
+        }
+        // A track the input doesn't have is caught rather than probing another
+        let missing = Config {
+            audio_track: Some(3),
+            ..opts
+        };
+        assert!(get_total_frames(&missing, FILM).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Options checking audio of `audio` seconds against a reference video of 100 seconds
+    #[cfg(unix)]
+    fn duration_opts(dir: &Path, audio: &str) -> Config {
+        Config {
+            input_aud: dir.join("in.flac"),
+            reference_video: Some(dir.join("video.mkv")),
+            ffprobe: fake_tool(
+                dir,
+                "ffprobe",
+                &format!(
+                    "case \"$*\" in\n\
+                     *video.mkv) echo 100.000000 ;;\n\
+                     *) echo {} ;;\n\
+                     esac",
+                    audio
+                ),
+            ),
+            warn_as_error: true,
+            ..test_opts(dir)
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn durations_within_the_tolerance_pass_quietly() {
+        let dir = test_dir("durations-close");
+        // --warn-as-error would fail on any warning
+        check_durations(&duration_opts(&dir, "102.500000")).unwrap();
+        check_durations(&duration_opts(&dir, "97.000000")).unwrap();
+        // Without a reference video nothing is probed
+        check_durations(&Config {
+            reference_video: None,
+            ..test_opts(&dir)
+        })
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn durations_past_the_tolerance_are_warned_about() {
+        let dir = test_dir("durations-far");
+        let message =
+            "Audio duration (103.500s) and video duration (100.000s) differ by more than 3s";
+        match check_durations(&duration_opts(&dir, "103.500000")) {
+            Err(SplitError::CheckFailed(failed)) => assert_eq!(
+                failed,
+                format!("{} (failing because of --warn-as-error)", message)
+            ),
+            other => panic!("expected the warning, got {:?}", other),
+        }
+        let warned = Config {
+            warn_as_error: false,
+            ..duration_opts(&dir, "103.500000")
+        };
+        check_durations(&warned).unwrap();
+        let strict = Config {
+            strict: true,
+            ..warned
+        };
+        match check_durations(&strict) {
+            Err(SplitError::CheckFailed(failed)) => assert_eq!(failed, message),
+            other => panic!("expected the check to fail, got {:?}", other),
+        }
+        // A looser tolerance lets it through
+        let loose = Config {
+            duration_tolerance: 5f64,
+            ..duration_opts(&dir, "103.500000")
+        };
+        check_durations(&loose).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_script_without_trims_can_be_passed_through() {
+        let dir = test_dir("passthrough");
+        let input_aud = dir.join("in.flac");
+        fs::write(&input_aud, "").unwrap();
+        let log = dir.join("ffmpeg.log");
+        let opts = Config {
+            input_aud,
+            backend: Backend::Ffmpeg,
+            ffmpeg: fake_tool(
+                &dir,
+                "ffmpeg",
+                &format!(
+                    "echo \"$*\" > '{}'\nfor output; do :; done\necho copied > \"$output\"",
+                    log.display()
+                ),
+            ),
+            ..script_opts(&dir, "FFmpegSource2(\"in.mkv\")\n# Trim(0, 99)\n")
+        };
+        assert!(matches!(split_audio(&opts), Err(SplitError::NoTrims(_))));
+        assert!(!opts.output_aud.exists());
+        let passthrough = Config {
+            passthrough_on_no_trims: true,
+            delay: Some(-50),
+            ..opts
+        };
+        split_audio(&passthrough).unwrap();
+        assert_eq!(
+            fs::read_to_string(&passthrough.output_aud).unwrap(),
+            "copied\n"
+        );
+        // The whole input is remuxed with the delay, rather than cut
+        let args = fs::read_to_string(&log).unwrap();
+        assert!(args.contains("-itsoffset -0.050 -i "), "{}", args);
+        assert!(!args.contains("-ss"), "{}", args);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fields_are_cut_at_twice_the_frame_rate() {
+        let opts = Config {
+            backend: Backend::Ffmpeg,
+            fields: true,
+            ..Config::default()
+        };
+        let field_rate = timing_framerate(&opts);
+        assert_eq!(field_rate, Rational::NTSC.doubled());
+        assert_eq!(timing_framerate(&Config::default()), Rational::NTSC);
+        // Field 60 is where frame 30 starts
+        assert_eq!(
+            frame_to_timecode(60, field_rate),
+            frame_to_timecode(30, Rational::NTSC)
+        );
+        let ranges = [(60, 120)];
+        let mut plan = plan_segments(&ranges, Some(600));
+        let merge_order = plan.select_pieces(&ranges, false, None).unwrap();
+        let commands = planned_commands(&opts, &plan, &merge_order, &[], field_rate, 0).unwrap();
+        let extract = &commands[0];
+        assert!(extract
+            .windows(2)
+            .any(|pair| pair == ["-ss", "00:00:01.001000000"]));
+        assert!(extract
+            .windows(2)
+            .any(|pair| pair == ["-to", "00:00:02.002000000"]));
+    }
+
+    #[test]
+    fn unusable_scripts_fail_with_their_own_errors() {
+        let dir = test_dir("script-errors");
+        // A script that isn't there
+        let missing = Config {
+            input_avs: Some(dir.join("missing.avs")),
+            ..test_opts(&dir)
+        };
+        let error = script_trims(&missing, FILM).unwrap_err();
+        assert!(matches!(error, SplitError::Script(..)));
+        assert_eq!(error.exit_code(), 1);
+        // One holding nothing but whitespace
+        let error = script_trims(&script_opts(&dir, " \n\t\n"), FILM).unwrap_err();
+        assert!(matches!(error, SplitError::EmptyScript));
+        assert_eq!(error.exit_code(), 2);
+        // One holding only comments, which is probably the wrong file
+        let error = script_trims(&script_opts(&dir, "# Trim(0, 99)\n"), FILM).unwrap_err();
+        assert!(matches!(error, SplitError::NoStatements));
+        assert_eq!(error.exit_code(), 2);
+        // One with statements but no trims is left to the caller, which may pass it through
+        let (trims, _) =
+            script_trims(&script_opts(&dir, "FFmpegSource2(\"in.mkv\")\n"), FILM).unwrap();
+        assert!(trims.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn trims_are_printed_without_the_audio() {
+        let dir = test_dir("print-trims");
+        // test_opts points ffprobe somewhere that doesn't exist, so nothing can be probed
+        let mut opts = Config {
+            input_aud: PathBuf::new(),
+            print_trims: true,
+            ..script_opts(
+                &dir,
+                "AssumeFPS(24000, 1001)\nTrim(0, 99) ++ Trim(200, 299)\n",
+            )
+        };
+        run(&mut opts).unwrap();
+        // A trim counting from the end of the clip needs the audio to place it
+        let mut opts = Config {
+            input_aud: PathBuf::new(),
+            print_trims: true,
+            ..script_opts(&dir, "AssumeFPS(24000, 1001)\nTrim(100, 0)\n")
+        };
+        match run(&mut opts) {
+            Err(SplitError::InvalidTrim { line, message, .. }) => {
+                assert_eq!(line, 2);
+                assert_eq!(
+                    message,
+                    "counts from the end of the clip, so -i is needed to place it"
+                );
+            }
+            other => panic!("expected the trim to need -i, got {:?}", other),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_dry_run_writes_nothing_but_still_probes_the_length() {
+        let dir = test_dir("dry-run");
+        let input_aud = dir.join("in.flac");
+        fs::write(&input_aud, "").unwrap();
+        let log = dir.join("ffprobe.log");
+        let opts = Config {
+            input_aud,
+            framerate: FILM,
+            framerate_source: FramerateSource::Argument,
+            backend: Backend::Ffmpeg,
+            dry_run: true,
+            ffprobe: fake_tool(
+                &dir,
+                "ffprobe",
+                &format!(
+                    "echo \"$*\" >> '{}'\ncase \"$*\" in *format=duration*) echo 10.000000 ;; esac",
+                    log.display()
+                ),
+            ),
+            ffmpeg: fake_tool(&dir, "ffmpeg", "exit 1"),
+            ..script_opts(&dir, "Trim(100, 0)")
+        };
+        let files = || {
+            let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| *path != log)
+                .collect();
+            files.sort();
+            files
+        };
+        let before = files();
+        let mut report = Report::default();
+        run_split(&opts, &mut report).unwrap();
+        // 10 seconds at 24000/1001 is 240 frames, which the trim runs to
+        assert_eq!((report.trims[0].start, report.trims[0].end), (100, 240));
+        assert!(fs::read_to_string(&log)
+            .unwrap()
+            .contains("format=duration"));
+        // Nothing else ran, and nothing was written but the probe's own log
+        assert!(report.commands.is_empty());
+        assert_eq!(files(), before);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,217 +1,1687 @@
 #![warn(clippy::all)]
 
-use chrono::NaiveTime;
-use clap::{App, Arg};
-use regex::Regex;
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
-use std::process::Command;
-
-#[derive(Debug, Clone)]
-struct Config {
-    framerate: f32,
-    input_aud: PathBuf,
-    output_aud: PathBuf,
-    input_avs: PathBuf,
-    verbose: bool,
-}
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind, SubCommand};
+use split_aud::backend::{Backend, DelayMode};
+use split_aud::captions::CaptionFormat;
+use split_aud::color::ColorMode;
+use split_aud::config_file::{config_file_path, load_config_file};
+use split_aud::cuts_file::{CutsFileMode, CutsUnit};
+use split_aud::error::SplitError;
+use split_aud::expand::expand_path;
+use split_aud::ffmpeg::Transcode;
+use split_aud::ffprobe::DurationSource;
+use split_aud::framerate::{FramerateSource, Rational, DEFAULT_FRAMERATE_TOLERANCE};
+use split_aud::job::{load_job, JobCut};
+use split_aud::mediainfo::parse_mediainfo_framerate;
+use split_aud::output_name::{compile_strip_suffix, default_strip_suffixes, derive_output_path};
+use split_aud::segments::FirstSegment;
+use split_aud::stage::{Cleanup, Stage};
+use split_aud::trims::compile_trim_regex;
+use split_aud::{error_label, run, Config};
+use std::fs::{self, File};
+use std::io::{self, IsTerminal};
+use std::process;
 
-fn split_audio(opts: &Config) {
-    // Determine if we should apply a delay to the audio
-    let delay_regex = Regex::new(r"DELAY (-?\d+)ms").unwrap();
-    let delay = if let Some(delay_captures) = delay_regex.captures(opts.input_aud.to_str().unwrap())
-    {
-        delay_captures[1].parse::<isize>().unwrap()
-    } else {
-        0isize
-    };
+/// What running with no arguments at all prints, in place of clap's usage error
+const GETTING_STARTED: &str = "\
+split_aud keeps the parts of an audio file that an AviSynth or VapourSynth script
+trims to, without re-encoding it.
 
-    // Read in the contents of the avisynth script
-    let mut avs_file = File::open(&opts.input_avs).unwrap();
-    let mut avs_contents = String::new();
-    avs_file.read_to_string(&mut avs_contents).ok();
-
-    // Determine where to trim
-    // A vector of timestamps for trimming
-    let mut cut_times: Vec<String> = Vec::new();
-    // This is not the best regex--it takes ALL TRIMS and includes them
-    let trim_regex = Regex::new(r"[tT]rim\((?:\w+, ?)?(\d+), ?(\d+)\)").unwrap();
-    for capture_group in trim_regex.captures_iter(&avs_contents) {
-        for (_, capture) in capture_group
-            .iter()
-            .enumerate()
-            .filter(|&(i, _)| i % 3 != 0)
-        {
-            let frame: usize = capture.unwrap().as_str().parse().unwrap();
-            let seconds: f32 = frame as f32 / opts.framerate;
-            let nano: f32 = seconds.fract() * 1_000_000_000f32;
-            let timestamp =
-                NaiveTime::from_num_seconds_from_midnight(seconds.trunc() as u32, nano as u32);
-            cut_times.push(timestamp.format("%H:%M:%S%.3f").to_string());
-        }
-    }
-
-    if cut_times.is_empty() {
-        // And for supporting python slice syntax
-        let trim_regex = Regex::new(r"clip\[(\d+): ?(\d+)\]").unwrap();
-        for capture_group in trim_regex.captures_iter(&avs_contents) {
-            for (i, capture) in capture_group
-                .iter()
-                .enumerate()
-                .filter(|&(i, _)| i % 3 != 0)
-            {
-                let frame: usize = capture.unwrap().as_str().parse::<usize>().unwrap()
-                    - if i == 2 { 1 } else { 0 };
-                let seconds: f32 = frame as f32 / opts.framerate;
-                let nano: f32 = seconds.fract() * 1_000_000_000f32;
-                let timestamp =
-                    NaiveTime::from_num_seconds_from_midnight(seconds.trunc() as u32, nano as u32);
-                cut_times.push(timestamp.format("%H:%M:%S%.3f").to_string());
-            }
-        }
-    }
+Getting started:
+    split_aud -i Ep01.flac Ep01.avs    Keep the script's trims of Ep01.flac, in Ep01.mka
+    split_aud doctor                   Check that mkvmerge, ffprobe and ffmpeg can be run
 
-    if cut_times.is_empty() {
-        panic!("No trims found in avs file");
-    }
-
-    // Split the audio file apart
-    eprintln!("Splitting audio file with {} delay", delay);
-    let output = Command::new("mkvmerge")
-        .arg("-o")
-        .arg(
-            opts.output_aud
-                .with_extension("split.mka")
-                .to_str()
-                .unwrap(),
-        )
-        .arg("--sync")
-        .arg(format!("0:{}", delay))
-        .arg(opts.input_aud.to_str().unwrap())
-        .arg("--split")
-        .arg(format!("timecodes:{}", cut_times.join(",")))
-        .output()
-        .unwrap_or_else(|e| panic!("failed to execute process: {}", e));
-    println!("{}", String::from_utf8(output.stdout).unwrap());
-
-    // Put it back together
-    let mut merge_files: Vec<PathBuf> = Vec::new();
-    let mut use_first = false;
-    for (i, timestamp) in cut_times.iter().enumerate() {
-        if i == cut_times.len() && use_first {
-            break;
-        }
-        if i == 0 && timestamp == "00:00:00.000" {
-            use_first = true;
-        }
-        if (use_first && i % 2 == 0) || (!use_first && i % 2 == 1) {
-            merge_files.push(
-                opts.output_aud
-                    .with_extension(format!("split-{:03}.mka", i + 1)),
-            );
-        }
-    }
-
-    let output = Command::new("mkvmerge")
-        .arg("-o")
-        .arg(opts.output_aud.to_str().unwrap())
-        .args(
-            &merge_files
-                .iter()
-                .enumerate()
-                .map(|(i, x)| {
-                    if i == 0 {
-                        x.to_str().unwrap().to_owned()
-                    } else {
-                        format!("+{}", x.to_str().unwrap())
-                    }
-                })
-                .collect::<Vec<String>>(),
-        )
-        .output()
-        .unwrap_or_else(|e| panic!("failed to execute process: {}", e));
-    println!("{}", String::from_utf8(output.stdout).unwrap());
-
-    println!("Cleaning temporary files...");
-    let split_regex = Regex::new(r"split-(?:\d{3})\.mka$").unwrap();
-    let dir = opts.output_aud.parent().unwrap();
-    for file in dir
-        .read_dir()
-        .unwrap()
-        .filter(Result::is_ok)
-        .map(|file| file.unwrap())
-        .filter(|file| split_regex.is_match(&file.file_name().to_string_lossy()))
-    {
-        let _ = std::fs::remove_file(file.path());
-    }
-}
+Run split_aud --help to see every option.
+";
 
-fn main() {
-    let matches = App::new("split_aud")
+/// Every flag and subcommand split_aud takes
+fn app() -> App<'static, 'static> {
+    App::new("split_aud")
         .version("0.1")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(SubCommand::with_name("doctor").about(
+            "Check that mkvmerge, ffprobe and ffmpeg can be run, and that the working \
+             directory can be written to. Honours --mkvmerge, --ffprobe, --ffmpeg, \
+             --backend and the config file.",
+        ))
         .arg(
             Arg::with_name("framerate")
                 .short("f")
                 .long("framerate")
-                .value_name("FRACTION")
-                .help("Set a custom framerate (default 30000/1001)")
+                .visible_alias("video-framerate")
+                .value_name("RATE")
+                .help(
+                    "Set a custom framerate as a fraction or a decimal, which trim frame \
+                     numbers count in (default: AssumeFPS from the script, or 30000/1001)",
+                )
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("fps-num")
+                .long("fps-num")
+                .value_name("NUM")
+                .help("Set the framerate's numerator, with --fps-den, instead of --framerate")
+                .takes_value(true)
+                .requires("fps-den")
+                .conflicts_with("framerate"),
+        )
+        .arg(
+            Arg::with_name("fps-den")
+                .long("fps-den")
+                .value_name("DEN")
+                .help("Set the framerate's denominator, with --fps-num, instead of --framerate")
+                .takes_value(true)
+                .requires("fps-num")
+                .conflicts_with("framerate"),
+        )
+        .arg(
+            Arg::with_name("mediainfo")
+                .long("mediainfo")
+                .value_name("PATH")
+                .help(
+                    "Use the video frame rate from a MediaInfo XML or JSON export when \
+                     --framerate isn't given, in place of AssumeFPS from the script",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fps-from")
+                .long("fps-from")
+                .value_name("VIDEO")
+                .help(
+                    "Use the frame rate ffprobe finds for the first video stream of VIDEO \
+                     when --framerate isn't given, in place of AssumeFPS from the script",
+                )
+                .conflicts_with("mediainfo")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("audio-framerate")
+                .long("audio-framerate")
+                .value_name("RATE")
+                .help(
+                    "The framerate the audio's own timeline is authored at, when it differs \
+                     from the video's. Only --normalize-delay uses it; trims are always cut \
+                     at the video framerate (default: the same as --framerate)",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("framerate-tolerance")
+                .long("framerate-tolerance")
+                .value_name("FPS")
+                .help(
+                    "How close a decimal --framerate must be to an NTSC rate like \
+                     24000/1001 to be snapped to it; others are used exactly (default 0.001)",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("framerate-from-script")
+                .long("framerate-from-script")
+                .help("Require the framerate to be declared with AssumeFPS in the script")
+                .conflicts_with_all(&["framerate", "fps-from"]),
+        )
+        .arg(
+            Arg::with_name("no-default-framerate")
+                .long("no-default-framerate")
+                .help(
+                    "Fail instead of assuming 30000/1001 when neither --framerate nor the \
+                     script gives a framerate",
+                ),
+        )
         .arg(
             Arg::with_name("input")
                 .short("i")
                 .long("input")
                 .help("Sets the input audio file to use")
                 .takes_value(true)
-                .required(true),
+                .required_unless_one(&["pair-dir", "compile", "job", "dump-script", "print-trims"]),
         )
         .arg(
             Arg::with_name("output")
                 .short("o")
                 .long("output")
-                .help("Sets the output mka file to write to (default: avs path plus .mka)")
+                .help(
+                    "Sets the output mka file to write to (default: avs path plus .mka, \
+                     without suffixes like ` DELAY -50ms`, ` track1` or `.jpn`). A directory \
+                     or a path ending in / gets a file named that way inside it",
+                )
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("strip-suffix")
+                .long("strip-suffix")
+                .value_name("PATTERN")
+                .help(
+                    "Strip suffixes matching this regex from the end of the script's name when \
+                     the output is named after it, in place of the built-in ones. \
+                     May be given more than once",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("keep-suffixes")
+                .long("keep-suffixes")
+                .help("Name the output after the whole script name, stripping no suffixes")
+                .conflicts_with("strip-suffix"),
+        )
         .arg(
             Arg::with_name("avs")
                 .help("Sets the input avs or vpy file to use")
-                .required(true)
+                .required_unless_one(&[
+                    "cuts-file",
+                    "pair-dir",
+                    "compile",
+                    "job",
+                    "audio-info",
+                    "identify",
+                    "trim",
+                    "split-at-chapters",
+                    "cuts-from-tags",
+                    "keyframes-file",
+                ])
                 .takes_value(true)
                 .index(1),
         )
+        .arg(
+            Arg::with_name("cuts-file")
+                .long("cuts-file")
+                .value_name("PATH")
+                .help(
+                    "Read the ranges to keep from this file instead of a script, \
+                     one `start end` pair per line",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cuts-unit")
+                .long("cuts-unit")
+                .value_name("UNIT")
+                .help(
+                    "What the numbers in --cuts-file count: frames at the --framerate, \
+                     or seconds and timecodes used as given. Timecodes are HH:MM:SS.fff, \
+                     or SMPTE HH:MM:SS:FF with a frame field (default frames)",
+                )
+                .possible_values(&["frames", "seconds", "timecode"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cuts-file-mode")
+                .long("cuts-file-mode")
+                .value_name("MODE")
+                .help(
+                    "Whether each line of --cuts-file is a start and an end, or a start \
+                     and a length in the same unit, so `100 50` keeps frames 100 to 149 \
+                     like `100 149` does (default range)",
+                )
+                .possible_values(&["range", "start-length"])
+                .takes_value(true)
+                .requires("cuts-file"),
+        )
+        .arg(
+            Arg::with_name("trim")
+                .long("trim")
+                .value_name("START-END")
+                .help(
+                    "Keep the frames from START to END, like Trim(START, END), instead of \
+                     reading trims from a script. May be given more than once; the ranges \
+                     are joined in the order given",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["cuts-file", "cuts-unit", "pair-dir", "job", "edit"]),
+        )
+        .arg(
+            Arg::with_name("keyframes-file")
+                .long("keyframes-file")
+                .value_name("PATH")
+                .help(
+                    "Cut at the keyframes in an Aegisub keyframes file instead of reading \
+                     trims from a script, keeping every piece. With --keep-indices, piece \
+                     1 is the audio before the first keyframe, if it isn't frame 0",
+                )
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "avs",
+                    "cuts-file",
+                    "cuts-unit",
+                    "trim",
+                    "pair-dir",
+                    "job",
+                    "edit",
+                    "split-at-chapters",
+                ]),
+        )
+        .arg(
+            Arg::with_name("split-at-chapters")
+                .long("split-at-chapters")
+                .help(
+                    "Cut at the input's chapters instead of reading trims from a script, \
+                     keeping every chapter. With --keep-indices, pieces are numbered like \
+                     the chapters, plus one for any audio before the first chapter",
+                )
+                .conflicts_with_all(&[
+                    "avs",
+                    "cuts-file",
+                    "cuts-unit",
+                    "trim",
+                    "pair-dir",
+                    "job",
+                    "edit",
+                ]),
+        )
+        .arg(
+            Arg::with_name("cuts-from-tags")
+                .long("cuts-from-tags")
+                .value_name("TAGNAME")
+                .help(
+                    "Read the ranges to keep from the input's TAGNAME tag instead of a \
+                     script, as START-END ranges separated by commas and counted in the \
+                     --cuts-unit, like 100-200,300-400. Only tags of the whole file count, \
+                     not those of a track or chapter. Needs ffprobe",
+                )
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "avs",
+                    "cuts-file",
+                    "trim",
+                    "keyframes-file",
+                    "split-at-chapters",
+                    "pair-dir",
+                    "job",
+                    "edit",
+                ]),
+        )
+        .arg(
+            Arg::with_name("pair-dir")
+                .long("pair-dir")
+                .value_name("DIR")
+                .help(
+                    "Split every audio file in DIR that has a script with the same basename \
+                     (Ep01.mka and Ep01.vpy), writing to DIR/trimmed or the -o directory. \
+                     Basenames are case-sensitive, extensions are not",
+                )
+                .takes_value(true)
+                .conflicts_with_all(&["input", "avs", "cuts-file", "report", "print-framerate"]),
+        )
+        .arg(
+            Arg::with_name("summary-json")
+                .long("summary-json")
+                .help(
+                    "Print one line of JSON on stdout at the end of the run, with whether it \
+                     succeeded, the output, how many segments were kept and how long the \
+                     output is, the framerate, the delay and where they came from, the \
+                     backend and any error",
+                ),
+        )
+        .arg(
+            Arg::with_name("post-hook")
+                .long("post-hook")
+                .value_name("COMMAND")
+                .help(
+                    "Run COMMAND through the shell once the output is written, replacing \
+                     {output}, {input} and {script} with those paths, quoted. A hook that \
+                     fails fails the run",
+                )
+                .takes_value(true)
+                .conflicts_with("no-merge"),
+        )
+        .arg(
+            Arg::with_name("compile")
+                .long("compile")
+                .value_names(&["SCRIPT", "AUDIO"])
+                .help(
+                    "Split AUDIO by SCRIPT and join what it keeps onto the -o output. \
+                     Give it once for each script, and the kept audio of each is joined \
+                     in the order given. Every audio file must have the same codec, \
+                     sample rate and channel layout",
+                )
+                .multiple(true)
+                .number_of_values(2)
+                .requires("output")
+                .conflicts_with_all(&[
+                    "input",
+                    "avs",
+                    "pair-dir",
+                    "job",
+                    "no-merge",
+                    "report",
+                    "command-log",
+                    "summary-json",
+                ]),
+        )
+        .arg(
+            Arg::with_name("incremental")
+                .long("incremental")
+                .help(
+                    "With --pair-dir, skip pairs whose output is already at least as new \
+                     as both the audio and the script",
+                )
+                .requires("pair-dir"),
+        )
+        .arg(
+            Arg::with_name("job")
+                .long("job")
+                .value_name("PATH")
+                .help(
+                    "Read the input, output, script or cuts, framerate, delay and backend \
+                     from a JSON job file instead of flags",
+                )
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "input",
+                    "output",
+                    "avs",
+                    "cuts-file",
+                    "cuts-unit",
+                    "pair-dir",
+                    "framerate",
+                    "delay",
+                    "backend",
+                ]),
+        )
+        .arg(
+            Arg::with_name("also-split")
+                .long("also-split")
+                .value_name("PATH")
+                .help(
+                    "Also cut another file, like the video, at the same points without the \
+                     audio delay, writing it next to that file as NAME.trimmed.mkv",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("delay")
+                .short("d")
+                .long("delay")
+                .value_name("MS")
+                .help("Override the audio delay in milliseconds (default: detected from filename)")
+                .takes_value(true)
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::with_name("delay-log")
+                .long("delay-log")
+                .value_name("PATH")
+                .help(
+                    "Read the delay from a log with a line like `Delay: -50ms` (default: \
+                     a filename DELAY marker, then NAME.log, NAME.txt or eac3to's \
+                     NAME - Log.txt next to the audio)",
+                )
+                .takes_value(true)
+                .conflicts_with("delay"),
+        )
+        .arg(
+            Arg::with_name("delay-mode")
+                .long("delay-mode")
+                .value_name("MODE")
+                .help(
+                    "How to apply a positive delay: sync shifts the timestamps, silence \
+                     uses ffmpeg to put real silence in front of the audio (default sync)",
+                )
+                .possible_values(&["sync", "silence"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-edit-compensation")
+                .long("no-edit-compensation")
+                .help(
+                    "Don't use the audio stream's start time (encoder delay or edit list) \
+                     as reported by ffprobe for the delay. It is only used when no other \
+                     delay was given or detected",
+                ),
+        )
+        .arg(
+            Arg::with_name("normalize-delay")
+                .long("normalize-delay")
+                .help(
+                    "Round the delay to the nearest whole number of frames at \
+                     --audio-framerate, or the framerate",
+                ),
+        )
+        .arg(
+            Arg::with_name("fields")
+                .long("fields")
+                .help("Treat trim numbers as field indices; timestamps use twice the --framerate"),
+        )
+        .arg(
+            Arg::with_name("invert")
+                .long("invert")
+                .help("Keep the parts of the audio the trims discard, and discard the trims"),
+        )
+        .arg(
+            Arg::with_name("no-merge")
+                .long("no-merge")
+                .help(
+                    "Write each kept piece to its own file instead of joining them, \
+                     named OUTPUT.1.mka, OUTPUT.2.mka and so on in join order",
+                )
+                .conflicts_with("verify"),
+        )
+        .arg(
+            Arg::with_name("stream")
+                .long("stream")
+                .help(
+                    "With the ffmpeg backend, read the kept parts straight from the input \
+                     into the output instead of writing each one out first, so the only \
+                     temporary file is a short list of the parts. A normal run needs about \
+                     as much free space again as the output for the pieces",
+                )
+                .conflicts_with("no-merge"),
+        )
+        .arg(
+            Arg::with_name("snap-to-packet")
+                .long("snap-to-packet")
+                .help(
+                    "With the ffmpeg backend, move each cut to the nearest start of an audio \
+                     packet, as ffprobe lists them, so no packet is split. A cut moves by up \
+                     to half a packet: about 11ms for 48kHz AAC, 16ms for AC-3 and \
+                     0.4ms for TrueHD",
+                ),
+        )
+        .arg(
+            Arg::with_name("deterministic")
+                .long("deterministic")
+                .help(
+                    "Write byte-identical files for identical inputs, by seeding mkvmerge \
+                     from a hash of the script and the input and having ffmpeg leave out \
+                     its random segment UID and muxing date. The work directory is named \
+                     after the output alone, without the process ID, so two runs writing \
+                     the same output in one directory at once clash",
+                ),
+        )
+        .arg(
+            Arg::with_name("timestamp-scale")
+                .long("timestamp-scale")
+                .value_name("NS")
+                .help(
+                    "Have mkvmerge write the output with this timestamp scale, so every \
+                     timestamp is a multiple of NS nanoseconds. Smaller is more precise, \
+                     but later timestamps can count less far (default: mkvmerge's \
+                     1000000, which is 1ms)",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("name-by-range")
+                .long("name-by-range")
+                .help("With --no-merge, name pieces after their frames, like OUTPUT.f500-1000.mka")
+                .requires("no-merge")
+                .conflicts_with("name-template"),
+        )
+        .arg(
+            Arg::with_name("name-template")
+                .long("name-template")
+                .value_name("TEMPLATE")
+                .help(
+                    "With --no-merge, what goes between the output name and its extension. \
+                     {index} is the position in join order, and {start} and {end} are the \
+                     piece's start and end frames, with {end} as \"end\" for a piece that \
+                     runs to the end of the audio (default {index})",
+                )
+                .takes_value(true)
+                .requires("no-merge"),
+        )
+        .arg(
+            Arg::with_name("keep-indices")
+                .long("keep-indices")
+                .value_name("LIST")
+                .help(
+                    "Join exactly these split pieces, numbered from 1 and in this order, \
+                     instead of the ones the trims pick (e.g. 1,3,5)",
+                )
+                .takes_value(true)
+                .conflicts_with("invert"),
+        )
+        .arg(
+            Arg::with_name("widen-short-trims")
+                .long("widen-short-trims")
+                .help("Widen zero-length trims like Trim(100, 100) to one frame instead of skipping them"),
+        )
+        .arg(
+            Arg::with_name("max-gap-merge")
+                .long("max-gap-merge")
+                .value_name("FRAMES")
+                .help(
+                    "Keep the audio between two trims when fewer than FRAMES frames would be \
+                     dropped, joining them into one piece",
+                )
+                .takes_value(true)
+                .conflicts_with_all(&["invert", "keep-indices"]),
+        )
+        .arg(
+            Arg::with_name("first-segment")
+                .long("first-segment")
+                .value_name("MODE")
+                .possible_values(&["auto", "keep", "drop"])
+                .help(
+                    "Whether the first piece of the split, from the start of the audio to \
+                     the first cut, is joined: auto keeps it when a trim covers it, keep \
+                     and drop force it. Use this when a script's first trim isn't read the \
+                     way it is meant, like a computed start frame",
+                )
+                .takes_value(true)
+                .conflicts_with("keep-indices"),
+        )
+        .arg(
+            Arg::with_name("end-tolerance-frames")
+                .long("end-tolerance-frames")
+                .value_name("FRAMES")
+                .help(
+                    "Move the end of the last trim to the end of the audio when it is within \
+                     FRAMES frames of it, so no sliver of audio is left after it",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("start-frame")
+                .long("start-frame")
+                .value_name("FRAME")
+                .help(
+                    "The frame of the script that the audio starts at, when the audio is \
+                     already a part of a longer clip. Trims are moved this many frames \
+                     earlier, and any part of them before the audio is dropped",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trim-regex")
+                .long("trim-regex")
+                .value_name("PATTERN")
+                .help(
+                    "Use a custom regex to find trims instead of the built-in ones. It must have \
+                     exactly two capture groups matching the start and end frame numbers",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trim-end-is-count")
+                .long("trim-end-is-count")
+                .help(
+                    "Read the second value of every Trim call in the script (or --trim-regex \
+                     match) as a number of frames instead of the last frame, so Trim(100, 50) \
+                     keeps frames 100 to 149. This changes the meaning of all of the script's \
+                     trims. A count of 0 still runs to the end, and Python slices are \
+                     unaffected",
+                ),
+        )
+        .arg(
+            Arg::with_name("trims-keyword")
+                .long("trims-keyword")
+                .value_name("KEYWORD")
+                .help(
+                    "The keyword of a `# KEYWORD: 0-100, 200-300` comment listing the ranges \
+                     to keep, which is used instead of the trims in the script when present \
+                     (default TRIMS)",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("edit")
+                .long("edit")
+                .value_name("NAME")
+                .help(
+                    "Only use the trims in one edit of the script, from a `# EDIT: NAME` \
+                     comment up to the next `# EDIT:` comment (default: the whole script)",
+                )
+                .takes_value(true)
+                .conflicts_with_all(&["cuts-file", "pair-dir"]),
+        )
+        .arg(
+            Arg::with_name("passthrough-on-no-trims")
+                .long("passthrough-on-no-trims")
+                .help("Copy the input audio unchanged to the output if no trims are found"),
+        )
+        .arg(
+            Arg::with_name("reference-video")
+                .long("reference-video")
+                .value_name("VIDEO")
+                .help("Check that the input audio is about as long as this video")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("duration-tolerance")
+                .long("duration-tolerance")
+                .value_name("SECONDS")
+                .help("How far the audio and reference video durations may differ (default 3)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("duration-source")
+                .long("duration-source")
+                .value_name("SOURCE")
+                .help(
+                    "Where ffprobe reads the audio's duration from: the container's `format` \
+                     duration, the audio `stream`'s, or `auto` to use the stream's when the \
+                     container has none, or first when --audio-track is given (default auto)",
+                )
+                .possible_values(&["format", "stream", "auto"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Treat failed sanity checks as errors instead of warnings"),
+        )
+        .arg(
+            Arg::with_name("warn-as-error")
+                .long("warn-as-error")
+                .help(
+                    "Fail with exit code 7 at the first warning instead of carrying on. \
+                     Every message that would start with Warning: counts, such as the \
+                     default framerate being assumed, several DELAY markers in the file \
+                     name, AudioDub or a frame rate change in the script, trims that were \
+                     skipped, widened or run past the audio, dropped duplicate cuts, and a \
+                     missing ffprobe or ffmpeg skipping a check",
+                ),
+        )
+        .arg(
+            Arg::with_name("mark-output")
+                .long("mark-output")
+                .help(
+                    "Write OUTPUT.split_aud next to the output, recording that it was \
+                     split and with which trims, so that splitting it again is caught",
+                ),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help(
+                    "Split the input even if its marker says it was already split, \
+                     or with --incremental even if its output is up to date",
+                ),
+        )
+        .arg(
+            Arg::with_name("audio-track")
+                .short("a")
+                .long("audio-track")
+                .value_name("N")
+                .help(
+                    "Split and probe this audio stream of the input, counting from 0 among \
+                     its audio streams only, so a movie's video and subtitles are skipped. \
+                     Only that stream is kept (default: the first audio stream, keeping \
+                     every track with mkvmerge when the first track is audio)",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("verify")
+                .long("verify")
+                .help(
+                    "Check that the output is as long as the kept trims, \
+                     within a frame per segment",
+                ),
+        )
+        .arg(
+            Arg::with_name("preview")
+                .long("preview")
+                .help(
+                    "Print where each piece lands in the output, and cut two second clips \
+                     of its start, each join and its end with ffmpeg, named \
+                     OUTPUT.preview-start.mka, OUTPUT.preview-join-01.mka and OUTPUT.preview-end.mka",
+                )
+                .conflicts_with("no-merge"),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .value_name("PATH")
+                .help("Write a JSON summary of the run to this file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("command-log")
+                .long("command-log")
+                .value_name("PATH")
+                .help(
+                    "Write every mkvmerge and ffmpeg command that was run to this file, \
+                     one per line and quoted so they can be pasted into a shell",
+                )
+                .takes_value(true)
+                .conflicts_with("pair-dir"),
+        )
+        .arg(
+            Arg::with_name("output-edl")
+                .long("output-edl")
+                .value_name("PATH")
+                .help(
+                    "Also write the kept ranges as a CMX3600 EDL with non-drop-frame \
+                     timecodes, one audio cut per range in join order",
+                )
+                .takes_value(true)
+                .conflicts_with("pair-dir"),
+        )
+        .arg(
+            Arg::with_name("output-avs-trims")
+                .long("output-avs-trims")
+                .value_name("PATH")
+                .help(
+                    "Also write the kept ranges as AviSynth Trim calls joined with ++, \
+                     in join order, after gaps are merged and trims widened or shifted",
+                )
+                .takes_value(true)
+                .conflicts_with("pair-dir"),
+        )
+        .arg(
+            Arg::with_name("output-markers")
+                .long("output-markers")
+                .value_name("PATH")
+                .help(
+                    "Also write a .srt or .vtt subtitle file with a marker at the start of \
+                     each kept segment, reading `Segment N: START - END` in join order, \
+                     to check the cuts against the source video",
+                )
+                .takes_value(true)
+                .conflicts_with("pair-dir"),
+        )
+        .arg(
+            Arg::with_name("timings")
+                .long("timings")
+                .help("Print how long ffprobe, the split, the merge and cleanup took"),
+        )
+        .arg(
+            Arg::with_name("list-segments")
+                .long("list-segments")
+                .help(
+                    "Print every piece the split will produce, whether it is kept, \
+                     and its frames and timecodes, then exit",
+                ),
+        )
+        .arg(
+            Arg::with_name("run")
+                .long("run")
+                .help("Carry on and split after --list-segments")
+                .requires("list-segments"),
+        )
+        .arg(
+            Arg::with_name("dump-plan")
+                .long("dump-plan")
+                .help(
+                    "Print everything the run resolved as JSON, from the framerate and \
+                     delay and where they came from to the trims, the segments kept and \
+                     the exact commands it would run, then exit. Only the probes are run, \
+                     and nothing is written",
+                )
+                .conflicts_with_all(&["list-segments", "interactive"]),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .short("n")
+                .long("dry-run")
+                .help(
+                    "Print the delay, the cut times and the commands the split and join \
+                     would run, ready to paste into a shell, then exit. The audio is still \
+                     probed, so cuts that need its length are where a real run puts them",
+                )
+                .conflicts_with_all(&["dump-plan", "list-segments", "interactive"]),
+        )
+        .arg(
+            Arg::with_name("stop-after")
+                .long("stop-after")
+                .value_name("STAGE")
+                .help(
+                    "Stop partway through a run: `probe` prints the cut times without \
+                     splitting, `split` leaves the numbered pieces without joining or \
+                     deleting them, and `merge` is a full run (default merge)",
+                )
+                .possible_values(&["probe", "split", "merge"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cleanup")
+                .long("cleanup")
+                .value_name("WHEN")
+                .help(
+                    "When to delete the numbered split pieces and the directory they are \
+                     written to: `on-success` once the output is written, `always` even \
+                     when the run fails, or `never` to keep them (default on-success)",
+                )
+                .possible_values(&["always", "on-success", "never"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("temp-dir")
+                .long("temp-dir")
+                .value_name("DIR")
+                .help(
+                    "Make the run's temporary directory, which the split pieces and every \
+                     other intermediate are written to, in DIR instead of next to the \
+                     output. Joining is quickest when DIR is on the same filesystem as \
+                     the output",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("keep-temp")
+                .long("keep-temp")
+                .help(
+                    "Keep the run's temporary directory and everything in it, the padded \
+                     and transcode intermediates as well as the split pieces",
+                )
+                .conflicts_with("cleanup"),
+        )
+        .arg(
+            Arg::with_name("interactive")
+                .long("interactive")
+                .help("Show the output path, cut times and temporary files and ask before splitting"),
+        )
+        .arg(
+            Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Answer yes to the --interactive prompt"),
+        )
+        .arg(
+            Arg::with_name("print-framerate")
+                .long("print-framerate")
+                .help("Print the framerate that would be used and exit without splitting"),
+        )
+        .arg(
+            Arg::with_name("audio-info")
+                .long("audio-info")
+                .help(
+                    "Print the codec, sample rate, channels and bit depth of the input's \
+                     audio streams and exit without splitting",
+                )
+                .conflicts_with_all(&["print-framerate", "pair-dir", "job"]),
+        )
+        .arg(
+            Arg::with_name("identify")
+                .long("identify")
+                .help(
+                    "Print what mkvmerge -J reports about the input, the tracks as mkvmerge \
+                     will split them, and exit. Needs mkvmerge, whichever backend is used",
+                )
+                .conflicts_with_all(&["audio-info", "print-framerate", "pair-dir", "job"]),
+        )
+        .arg(
+            Arg::with_name("print-trims")
+                .long("print-trims")
+                .help(
+                    "Print the script's trims with their timecodes and exit without splitting. \
+                     -i is only needed for trims counted from the end of the clip",
+                )
+                .requires("avs")
+                .conflicts_with_all(&["print-framerate", "pair-dir", "job", "audio-info"]),
+        )
+        .arg(
+            Arg::with_name("dump-script")
+                .long("dump-script")
+                .help(
+                    "Print the script text that trims are searched for in, after --edit picks \
+                     out its edit, and exit",
+                )
+                .hidden(true)
+                .requires("avs")
+                .conflicts_with_all(&[
+                    "print-framerate",
+                    "pair-dir",
+                    "job",
+                    "audio-info",
+                    "print-trims",
+                ]),
+        )
+        .arg(
+            Arg::with_name("mkdir")
+                .long("mkdir")
+                .help("Create the output directory if it doesn't exist (the default)")
+                .overrides_with("no-mkdir"),
+        )
+        .arg(
+            Arg::with_name("no-mkdir")
+                .long("no-mkdir")
+                .help("Fail instead of creating a missing output directory")
+                .overrides_with("mkdir"),
+        )
+        .arg(
+            Arg::with_name("preserve-mtime")
+                .long("preserve-mtime")
+                .help("Give the output the same modification time as the input audio"),
+        )
+        .arg(
+            Arg::with_name("mkvmerge")
+                .long("mkvmerge")
+                .value_name("PATH")
+                .help("Path to the mkvmerge executable")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ffprobe")
+                .long("ffprobe")
+                .value_name("PATH")
+                .help("Path to the ffprobe executable")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ffmpeg")
+                .long("ffmpeg")
+                .value_name("PATH")
+                .help("Path to the ffmpeg executable")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .help(
+                    "When to colour errors and doctor results. auto colours a terminal \
+                     unless NO_COLOR is set (default auto)",
+                )
+                .possible_values(&["auto", "always", "never"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .value_name("BACKEND")
+                .help("The tool used to split and join the audio (default mkvmerge)")
+                .possible_values(&["mkvmerge", "ffmpeg"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("copy-codec")
+                .long("copy-codec")
+                .help(
+                    "With the ffmpeg backend, write the audio in its own codec to \
+                     the container given by the output extension instead of Matroska",
+                ),
+        )
+        .arg(
+            Arg::with_name("transcode")
+                .long("transcode")
+                .value_name("CODEC[:BITRATE]")
+                .conflicts_with_all(&["copy-codec", "no-merge"])
+                .help(
+                    "With the ffmpeg backend, encode the joined output to aac, ac3, eac3, \
+                     flac, mp3, opus or vorbis, like opus:128k, in the container given by \
+                     the output extension. The audio is copied as it is without this",
+                ),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("PATH")
+                .help(
+                    "Read default options from this file \
+                     (default: split_aud.toml in the current directory or $XDG_CONFIG_HOME)",
+                )
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("verbosity")
                 .short("v")
                 .long("verbose")
                 .help("Sets the level of verbosity"),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("Don't warn when the default framerate is assumed"),
+        )
+        .after_help(
+            "Every path argument, the script and the --mkvmerge, --ffprobe and --ffmpeg tools included, has ~ and $VAR expanded.\n\n\
+             EXIT CODES:\n    0    Success\n    1    I/O error\n    2    No trims found, or the script is empty\n    \
+             3    mkvmerge, ffmpeg or ffprobe not found\n    4    mkvmerge, ffmpeg or ffprobe failed\n    5    Invalid arguments\n    \
+             6    Invalid trim in script\n    7    Sanity check failed (--strict, --verify, --warn-as-error)\n    \
+             8    No framerate declared in script\n    9    Cancelled at the --interactive prompt",
+        )
+}
 
-    let mut options = Config {
-        framerate: 30000f32 / 1001f32,
-        input_aud: PathBuf::new(),
-        output_aud: PathBuf::new(),
-        input_avs: PathBuf::new(),
-        verbose: false,
+fn parse_args() -> Result<Config, SplitError> {
+    let matches = app().get_matches_safe().unwrap_or_else(|e| match e.kind {
+        ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+        // Someone trying the tool out gets pointed somewhere, scripts get the usage error
+        _ if std::env::args_os().len() == 1 && io::stdin().is_terminal() => {
+            eprint!("{}", GETTING_STARTED);
+            process::exit(SplitError::InvalidArgument(String::new()).exit_code());
+        }
+        _ => {
+            eprintln!("{}", e.message);
+            process::exit(SplitError::InvalidArgument(String::new()).exit_code());
+        }
+    });
+    options_from_matches(&matches)
+}
+
+/// The options the parsed arguments ask for, over those of the config file
+fn options_from_matches(matches: &ArgMatches) -> Result<Config, SplitError> {
+    let mut options = Config::default();
+
+    // Needed before any framerate is parsed, including one from the config file
+    let framerate_tolerance = match matches.value_of("framerate-tolerance") {
+        Some(tolerance) => tolerance
+            .parse::<f64>()
+            .ok()
+            .filter(|tolerance| *tolerance >= 0f64)
+            .ok_or_else(|| {
+                SplitError::InvalidArgument(format!("Invalid framerate tolerance: {}", tolerance))
+            })?,
+        None => DEFAULT_FRAMERATE_TOLERANCE,
     };
 
+    let mut strip_suffixes = default_strip_suffixes();
+
+    // Settings from a config file come first so that flags can override them
+    let config_path = config_file_path(matches.value_of("config").map(expand_path).transpose()?);
+    if let Some(config_path) = config_path {
+        let config = load_config_file(&config_path)?;
+        if let Some(framerate) = config.framerate {
+            options.framerate = Rational::parse(&framerate, framerate_tolerance)?;
+            options.framerate_source = FramerateSource::Argument;
+        }
+        if let Some(mkvmerge) = config.mkvmerge {
+            options.mkvmerge = mkvmerge;
+        }
+        if let Some(ffprobe) = config.ffprobe {
+            options.ffprobe = ffprobe;
+        }
+        if let Some(ffmpeg) = config.ffmpeg {
+            options.ffmpeg = ffmpeg;
+        }
+        if let Some(backend) = config.backend {
+            options.backend = backend;
+        }
+        if let Some(ref suffix) = config.strip_suffix {
+            strip_suffixes = vec![compile_strip_suffix(suffix)?];
+        }
+        if let Some(verbose) = config.verbose {
+            options.verbose = verbose;
+        }
+    }
+
     if matches.is_present("framerate") {
-        let parts: Vec<&str> = matches.value_of("framerate").unwrap().split('/').collect();
-        let framerate_num = parts[0].parse::<f32>().unwrap();
-        let framerate_den = parts[1].parse::<f32>().unwrap();
-        options.framerate = framerate_num / framerate_den;
+        options.framerate =
+            Rational::parse(matches.value_of("framerate").unwrap(), framerate_tolerance)?;
+        options.framerate_source = FramerateSource::Argument;
+    } else if let (Some(num), Some(den)) =
+        (matches.value_of("fps-num"), matches.value_of("fps-den"))
+    {
+        let whole = |value: &str, flag: &str| {
+            value
+                .parse::<u64>()
+                .ok()
+                .filter(|&value| value > 0)
+                .ok_or_else(|| {
+                    SplitError::InvalidArgument(format!(
+                        "Invalid {} {}, expected a positive whole number",
+                        flag, value
+                    ))
+                })
+        };
+        let (num, den) = (whole(num, "--fps-num")?, whole(den, "--fps-den")?);
+        options.framerate = Rational::parse(&format!("{}/{}", num, den), framerate_tolerance)?;
+        options.framerate_source = FramerateSource::Argument;
+    } else if let Some(path) = matches.value_of("mediainfo") {
+        let path = expand_path(path)?;
+        let contents = fs::read_to_string(&path).map_err(|e| SplitError::Io(path.clone(), e))?;
+        options.framerate =
+            parse_mediainfo_framerate(&contents, framerate_tolerance)?.ok_or_else(|| {
+                SplitError::InvalidArgument(format!(
+                    "No video frame rate found in {}",
+                    path.display()
+                ))
+            })?;
+        options.framerate_source = FramerateSource::MediaInfo;
+    }
+    if let Some(framerate) = matches.value_of("audio-framerate") {
+        options.audio_framerate = Some(Rational::parse(framerate, framerate_tolerance)?);
     }
+    options.fps_from = matches.value_of("fps-from").map(expand_path).transpose()?;
+    options.framerate_from_script = matches.is_present("framerate-from-script");
+    options.no_default_framerate = matches.is_present("no-default-framerate");
 
-    options.input_aud = PathBuf::from(matches.value_of("input").unwrap());
-    options.input_avs = PathBuf::from(matches.value_of("avs").unwrap());
-    if matches.is_present("output") {
-        options.output_aud = PathBuf::from(matches.value_of("output").unwrap());
+    if let Some(suffixes) = matches.values_of("strip-suffix") {
+        strip_suffixes = suffixes
+            .map(compile_strip_suffix)
+            .collect::<Result<_, _>>()?;
+    } else if matches.is_present("keep-suffixes") {
+        strip_suffixes.clear();
+    }
+
+    // A job stands in for the flags it conflicts with
+    if let Some(job_path) = matches.value_of("job") {
+        let job = load_job(&expand_path(job_path)?)?;
+        if let Some(ref framerate) = job.framerate {
+            options.framerate = Rational::parse(framerate, framerate_tolerance)?;
+            options.framerate_source = FramerateSource::Argument;
+        }
+        options.output_aud = match (job.output, &job.script) {
+            (Some(output), _) => output,
+            (None, Some(script)) => derive_output_path(script, &strip_suffixes),
+            (None, None) => unreachable!("load_job requires an output without a script"),
+        };
+        options.input_aud = job.input;
+        options.input_avs = job.script;
+        options.cuts_file = job.cuts_file;
+        options.cuts = job.cuts;
+        options.cuts_unit = job.cuts_unit;
+        options.delay = job.delay;
+        if let Some(backend) = job.backend {
+            options.backend = backend;
+        }
     } else {
-        options.output_aud = options.input_avs.with_extension("mka");
+        options.input_aud = matches
+            .value_of("input")
+            .map(expand_path)
+            .transpose()?
+            .unwrap_or_default();
+        options.input_avs = matches.value_of("avs").map(expand_path).transpose()?;
+        if let Some(trims) = matches.values_of("trim") {
+            options.cuts = Some(trims.map(JobCut::parse_trim).collect::<Result<_, _>>()?);
+        }
+        options.cuts_file = matches.value_of("cuts-file").map(expand_path).transpose()?;
+        options.split_at_chapters = matches.is_present("split-at-chapters");
+        options.cuts_tag = matches.value_of("cuts-from-tags").map(ToOwned::to_owned);
+        options.keyframes_file = matches
+            .value_of("keyframes-file")
+            .map(expand_path)
+            .transpose()?;
+        if matches.is_present("cuts-unit") {
+            options.cuts_unit = CutsUnit::parse(matches.value_of("cuts-unit").unwrap())?;
+        }
+        if let Some(mode) = matches.value_of("cuts-file-mode") {
+            options.cuts_file_mode = CutsFileMode::parse(mode)?;
+        }
+        options.pair_dir = matches.value_of("pair-dir").map(expand_path).transpose()?;
+        if let Some(values) = matches.values_of("compile") {
+            let paths = values.map(expand_path).collect::<Result<Vec<_>, _>>()?;
+            options.compile = paths
+                .chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect();
+        }
+        options.incremental = matches.is_present("incremental");
+        if let Some(ref dir) = options.pair_dir {
+            // In batch mode the output is a directory; each pair gets its own file in it
+            options.output_aud = match matches.value_of("output") {
+                Some(output) => expand_path(output)?,
+                None => dir.join("trimmed"),
+            };
+        } else if let Some(output) = matches.value_of("output") {
+            let path = expand_path(output)?;
+            // `-o dir/` names the file the same way as without -o, but puts it in `dir`
+            let is_dir = output.ends_with('/')
+                || output.ends_with(std::path::MAIN_SEPARATOR)
+                || path.is_dir();
+            options.output_aud = if is_dir {
+                let named_after = options.input_avs.as_ref().unwrap_or(&options.input_aud);
+                let derived = derive_output_path(named_after, &strip_suffixes);
+                let output = path.join(derived.file_name().unwrap_or_default());
+                let same_as_input = output.exists()
+                    && fs::canonicalize(&output).ok() == fs::canonicalize(&options.input_aud).ok();
+                if same_as_input {
+                    return Err(SplitError::InvalidArgument(format!(
+                        "The output in {} would be named {}, the same as the input. \
+                         Pass a file name to -o instead",
+                        path.display(),
+                        output.display()
+                    )));
+                }
+                output
+            } else {
+                path
+            };
+        } else if let Some(ref avs) = options.input_avs {
+            options.output_aud = derive_output_path(avs, &strip_suffixes);
+        } else if !matches.is_present("audio-info")
+            && !matches.is_present("identify")
+            && matches.subcommand_matches("doctor").is_none()
+        {
+            return Err(SplitError::InvalidArgument(
+                "--output is required when no script is given".to_owned(),
+            ));
+        }
     }
+    options.also_split = matches
+        .value_of("also-split")
+        .map(expand_path)
+        .transpose()?;
 
-    options.verbose = matches.is_present("verbosity");
+    if matches.is_present("delay") {
+        let delay = matches.value_of("delay").unwrap();
+        options.delay = Some(
+            delay
+                .parse::<isize>()
+                .map_err(|_| SplitError::InvalidArgument(format!("Invalid delay: {}", delay)))?,
+        );
+    }
 
-    split_audio(&options);
+    options.delay_log = matches.value_of("delay-log").map(expand_path).transpose()?;
+    if matches.is_present("delay-mode") {
+        options.delay_mode = DelayMode::parse(matches.value_of("delay-mode").unwrap())?;
+    }
+    options.edit_compensation = !matches.is_present("no-edit-compensation");
+    options.normalize_delay = matches.is_present("normalize-delay");
+    options.fields = matches.is_present("fields");
+    options.invert = matches.is_present("invert");
+    options.widen_short_trims = matches.is_present("widen-short-trims");
+    if let Some(max_gap) = matches.value_of("max-gap-merge") {
+        options.max_gap_merge = Some(max_gap.parse::<usize>().map_err(|_| {
+            SplitError::InvalidArgument(format!("Invalid --max-gap-merge: {}", max_gap))
+        })?);
+    }
+    if let Some(first) = matches.value_of("first-segment") {
+        options.first_segment = FirstSegment::parse(first)?;
+    }
+    if let Some(tolerance) = matches.value_of("end-tolerance-frames") {
+        options.end_tolerance = Some(tolerance.parse::<usize>().map_err(|_| {
+            SplitError::InvalidArgument(format!("Invalid --end-tolerance-frames: {}", tolerance))
+        })?);
+    }
+    if let Some(start_frame) = matches.value_of("start-frame") {
+        options.start_frame = start_frame.parse::<usize>().map_err(|_| {
+            SplitError::InvalidArgument(format!("Invalid --start-frame: {}", start_frame))
+        })?;
+    }
+    options.no_merge = matches.is_present("no-merge");
+    if matches.is_present("name-by-range") {
+        options.name_template = "f{start}-{end}".to_owned();
+    }
+    if let Some(template) = matches.value_of("name-template") {
+        options.name_template = template.to_owned();
+    }
+    if let Some(indices) = matches.value_of("keep-indices") {
+        options.keep_indices = Some(
+            indices
+                .split(',')
+                .map(|index| {
+                    index
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&index| index > 0)
+                        .ok_or_else(|| {
+                            SplitError::InvalidArgument(format!(
+                                "Invalid piece index in --keep-indices: {}",
+                                index
+                            ))
+                        })
+                })
+                .collect::<Result<_, _>>()?,
+        );
+    }
+    if matches.is_present("trim-regex") {
+        options.trim_regex = Some(compile_trim_regex(matches.value_of("trim-regex").unwrap())?);
+    }
+    options.trim_end_is_count = matches.is_present("trim-end-is-count");
+    options.edit = matches.value_of("edit").map(str::to_owned);
+    if let Some(keyword) = matches.value_of("trims-keyword") {
+        options.trims_keyword = keyword.to_owned();
+    }
+    options.passthrough_on_no_trims = matches.is_present("passthrough-on-no-trims");
+    if let Some(path) = matches.value_of("reference-video") {
+        options.reference_video = Some(expand_path(path)?);
+    }
+    if matches.is_present("duration-tolerance") {
+        let tolerance = matches.value_of("duration-tolerance").unwrap();
+        options.duration_tolerance = tolerance
+            .parse::<f64>()
+            .ok()
+            .filter(|tolerance| *tolerance >= 0f64)
+            .ok_or_else(|| {
+                SplitError::InvalidArgument(format!("Invalid duration tolerance: {}", tolerance))
+            })?;
+    }
+    if let Some(source) = matches.value_of("duration-source") {
+        options.duration_source = DurationSource::parse(source)?;
+    }
+    if let Some(track) = matches.value_of("audio-track") {
+        options.audio_track = Some(track.parse::<usize>().map_err(|_| {
+            SplitError::InvalidArgument(format!("Invalid --audio-track: {}", track))
+        })?);
+    }
+    options.strict = matches.is_present("strict");
+    options.warn_as_error = matches.is_present("warn-as-error");
+    options.force = matches.is_present("force");
+    options.mark_output = matches.is_present("mark-output");
+    options.verify = matches.is_present("verify");
+    options.preview = matches.is_present("preview");
+    if let Some(path) = matches.value_of("report") {
+        options.report = Some(expand_path(path)?);
+    }
+    options.output_edl = matches
+        .value_of("output-edl")
+        .map(expand_path)
+        .transpose()?;
+    options.output_avs_trims = matches
+        .value_of("output-avs-trims")
+        .map(expand_path)
+        .transpose()?;
+    if let Some(path) = matches.value_of("output-markers") {
+        let path = expand_path(path)?;
+        let format = CaptionFormat::from_path(&path)?;
+        options.output_markers = Some((path, format));
+    }
+    options.command_log = matches
+        .value_of("command-log")
+        .map(expand_path)
+        .transpose()?;
+    options.mkdir = !matches.is_present("no-mkdir");
+    options.preserve_mtime = matches.is_present("preserve-mtime");
+    options.timings = matches.is_present("timings");
+    options.list_segments = matches.is_present("list-segments");
+    options.run = matches.is_present("run");
+    options.dump_plan = matches.is_present("dump-plan");
+    options.dry_run = matches.is_present("dry-run");
+    if let Some(stage) = matches.value_of("stop-after") {
+        options.stop_after = Stage::parse(stage)?;
+    }
+    if let Some(cleanup) = matches.value_of("cleanup") {
+        options.cleanup = Cleanup::parse(cleanup)?;
+    }
+    if matches.is_present("keep-temp") {
+        options.keep_temp = true;
+        options.cleanup = Cleanup::Never;
+    }
+    if let Some(path) = matches.value_of("temp-dir") {
+        options.temp_dir = Some(expand_path(path)?);
+    }
+    options.interactive = matches.is_present("interactive");
+    options.yes = matches.is_present("yes");
+    options.print_framerate = matches.is_present("print-framerate");
+    options.audio_info = matches.is_present("audio-info");
+    options.identify = matches.is_present("identify");
+    options.dump_script = matches.is_present("dump-script");
+    options.print_trims = matches.is_present("print-trims");
+    if let Some(color) = matches.value_of("color") {
+        options.color = ColorMode::parse(color)?;
+    }
+    options.doctor = matches.subcommand_matches("doctor").is_some();
+    if let Some(path) = matches.value_of("mkvmerge") {
+        options.mkvmerge = expand_path(path)?;
+    }
+    if let Some(path) = matches.value_of("ffprobe") {
+        options.ffprobe = expand_path(path)?;
+    }
+    if let Some(path) = matches.value_of("ffmpeg") {
+        options.ffmpeg = expand_path(path)?;
+    }
+    if matches.is_present("backend") {
+        options.backend = Backend::parse(matches.value_of("backend").unwrap())?;
+    }
+    options.copy_codec = matches.is_present("copy-codec");
+    if options.copy_codec && options.backend != Backend::Ffmpeg {
+        return Err(SplitError::InvalidArgument(
+            "--copy-codec requires the ffmpeg backend".to_owned(),
+        ));
+    }
+    if let Some(transcode) = matches.value_of("transcode") {
+        options.transcode = Some(Transcode::parse(transcode)?);
+        if options.backend != Backend::Ffmpeg {
+            return Err(SplitError::InvalidArgument(
+                "--transcode requires the ffmpeg backend".to_owned(),
+            ));
+        }
+    }
+    if let Some(scale) = matches.value_of("timestamp-scale") {
+        options.timestamp_scale = Some(
+            scale
+                .parse::<u64>()
+                .ok()
+                .filter(|&scale| scale > 0)
+                .ok_or_else(|| {
+                    SplitError::InvalidArgument(format!(
+                        "Invalid --timestamp-scale {}, expected a positive whole number",
+                        scale
+                    ))
+                })?,
+        );
+        if options.backend != Backend::Mkvmerge {
+            return Err(SplitError::InvalidArgument(
+                "--timestamp-scale requires the mkvmerge backend".to_owned(),
+            ));
+        }
+    }
+    options.deterministic = matches.is_present("deterministic");
+    options.post_hook = matches.value_of("post-hook").map(str::to_owned);
+    options.summary_json = matches.is_present("summary-json");
+    options.stream = matches.is_present("stream");
+    options.snap_to_packet = matches.is_present("snap-to-packet");
+    if options.snap_to_packet && options.backend != Backend::Ffmpeg {
+        return Err(SplitError::InvalidArgument(
+            "--snap-to-packet requires the ffmpeg backend".to_owned(),
+        ));
+    }
+    if options.stream && options.backend != Backend::Ffmpeg {
+        return Err(SplitError::InvalidArgument(
+            "--stream requires the ffmpeg backend".to_owned(),
+        ));
+    }
+    if options.stream && options.stop_after == Stage::Split {
+        return Err(SplitError::InvalidArgument(
+            "--stream writes no pieces to stop after".to_owned(),
+        ));
+    }
+    if matches.is_present("verbosity") {
+        options.verbose = true;
+    }
+    options.quiet = matches.is_present("quiet");
+
+    // Catch a missing or unreadable script before doing anything else
+    if let Some(ref avs) = options.input_avs {
+        File::open(avs).map_err(|e| SplitError::Script(avs.clone(), e))?;
+    }
+
+    Ok(options)
+}
+
+fn main() {
+    // The error can't be coloured before --color has been read
+    let mut options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(e.exit_code());
+        }
+    };
+    if let Err(e) = run(&mut options) {
+        eprintln!("{} {}", error_label(&options), e);
+        process::exit(e.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use split_aud::job::cut_trims;
+    use std::path::{Path, PathBuf};
+
+    /// `args` after the program name
+    fn args<'a>(args: &[&'a str]) -> Vec<&'a str> {
+        std::iter::once("split_aud")
+            .chain(args.iter().cloned())
+            .collect()
+    }
+
+    /// `args` with an input, output and trim, which every run without a script needs
+    fn trimmed<'a>(extra: &[&'a str]) -> Vec<&'a str> {
+        let mut args = vec!["-i", "in.flac", "--trim", "0-100", "-o", "out.mka"];
+        args.extend_from_slice(extra);
+        args
+    }
+
+    /// Parses `args` the way `parse_args` parses the command line
+    fn parse(args_given: &[&str]) -> Result<Config, SplitError> {
+        let matches = app()
+            .get_matches_from_safe(args(args_given))
+            .map_err(|e| SplitError::InvalidArgument(e.message))?;
+        options_from_matches(&matches)
+    }
+
+    /// The kind of usage error clap gives for `args`
+    fn usage_error(args_given: &[&str]) -> ErrorKind {
+        app()
+            .get_matches_from_safe(args(args_given))
+            .map(|_| ())
+            .unwrap_err()
+            .kind
+    }
+
+    #[test]
+    fn fps_num_and_den_give_the_framerate_together() {
+        let options = parse(&trimmed(&["--fps-num", "24000", "--fps-den", "1001"])).unwrap();
+        assert_eq!(
+            options.framerate,
+            Rational {
+                num: 24000,
+                den: 1001
+            }
+        );
+        assert_eq!(options.framerate_source, FramerateSource::Argument);
+    }
+
+    #[test]
+    fn fps_num_and_den_need_each_other() {
+        assert_eq!(
+            usage_error(&trimmed(&["--fps-num", "24000"])),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            usage_error(&trimmed(&["--fps-den", "1001"])),
+            ErrorKind::MissingRequiredArgument
+        );
+    }
+
+    #[test]
+    fn fps_num_and_den_conflict_with_framerate() {
+        assert_eq!(
+            usage_error(&trimmed(&[
+                "-f",
+                "25",
+                "--fps-num",
+                "24000",
+                "--fps-den",
+                "1001"
+            ])),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn fps_num_and_den_must_be_positive_whole_numbers() {
+        for (num, den) in [
+            ("0", "1001"),
+            ("24000", "0"),
+            ("23.976", "1"),
+            ("24000", "-1"),
+        ] {
+            match parse(&trimmed(&["--fps-num", num, "--fps-den", den])) {
+                Err(SplitError::InvalidArgument(_)) => (),
+                other => panic!("expected {}/{} to be rejected, got {:?}", num, den, other),
+            }
+        }
+    }
+
+    #[test]
+    fn trims_are_kept_in_the_order_given() {
+        let options = parse(&[
+            "-i", "in.flac", "--trim", "0-100", "--trim", "200-300", "-o", "out.mka",
+        ])
+        .unwrap();
+        let cuts = options.cuts.unwrap();
+        let trims = cut_trims(&cuts, CutsUnit::Frames, options.framerate).unwrap();
+        let ranges: Vec<(usize, usize)> = trims.iter().map(|trim| (trim.start, trim.end)).collect();
+        // Like Trim(0, 100) and Trim(200, 300), the end frames are kept
+        assert_eq!(ranges, [(0, 101), (200, 301)]);
+        assert_eq!(trims[1].line, 2);
+        assert_eq!(trims[1].text, "--trim 200-300");
+    }
+
+    #[test]
+    fn a_trim_without_an_end_is_rejected() {
+        match parse(&["-i", "in.flac", "--trim", "100", "-o", "out.mka"]) {
+            Err(SplitError::InvalidArgument(message)) => {
+                assert_eq!(message, "Invalid --trim 100, expected START-END")
+            }
+            other => panic!("expected a bad --trim, got {:?}", other),
+        }
+    }
+
+    /// An empty directory of the test's own under the system temp directory
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("split_aud-main-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn an_output_directory_gets_the_derived_name() {
+        let options = parse(&[
+            "-i",
+            "audio/Ep01 DELAY -50ms.flac",
+            "--trim",
+            "0-100",
+            "-o",
+            "some/dir/",
+        ])
+        .unwrap();
+        assert_eq!(options.output_aud, Path::new("some/dir/Ep01.mka"));
+    }
+
+    #[test]
+    fn an_output_directory_is_named_after_the_script() {
+        let dir = test_dir("output-dir");
+        let script = dir.join("Ep02.avs");
+        fs::write(&script, "Trim(0, 100)").unwrap();
+        let options =
+            parse(&["-i", "in.flac", script.to_str().unwrap(), "-o", "some/dir/"]).unwrap();
+        assert_eq!(options.output_aud, Path::new("some/dir/Ep02.mka"));
+        // An existing directory needn't end in a slash
+        let out = dir.join("out");
+        fs::create_dir(&out).unwrap();
+        let options = parse(&[
+            "-i",
+            "in.flac",
+            script.to_str().unwrap(),
+            "-o",
+            out.to_str().unwrap(),
+        ])
+        .unwrap();
+        assert_eq!(options.output_aud, out.join("Ep02.mka"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_output_directory_cannot_name_the_output_after_the_input_it_holds() {
+        let dir = test_dir("output-dir-input");
+        let input = dir.join("Ep03.mka");
+        fs::write(&input, "").unwrap();
+        let output = format!("{}/", dir.display());
+        match parse(&[
+            "-i",
+            input.to_str().unwrap(),
+            "--trim",
+            "0-100",
+            "-o",
+            &output,
+        ]) {
+            Err(SplitError::InvalidArgument(message)) => {
+                assert!(message.contains("the same as the input"), "{}", message)
+            }
+            other => panic!("expected the output to be refused, got {:?}", other),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn print_trims_needs_no_input() {
+        let dir = test_dir("print-trims");
+        let script = dir.join("Ep04.avs");
+        fs::write(&script, "Trim(0, 100)").unwrap();
+        let options = parse(&[script.to_str().unwrap(), "--print-trims"]).unwrap();
+        assert!(options.print_trims);
+        assert_eq!(options.input_aud, PathBuf::new());
+        // Every other run needs the audio
+        assert_eq!(
+            usage_error(&[script.to_str().unwrap()]),
+            ErrorKind::MissingRequiredArgument
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,62 @@
+use crate::error::SplitError;
+use crate::json::Json;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What `--mark-output` records next to an output about how it was made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    /// The audio the output was split from
+    pub source: String,
+    /// A [`trims_hash`] of the segments that were kept
+    pub trims_hash: String,
+}
+
+/// Where the marker for `audio` goes, like `Ep01.trimmed.mka.split_aud`
+pub fn marker_path(audio: &Path) -> PathBuf {
+    let mut name = audio.as_os_str().to_owned();
+    name.push(".split_aud");
+    PathBuf::from(name)
+}
+
+/// A short hash of the kept `spans` (in nanoseconds, in join order), which stays the
+/// same between runs and builds so that markers can be compared.
+///
+/// This is 64-bit FNV-1a, which is plenty to tell two sets of trims apart.
+pub fn trims_hash(spans: &[(u128, Option<u128>)]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &(start, end) in spans {
+        let span = match end {
+            Some(end) => format!("{}-{};", start, end),
+            None => format!("{}-;", start),
+        };
+        for byte in span.bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+pub fn write_marker(output: &Path, marker: &Marker) -> Result<(), SplitError> {
+    let path = marker_path(output);
+    let json = Json::object(vec![
+        ("source", Json::string(marker.source.as_str())),
+        ("trims_hash", Json::string(marker.trims_hash.as_str())),
+    ]);
+    fs::write(&path, json.to_pretty_string() + "\n").map_err(|e| SplitError::Io(path, e))
+}
+
+/// Reads the marker left next to `audio`, if it has one. A marker that can't be read
+/// is ignored, since it only ever guards against splitting a file twice.
+pub fn read_marker(audio: &Path) -> Option<Marker> {
+    let contents = fs::read_to_string(marker_path(audio)).ok()?;
+    let json = Json::parse(&contents).ok()?;
+    match (json.get("source"), json.get("trims_hash")) {
+        (Some(Json::String(source)), Some(Json::String(trims_hash))) => Some(Marker {
+            source: source.clone(),
+            trims_hash: trims_hash.clone(),
+        }),
+        _ => None,
+    }
+}
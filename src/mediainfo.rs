@@ -0,0 +1,165 @@
+use crate::error::SplitError;
+use crate::framerate::Rational;
+use crate::json::Json;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Reads the frame rate of the first video track in a MediaInfo export, either the XML
+/// of `mediainfo --Output=XML` or the JSON of `--Output=JSON`, for `--mediainfo`.
+///
+/// `FrameRate_Num` and `FrameRate_Den` give the exact rate when MediaInfo knows it.
+/// Otherwise the decimal `FrameRate` is read, matched to a common rate within
+/// `tolerance` like `--framerate` is. Returns `None` when there is no video frame rate.
+pub fn parse_mediainfo_framerate(
+    contents: &str,
+    tolerance: f64,
+) -> Result<Option<Rational>, SplitError> {
+    let fields = if contents.trim_start().starts_with('{') {
+        json_video_fields(contents)?
+    } else {
+        xml_video_fields(contents)
+    };
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+    if let (Some(num), Some(den)) = (field("FrameRate_Num"), field("FrameRate_Den")) {
+        return Rational::parse(&format!("{}/{}", num, den), tolerance).map(Some);
+    }
+    field("FrameRate")
+        .map(|rate| Rational::parse(rate, tolerance))
+        .transpose()
+}
+
+/// The frame rate fields of the first `<track type="Video">`
+fn xml_video_fields(contents: &str) -> Vec<(String, String)> {
+    static TRACK_REGEX: OnceLock<Regex> = OnceLock::new();
+    let track_regex = TRACK_REGEX
+        .get_or_init(|| Regex::new(r#"(?s)<track\s+type="Video"[^>]*>(.*?)</track>"#).unwrap());
+    static FIELD_REGEX: OnceLock<Regex> = OnceLock::new();
+    let field_regex =
+        FIELD_REGEX.get_or_init(|| Regex::new(r"<(FrameRate(?:_Num|_Den)?)>([^<]*)<").unwrap());
+    match track_regex.captures(contents) {
+        Some(track) => field_regex
+            .captures_iter(&track[1])
+            .map(|field| (field[1].to_owned(), field[2].to_owned()))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// The string fields of the first track with an `@type` of `Video`
+fn json_video_fields(contents: &str) -> Result<Vec<(String, String)>, SplitError> {
+    let json = Json::parse(contents)
+        .map_err(|e| SplitError::InvalidArgument(format!("Invalid MediaInfo JSON: {}", e)))?;
+    let tracks = match json.get("media").and_then(|media| media.get("track")) {
+        Some(Json::Array(tracks)) => tracks,
+        _ => return Ok(Vec::new()),
+    };
+    let video = tracks
+        .iter()
+        .find(|track| matches!(track.get("@type"), Some(Json::String(kind)) if kind == "Video"));
+    Ok(match video {
+        Some(Json::Object(fields)) => fields
+            .iter()
+            .filter_map(|(name, value)| match value {
+                Json::String(value) => Some((name.clone(), value.clone())),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framerate::DEFAULT_FRAMERATE_TOLERANCE;
+
+    const FILM: Rational = Rational {
+        num: 24000,
+        den: 1001,
+    };
+
+    fn parse(contents: &str) -> Option<Rational> {
+        parse_mediainfo_framerate(contents, DEFAULT_FRAMERATE_TOLERANCE).unwrap()
+    }
+
+    /// A trimmed `mediainfo --Output=XML` export with the given video track fields
+    fn xml(video_fields: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<MediaInfo xmlns="https://mediaarea.net/mediainfo" version="2.0">
+<media ref="episode.mkv">
+<track type="General">
+<FrameRate>25.000</FrameRate>
+</track>
+<track type="Video" typeorder="1">
+<Format>AVC</Format>
+{}
+</track>
+<track type="Audio">
+<FrameRate>31.250</FrameRate>
+</track>
+</media>
+</MediaInfo>"#,
+            video_fields
+        )
+    }
+
+    #[test]
+    fn the_exact_xml_rate_is_preferred() {
+        let contents = xml("<FrameRate_Mode>CFR</FrameRate_Mode>\n\
+             <FrameRate>23.976</FrameRate>\n\
+             <FrameRate_Num>24000</FrameRate_Num>\n\
+             <FrameRate_Den>1001</FrameRate_Den>");
+        assert_eq!(parse(&contents), Some(FILM));
+    }
+
+    #[test]
+    fn the_decimal_xml_rate_is_matched_to_a_common_rate() {
+        assert_eq!(
+            parse(&xml("<FrameRate>29.970</FrameRate>")),
+            Some(Rational::NTSC)
+        );
+        assert_eq!(
+            parse(&xml("<FrameRate>50.000</FrameRate>")),
+            Some(Rational { num: 50, den: 1 })
+        );
+        // Without both halves of the exact rate the decimal is read
+        assert_eq!(
+            parse(&xml(
+                "<FrameRate>23.976</FrameRate>\n<FrameRate_Num>24000</FrameRate_Num>"
+            )),
+            Some(FILM)
+        );
+    }
+
+    #[test]
+    fn only_the_video_track_is_read() {
+        // The general and audio tracks have frame rates of their own
+        assert_eq!(parse(&xml("<Width>1920</Width>")), None);
+        assert_eq!(parse("<MediaInfo></MediaInfo>"), None);
+    }
+
+    #[test]
+    fn a_bad_xml_rate_is_an_error() {
+        assert!(parse_mediainfo_framerate(
+            &xml("<FrameRate>fast</FrameRate>"),
+            DEFAULT_FRAMERATE_TOLERANCE
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json_exports_are_read_too() {
+        let contents = r#"{"media": {"track": [
+            {"@type": "General", "FrameRate": "25.000"},
+            {"@type": "Video", "FrameRate": "23.976", "FrameRate_Num": "24000", "FrameRate_Den": "1001"}
+        ]}}"#;
+        assert_eq!(parse(contents), Some(FILM));
+    }
+}
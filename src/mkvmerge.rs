@@ -0,0 +1,195 @@
+use crate::error::{spawn_error, SplitError};
+use crate::json::Json;
+use crate::report::{Report, ReportCommand};
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::OnceLock;
+
+/// A parsed `mkvmerge --version`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MkvmergeVersion(pub u32, pub u32, pub u32);
+
+impl fmt::Display for MkvmergeVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// The oldest mkvmerge this tool supports
+pub const MIN_MKVMERGE_VERSION: MkvmergeVersion = MkvmergeVersion(5, 0, 0);
+
+/// mkvmerge 17 renamed "timecodes" to "timestamps" throughout its options
+const TIMESTAMPS_RENAME_VERSION: MkvmergeVersion = MkvmergeVersion(17, 0, 0);
+
+impl MkvmergeVersion {
+    /// Reads the version out of output like `mkvmerge v80.0 ('Roundabout') 64-bit`
+    pub fn parse(output: &str) -> Option<MkvmergeVersion> {
+        static VERSION_REGEX: OnceLock<Regex> = OnceLock::new();
+        let version_regex = VERSION_REGEX
+            .get_or_init(|| Regex::new(r"mkvmerge v(\d+)\.(\d+)(?:\.(\d+))?").unwrap());
+        let captures = version_regex.captures(output)?;
+        Some(MkvmergeVersion(
+            captures[1].parse().ok()?,
+            captures[2].parse().ok()?,
+            captures
+                .get(3)
+                .map_or(Some(0), |patch| patch.as_str().parse().ok())?,
+        ))
+    }
+
+    /// The name `--split` uses for splitting at a list of times
+    pub fn split_mode(self) -> &'static str {
+        if self >= TIMESTAMPS_RENAME_VERSION {
+            "timestamps"
+        } else {
+            "timecodes"
+        }
+    }
+
+    /// The option that sets the output's timestamp scale
+    pub fn timestamp_scale_option(self) -> &'static str {
+        if self >= TIMESTAMPS_RENAME_VERSION {
+            "--timestamp-scale"
+        } else {
+            "--timecode-scale"
+        }
+    }
+}
+
+/// Checks which mkvmerge is installed, failing if it is too old to rely on
+pub fn mkvmerge_version(mkvmerge: &Path) -> Result<MkvmergeVersion, SplitError> {
+    let output = Command::new(mkvmerge)
+        .arg("--version")
+        .output()
+        .map_err(|e| spawn_error("mkvmerge", e))?;
+    supported_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Reads the version out of `mkvmerge --version`, failing if it is too old
+fn supported_version(stdout: &str) -> Result<MkvmergeVersion, SplitError> {
+    let version = MkvmergeVersion::parse(stdout).ok_or_else(|| {
+        SplitError::ProcessFailed(
+            "mkvmerge".to_owned(),
+            format!("could not read version from {:?}", stdout.trim()),
+        )
+    })?;
+    if version < MIN_MKVMERGE_VERSION {
+        return Err(SplitError::ProcessFailed(
+            "mkvmerge".to_owned(),
+            format!(
+                "version {} is too old, at least {} is required",
+                version, MIN_MKVMERGE_VERSION
+            ),
+        ));
+    }
+    Ok(version)
+}
+
+/// Runs `mkvmerge -J` on `file`, giving mkvmerge's own view of its container and tracks
+pub fn identify(mkvmerge: &Path, file: &Path) -> Result<Json, SplitError> {
+    let output = Command::new(mkvmerge)
+        .arg("-J")
+        .arg(file)
+        .output()
+        .map_err(|e| spawn_error("mkvmerge", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // A file mkvmerge can't read still gets JSON, with the reason in its `errors`
+    if stdout.trim().is_empty() {
+        return Err(SplitError::ProcessFailed(
+            "mkvmerge".to_owned(),
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    Json::parse(&stdout).map_err(|e| {
+        SplitError::ProcessFailed(
+            "mkvmerge".to_owned(),
+            format!("could not read the output of -J: {}", e),
+        )
+    })
+}
+
+pub fn run_mkvmerge(
+    mkvmerge: &Path,
+    args: Vec<String>,
+    report: &mut Report,
+) -> Result<Output, SplitError> {
+    let result = Command::new(mkvmerge).args(&args).output();
+    let mut command_line = vec![mkvmerge.to_string_lossy().into_owned()];
+    command_line.extend(args);
+    report.commands.push(ReportCommand {
+        args: command_line,
+        status: result.as_ref().ok().and_then(|output| output.status.code()),
+    });
+
+    let output = result.map_err(|e| spawn_error("mkvmerge", e))?;
+    // mkvmerge exits with 1 when it only emitted warnings
+    match output.status.code() {
+        Some(0) | Some(1) => Ok(output),
+        status => Err(SplitError::ProcessFailed(
+            "mkvmerge".to_owned(),
+            failure_message(&output, status),
+        )),
+    }
+}
+
+/// What mkvmerge said about a failure. It writes its errors to stdout, but a build
+/// that can't start may only write to stderr, so both are kept.
+fn failure_message(output: &Output, status: Option<i32>) -> String {
+    let said: Vec<String> = [&output.stdout, &output.stderr]
+        .iter()
+        .map(|text| String::from_utf8_lossy(text).trim().to_owned())
+        .filter(|text| !text.is_empty())
+        .collect();
+    match status {
+        _ if !said.is_empty() => said.join("\n"),
+        Some(code) => format!("exited with status {}", code),
+        None => "killed by a signal".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_real_version_strings() {
+        assert_eq!(
+            MkvmergeVersion::parse("mkvmerge v9.8.0 ('Kuglblitz') 64-bit\n"),
+            Some(MkvmergeVersion(9, 8, 0))
+        );
+        assert_eq!(
+            MkvmergeVersion::parse("mkvmerge v81.0 ('Milliontown') 64-bit"),
+            Some(MkvmergeVersion(81, 0, 0))
+        );
+        assert_eq!(
+            MkvmergeVersion::parse("mkvmerge v5.0.1 ('Es ist Sommer') built on Oct 10 2011"),
+            Some(MkvmergeVersion(5, 0, 1))
+        );
+        assert_eq!(MkvmergeVersion::parse("mkvmerge: command not found"), None);
+    }
+
+    #[test]
+    fn versions_before_17_use_timecodes() {
+        assert_eq!(MkvmergeVersion(9, 8, 0).split_mode(), "timecodes");
+        assert_eq!(MkvmergeVersion(81, 0, 0).split_mode(), "timestamps");
+        assert_eq!(
+            MkvmergeVersion(16, 0, 0).timestamp_scale_option(),
+            "--timecode-scale"
+        );
+    }
+
+    #[test]
+    fn too_old_or_unreadable_versions_are_refused() {
+        assert_eq!(
+            supported_version("mkvmerge v9.8.0 ('Kuglblitz') 64-bit").unwrap(),
+            MkvmergeVersion(9, 8, 0)
+        );
+        assert!(matches!(
+            supported_version("mkvmerge v4.9.1 ('Ich will') built on Jul 12 2011"),
+            Err(SplitError::ProcessFailed(_, ref message)) if message.contains("too old")
+        ));
+        assert!(supported_version("").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::error::SplitError;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// Suffixes stripped from a script's name when the output is named after it, so that
+/// `Ep01 DELAY -50ms.avs` gives `Ep01.mka`.
+///
+/// These cover the delay eac3to writes into its file names, `track0` style track
+/// numbers set apart from the rest of the name (so `Soundtrack1` is left alone), and
+/// a trailing lowercase language code like `.jpn` or `[eng]`.
+pub const DEFAULT_STRIP_SUFFIXES: &[&str] = &[
+    r"(?i)\s*DELAY\s*[+-]?\d+(?:\.\d+)?\s*ms",
+    r"(?i)(?:[\s._-]+|\b)[\[(]?track\s*\d+[\])]?",
+    r"[\s._-]+[\[(]?(?:und|eng|en|jpn|ja|ger|deu|de|fre|fra|fr|spa|es|ita|it|chi|zho|zh|kor|ko|rus|ru|por|pt)[\])]?",
+];
+
+/// Compiles a suffix pattern, anchored so that it only matches at the end of a name
+pub fn compile_strip_suffix(pattern: &str) -> Result<Regex, SplitError> {
+    Regex::new(&format!("(?:{})$", pattern)).map_err(|e| {
+        SplitError::InvalidArgument(format!("Invalid suffix pattern {}: {}", pattern, e))
+    })
+}
+
+pub fn default_strip_suffixes() -> Vec<Regex> {
+    DEFAULT_STRIP_SUFFIXES
+        .iter()
+        .map(|pattern| compile_strip_suffix(pattern).unwrap())
+        .collect()
+}
+
+/// Names the output after `source` with an `.mka` extension, after stripping any of
+/// `suffixes` from the end of its name.
+///
+/// Suffixes are stripped until none match, in any order, so `Ep01 track1 DELAY 0ms`
+/// loses both. A name that would be stripped to nothing is kept as it is.
+pub fn derive_output_path(source: &Path, suffixes: &[Regex]) -> PathBuf {
+    let stem = match source.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) => stem,
+        None => return source.with_extension("mka"),
+    };
+    let mut name = stem;
+    while let Some(found) = suffixes
+        .iter()
+        .filter_map(|suffix| suffix.find(name))
+        .find(|found| found.start() > 0 && !found.as_str().is_empty())
+    {
+        name = name[..found.start()].trim_end();
+    }
+    if name.is_empty() {
+        name = stem;
+    }
+    source.with_file_name(format!("{}.mka", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derive(name: &str) -> PathBuf {
+        derive_output_path(Path::new(name), &default_strip_suffixes())
+    }
+
+    #[test]
+    fn strips_delay_track_and_language() {
+        assert_eq!(derive("Ep01 DELAY -50ms.avs"), PathBuf::from("Ep01.mka"));
+        assert_eq!(
+            derive("Ep01 track1 DELAY 0ms.avs"),
+            PathBuf::from("Ep01.mka")
+        );
+        assert_eq!(derive("Ep01_[track2].jpn.avs"), PathBuf::from("Ep01.mka"));
+        assert_eq!(derive("Ep01(track 3).avs"), PathBuf::from("Ep01.mka"));
+    }
+
+    #[test]
+    fn keeps_track_inside_a_word() {
+        assert_eq!(derive("Soundtrack1.avs"), PathBuf::from("Soundtrack1.mka"));
+        assert_eq!(
+            derive("Soundtrack 2.avs"),
+            PathBuf::from("Soundtrack 2.mka")
+        );
+    }
+
+    #[test]
+    fn keeps_a_name_that_would_be_stripped_to_nothing() {
+        assert_eq!(derive("track1.avs"), PathBuf::from("track1.mka"));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::error::SplitError;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Extensions treated as audio when pairing, compared case-insensitively
+pub const AUDIO_EXTENSIONS: &[&str] = &[
+    "aac", "ac3", "dts", "eac3", "flac", "m4a", "mka", "mp3", "ogg", "opus", "thd", "w64", "wav",
+];
+
+/// Extensions treated as scripts when pairing, compared case-insensitively
+pub const SCRIPT_EXTENSIONS: &[&str] = &["avs", "vpy"];
+
+/// An audio file and the script that trims it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pair {
+    pub name: String,
+    pub audio: PathBuf,
+    pub script: PathBuf,
+}
+
+#[derive(Debug, Default)]
+struct Candidates {
+    audio: Vec<PathBuf>,
+    scripts: Vec<PathBuf>,
+}
+
+/// Pairs up the audio files and scripts in `dir` that share a basename,
+/// so `Ep01.mka` goes with `Ep01.vpy`.
+///
+/// Basenames must match exactly, including case, while extensions are compared
+/// case-insensitively. Subdirectories are not searched. A basename with more than
+/// one audio file or script is ambiguous and is left unmatched.
+///
+/// Returns the pairs sorted by basename, and every file that could not be paired.
+pub fn find_pairs(dir: &Path) -> Result<(Vec<Pair>, Vec<PathBuf>), SplitError> {
+    let mut candidates: BTreeMap<String, Candidates> = BTreeMap::new();
+    let entries = dir
+        .read_dir()
+        .map_err(|e| SplitError::Io(dir.to_path_buf(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| SplitError::Io(dir.to_path_buf(), e))?
+            .path();
+        if !path.is_file() {
+            continue;
+        }
+        let (stem, extension) = match (path.file_stem(), path.extension()) {
+            (Some(stem), Some(extension)) => (
+                stem.to_string_lossy().into_owned(),
+                extension.to_string_lossy().to_ascii_lowercase(),
+            ),
+            _ => continue,
+        };
+        if AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+            candidates.entry(stem).or_default().audio.push(path);
+        } else if SCRIPT_EXTENSIONS.contains(&extension.as_str()) {
+            candidates.entry(stem).or_default().scripts.push(path);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let mut unmatched = Vec::new();
+    for (name, mut candidates) in candidates {
+        if candidates.audio.len() == 1 && candidates.scripts.len() == 1 {
+            pairs.push(Pair {
+                name,
+                audio: candidates.audio.remove(0),
+                script: candidates.scripts.remove(0),
+            });
+        } else {
+            unmatched.extend(candidates.audio);
+            unmatched.extend(candidates.scripts);
+        }
+    }
+    unmatched.sort();
+    Ok((pairs, unmatched))
+}
@@ -0,0 +1,154 @@
+use crate::error::SplitError;
+use crate::json::Json;
+use crate::timings::Timings;
+use std::fs;
+use std::path::Path;
+
+/// One trim as it was turned into cut times
+#[derive(Debug, Clone)]
+pub struct ReportTrim {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub start_time: String,
+    pub end_time: String,
+}
+
+/// One subprocess that was run, and how it exited
+#[derive(Debug, Clone)]
+pub struct ReportCommand {
+    pub args: Vec<String>,
+    pub status: Option<i32>,
+}
+
+/// What `--verify` measured, with frames counted at the video framerate
+#[derive(Debug, Clone)]
+pub struct ReportVerify {
+    pub expected_duration: f64,
+    pub output_duration: f64,
+    pub expected_frames: usize,
+    pub output_frames: usize,
+}
+
+/// A summary of everything a run decided and did, written out by `--report`
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub framerate: String,
+    pub framerate_source: String,
+    pub delay: isize,
+    pub delay_source: String,
+    pub edit_compensation: isize,
+    pub trims: Vec<ReportTrim>,
+    pub kept_segments: Vec<usize>,
+    pub discarded_segments: Vec<usize>,
+    /// How long the joined output should be, in seconds and in frames of the video,
+    /// when every piece it takes has a known end
+    pub output_duration: Option<f64>,
+    pub output_frames: Option<usize>,
+    pub commands: Vec<ReportCommand>,
+    pub verify: Option<ReportVerify>,
+    pub timings: Timings,
+    pub error: Option<String>,
+}
+
+impl Report {
+    pub fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("framerate", Json::string(self.framerate.as_str())),
+            (
+                "framerate_source",
+                Json::string(self.framerate_source.as_str()),
+            ),
+            ("delay", self.delay.into()),
+            ("delay_source", Json::string(self.delay_source.as_str())),
+            ("edit_compensation", self.edit_compensation.into()),
+            ("trims", self.trims_json()),
+            (
+                "segments",
+                Json::object(vec![
+                    ("kept", self.kept_segments.clone().into()),
+                    ("discarded", self.discarded_segments.clone().into()),
+                ]),
+            ),
+            (
+                "commands",
+                Json::Array(
+                    self.commands
+                        .iter()
+                        .map(|command| {
+                            Json::object(vec![
+                                ("args", command.args.clone().into()),
+                                ("exit_status", command.status.map(i64::from).into()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "verify",
+                self.verify.as_ref().map_or(Json::Null, |verify| {
+                    Json::object(vec![
+                        ("expected_duration", verify.expected_duration.into()),
+                        ("output_duration", verify.output_duration.into()),
+                        ("expected_frames", verify.expected_frames.into()),
+                        ("output_frames", verify.output_frames.into()),
+                    ])
+                }),
+            ),
+            (
+                "timings",
+                Json::Object(
+                    self.timings
+                        .phases
+                        .iter()
+                        .map(|&(phase, elapsed)| (phase.to_owned(), elapsed.as_secs_f64().into()))
+                        .collect(),
+                ),
+            ),
+            ("error", self.error.clone().into()),
+        ])
+    }
+
+    /// The trims as they were turned into cut times
+    pub fn trims_json(&self) -> Json {
+        Json::Array(
+            self.trims
+                .iter()
+                .map(|trim| {
+                    Json::object(vec![
+                        ("start_frame", trim.start.into()),
+                        ("end_frame", trim.end.into()),
+                        ("line", trim.line.into()),
+                        ("start_time", Json::string(trim.start_time.as_str())),
+                        ("end_time", Json::string(trim.end_time.as_str())),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    /// The result of a run as one object, for `--summary-json`
+    pub fn summary_json(&self, output: &Path, backend: &str) -> Json {
+        Json::object(vec![
+            ("success", self.error.is_none().into()),
+            ("output", output.to_string_lossy().into_owned().into()),
+            ("kept_segments", self.kept_segments.len().into()),
+            ("output_frames", self.output_frames.into()),
+            ("output_duration", self.output_duration.into()),
+            ("framerate", Json::string(self.framerate.as_str())),
+            (
+                "framerate_source",
+                Json::string(self.framerate_source.as_str()),
+            ),
+            ("delay", self.delay.into()),
+            ("delay_source", Json::string(self.delay_source.as_str())),
+            ("backend", Json::string(backend)),
+            ("error", self.error.clone().into()),
+        ])
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SplitError> {
+        fs::write(path, self.to_json().to_pretty_string() + "\n")
+            .map_err(|e| SplitError::Io(path.to_path_buf(), e))
+    }
+}
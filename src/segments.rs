@@ -0,0 +1,307 @@
+use crate::error::SplitError;
+
+/// Whether the first piece of the split is joined, for `--first-segment`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirstSegment {
+    /// Keep it when it lies inside a trim, like any other piece
+    Auto,
+    Keep,
+    Drop,
+}
+
+impl FirstSegment {
+    pub fn parse(value: &str) -> Result<FirstSegment, SplitError> {
+        match value {
+            "auto" => Ok(FirstSegment::Auto),
+            "keep" => Ok(FirstSegment::Keep),
+            "drop" => Ok(FirstSegment::Drop),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Unknown first segment setting {}, expected auto, keep or drop",
+                value
+            ))),
+        }
+    }
+}
+
+/// One piece of the audio as mkvmerge's split will produce it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// The number mkvmerge gives this piece, starting from 1
+    pub index: usize,
+    pub start: usize,
+    /// `None` when the piece runs to the end of the audio
+    pub end: Option<usize>,
+    pub keep: bool,
+}
+
+/// Where to cut the audio, and which of the resulting pieces to keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentPlan {
+    /// Frames to split at, ascending and without duplicates
+    pub cuts: Vec<usize>,
+    /// Frames that more than one range edge wanted to cut at, which were only cut once
+    pub duplicates: Vec<usize>,
+    pub segments: Vec<Segment>,
+}
+
+/// Works out the pieces produced by splitting at every edge of `ranges`.
+///
+/// Each range is `[start, end)` in frames. A piece is kept when it lies inside one of
+/// the ranges. mkvmerge does not produce an empty piece for a cut at the very start,
+/// so cuts at frame 0 (or at or past `total_frames`, when it is known) are left out.
+pub fn plan_segments(ranges: &[(usize, usize)], total_frames: Option<usize>) -> SegmentPlan {
+    let mut cuts: Vec<usize> = ranges
+        .iter()
+        .flat_map(|&(start, end)| vec![start, end])
+        .filter(|&frame| frame > 0 && total_frames.is_none_or(|total| frame < total))
+        .collect();
+    // mkvmerge needs its split points ascending and unique
+    cuts.sort_unstable();
+    let mut duplicates: Vec<usize> = cuts
+        .windows(2)
+        .filter(|w| w[0] == w[1])
+        .map(|w| w[0])
+        .collect();
+    duplicates.dedup();
+    cuts.dedup();
+
+    let mut starts = vec![0];
+    starts.extend(cuts.iter().cloned());
+    let segments = starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = cuts.get(i).cloned().or(total_frames);
+            let keep = end.is_some_and(|end| {
+                ranges
+                    .iter()
+                    .any(|&(range_start, range_end)| range_start <= start && end <= range_end)
+            });
+            Segment {
+                index: i + 1,
+                start,
+                end,
+                keep,
+            }
+        })
+        .collect();
+
+    SegmentPlan {
+        cuts,
+        duplicates,
+        segments,
+    }
+}
+
+/// Joins each range to the one after it when the gap between them is small enough,
+/// keeping the gap, so a dense edit doesn't leave slivers of dropped audio.
+///
+/// Only a range that comes later in the audio than the one before it is joined.
+/// `is_small` is given the end of one range and the start of the next.
+pub fn absorb_small_gaps<F>(ranges: &[(usize, usize)], is_small: F) -> Vec<(usize, usize)>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut absorbed: Vec<(usize, usize)> = Vec::new();
+    for &(start, end) in ranges {
+        match absorbed.last_mut() {
+            Some(last) if last.1 <= start && is_small(last.1, start) => last.1 = end,
+            _ => absorbed.push((start, end)),
+        }
+    }
+    absorbed
+}
+
+impl SegmentPlan {
+    /// Keeps the pieces between and around the trims instead of the trims themselves.
+    pub fn invert(&mut self) {
+        for segment in &mut self.segments {
+            segment.keep = !segment.keep;
+        }
+    }
+
+    /// The piece numbers to keep, in the order they appear in the audio.
+    pub fn kept(&self) -> Vec<usize> {
+        self.segments
+            .iter()
+            .filter(|segment| segment.keep)
+            .map(|segment| segment.index)
+            .collect()
+    }
+
+    /// Picks the piece numbers to join, in the order to join them.
+    ///
+    /// Explicit `keep_indices` replace the automatic choice entirely, and the pieces are
+    /// marked kept to match. Otherwise an `inverted` plan joins its kept pieces in the
+    /// order they appear in the audio, and any other follows the order of `ranges`.
+    pub fn select_pieces(
+        &mut self,
+        ranges: &[(usize, usize)],
+        inverted: bool,
+        keep_indices: Option<&[usize]>,
+    ) -> Result<Vec<usize>, SplitError> {
+        if let Some(indices) = keep_indices {
+            let count = self.segments.len();
+            if let Some(&index) = indices.iter().find(|&&index| index > count) {
+                return Err(SplitError::InvalidArgument(format!(
+                    "--keep-indices {} is out of range, the split produces {} pieces",
+                    index, count
+                )));
+            }
+            for segment in &mut self.segments {
+                segment.keep = indices.contains(&segment.index);
+            }
+            Ok(indices.to_vec())
+        } else if inverted {
+            Ok(self.kept())
+        } else {
+            Ok(self.merge_order(ranges))
+        }
+    }
+
+    /// Forces whether the first piece is joined, for `--first-segment`. A first piece
+    /// that wasn't going to be joined is put ahead of the rest, where it is in the audio.
+    pub fn force_first(&mut self, first: FirstSegment, merge_order: &mut Vec<usize>) {
+        let keep = match first {
+            FirstSegment::Auto => return,
+            FirstSegment::Keep => true,
+            FirstSegment::Drop => false,
+        };
+        // There is always a first piece, even with no cuts at all
+        self.segments[0].keep = keep;
+        if !keep {
+            merge_order.retain(|&index| index != 1);
+        } else if !merge_order.contains(&1) {
+            merge_order.insert(0, 1);
+        }
+    }
+
+    /// The piece numbers to join, following the order of `ranges`.
+    pub fn merge_order(&self, ranges: &[(usize, usize)]) -> Vec<usize> {
+        ranges
+            .iter()
+            .flat_map(|&(range_start, range_end)| {
+                self.segments
+                    .iter()
+                    .filter(move |segment| {
+                        segment.keep
+                            && range_start <= segment.start
+                            && segment.end.is_some_and(|end| end <= range_end)
+                    })
+                    .map(|segment| segment.index)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The pieces of `plan` as `(start, end, keep)`
+    fn pieces(plan: &SegmentPlan) -> Vec<(usize, Option<usize>, bool)> {
+        plan.segments
+            .iter()
+            .map(|segment| (segment.start, segment.end, segment.keep))
+            .collect()
+    }
+
+    #[test]
+    fn plans_a_piece_between_every_cut() {
+        let plan = plan_segments(&[(100, 200), (300, 400)], Some(500));
+        assert_eq!(plan.cuts, vec![100, 200, 300, 400]);
+        assert_eq!(
+            pieces(&plan),
+            vec![
+                (0, Some(100), false),
+                (100, Some(200), true),
+                (200, Some(300), false),
+                (300, Some(400), true),
+                (400, Some(500), false),
+            ]
+        );
+        assert_eq!(plan.kept(), vec![2, 4]);
+    }
+
+    #[test]
+    fn cuts_at_the_very_start_and_end_are_left_out() {
+        let plan = plan_segments(&[(0, 100), (300, 500)], Some(500));
+        assert_eq!(plan.cuts, vec![100, 300]);
+        assert_eq!(plan.kept(), vec![1, 3]);
+    }
+
+    #[test]
+    fn the_last_piece_is_only_kept_when_the_length_is_known() {
+        let plan = plan_segments(&[(100, 500)], None);
+        assert_eq!(
+            pieces(&plan),
+            vec![
+                (0, Some(100), false),
+                (100, Some(500), true),
+                (500, None, false)
+            ]
+        );
+        let plan = plan_segments(&[(100, 600)], Some(500));
+        assert_eq!(
+            pieces(&plan),
+            vec![(0, Some(100), false), (100, Some(500), true)]
+        );
+    }
+
+    #[test]
+    fn shared_edges_are_cut_once() {
+        let plan = plan_segments(&[(100, 200), (200, 300)], Some(500));
+        assert_eq!(plan.cuts, vec![100, 200, 300]);
+        assert_eq!(plan.duplicates, vec![200]);
+        assert_eq!(plan.kept(), vec![2, 3]);
+    }
+
+    #[test]
+    fn pieces_are_joined_in_the_order_of_the_ranges() {
+        let ranges = [(300, 400), (100, 200)];
+        let mut plan = plan_segments(&ranges, Some(500));
+        assert_eq!(
+            plan.select_pieces(&ranges, false, None).unwrap(),
+            vec![4, 2]
+        );
+        plan.invert();
+        assert_eq!(
+            plan.select_pieces(&ranges, true, None).unwrap(),
+            vec![1, 3, 5]
+        );
+    }
+
+    #[test]
+    fn explicit_indices_replace_the_automatic_choice() {
+        let ranges = [(100, 200)];
+        let mut plan = plan_segments(&ranges, Some(500));
+        assert_eq!(
+            plan.select_pieces(&ranges, false, Some(&[3, 1])).unwrap(),
+            vec![3, 1]
+        );
+        assert_eq!(plan.kept(), vec![1, 3]);
+        assert!(matches!(
+            plan.select_pieces(&ranges, false, Some(&[4])),
+            Err(SplitError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn the_first_piece_can_be_forced_either_way() {
+        let ranges = [(100, 200)];
+        let mut plan = plan_segments(&ranges, Some(500));
+        let mut order = plan.select_pieces(&ranges, false, None).unwrap();
+        plan.force_first(FirstSegment::Keep, &mut order);
+        assert_eq!(order, vec![1, 2]);
+        plan.force_first(FirstSegment::Drop, &mut order);
+        assert_eq!(order, vec![2]);
+        assert!(!plan.segments[0].keep);
+    }
+
+    #[test]
+    fn small_gaps_are_absorbed_into_the_range_before() {
+        let ranges = [(0, 100), (102, 200), (400, 500), (300, 350)];
+        let absorbed = absorb_small_gaps(&ranges, |end, start| start - end <= 5);
+        assert_eq!(absorbed, vec![(0, 200), (400, 500), (300, 350)]);
+    }
+}
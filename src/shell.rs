@@ -0,0 +1,107 @@
+/// Quotes `arg` so a shell reads it back as the same single argument.
+///
+/// Arguments made only of characters no shell treats specially are left alone.
+/// Anything else is wrapped in single quotes, with an embedded `'` written as `'\''`.
+#[cfg(not(windows))]
+pub fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=+,@%".contains(c));
+    if plain {
+        return arg.to_owned();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Quotes `arg` so `cmd.exe` and programs parsing their command line the usual way
+/// read it back as the same single argument.
+///
+/// Anything with spaces or quotes is wrapped in double quotes, with an embedded `"`
+/// and any backslashes before it escaped with backslashes.
+#[cfg(windows)]
+pub fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=+,@\\".contains(c));
+    if plain {
+        return arg.to_owned();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+/// Renders a program and its arguments as a line that can be pasted into a shell
+pub fn command_line(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_arguments_are_left_alone() {
+        assert_eq!(quote("--split"), "--split");
+        assert_eq!(
+            quote("timestamps:00:00:01.000,00:00:02.000"),
+            "timestamps:00:00:01.000,00:00:02.000"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn quotes_spaces_single_quotes_and_empty_arguments() {
+        assert_eq!(quote("Ep 01.mka"), "'Ep 01.mka'");
+        assert_eq!(quote("Tom's Ep01.mka"), r"'Tom'\''s Ep01.mka'");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn quotes_spaces_double_quotes_and_empty_arguments() {
+        assert_eq!(
+            quote(r"C:\My Audio\Ep 01.mka"),
+            r#""C:\My Audio\Ep 01.mka""#
+        );
+        assert_eq!(quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote(r"C:\dir with space\"), r#""C:\dir with space\\""#);
+        assert_eq!(quote(""), r#""""#);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn joins_a_command_line() {
+        let args = vec![
+            "mkvmerge".to_owned(),
+            "-o".to_owned(),
+            "My Show/Ep 01.mka".to_owned(),
+            "".to_owned(),
+        ];
+        assert_eq!(command_line(&args), "mkvmerge -o 'My Show/Ep 01.mka' ''");
+    }
+}
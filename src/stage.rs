@@ -0,0 +1,83 @@
+use crate::error::SplitError;
+
+/// How far through a run to go before stopping, for `--stop-after`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// Probe the input and work out the cut times, then print them
+    Probe,
+    /// Also split the audio into numbered pieces, and leave them in place
+    Split,
+    /// Also join the kept pieces and clean up, which is a full run
+    Merge,
+}
+
+impl Stage {
+    pub fn parse(value: &str) -> Result<Stage, SplitError> {
+        match value {
+            "probe" => Ok(Stage::Probe),
+            "split" => Ok(Stage::Split),
+            "merge" => Ok(Stage::Merge),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Unknown stage {}, expected probe, split or merge",
+                value
+            ))),
+        }
+    }
+}
+
+/// When the numbered split pieces are deleted, for `--cleanup`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cleanup {
+    /// Whether or not the run succeeded
+    Always,
+    /// Only once the output has been written, leaving them to look at after a failure
+    OnSuccess,
+    Never,
+}
+
+impl Cleanup {
+    pub fn parse(value: &str) -> Result<Cleanup, SplitError> {
+        match value {
+            "always" => Ok(Cleanup::Always),
+            "on-success" => Ok(Cleanup::OnSuccess),
+            "never" => Ok(Cleanup::Never),
+            _ => Err(SplitError::InvalidArgument(format!(
+                "Unknown cleanup mode {}, expected always, on-success or never",
+                value
+            ))),
+        }
+    }
+
+    /// Whether to delete the pieces of a run that did or didn't succeed
+    pub fn removes_pieces(self, succeeded: bool) -> bool {
+        match self {
+            Cleanup::Always => true,
+            Cleanup::OnSuccess => succeeded,
+            Cleanup::Never => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleanup_modes_follow_the_result() {
+        assert!(Cleanup::Always.removes_pieces(true));
+        assert!(Cleanup::Always.removes_pieces(false));
+        assert!(Cleanup::OnSuccess.removes_pieces(true));
+        assert!(!Cleanup::OnSuccess.removes_pieces(false));
+        assert!(!Cleanup::Never.removes_pieces(true));
+        assert!(!Cleanup::Never.removes_pieces(false));
+    }
+
+    #[test]
+    fn parses_cleanup_modes_and_stages() {
+        assert_eq!(Cleanup::parse("on-success").unwrap(), Cleanup::OnSuccess);
+        assert!(Cleanup::parse("sometimes").is_err());
+        assert_eq!(Stage::parse("split").unwrap(), Stage::Split);
+        assert!(Stage::Probe < Stage::Split && Stage::Split < Stage::Merge);
+        assert!(Stage::parse("join").is_err());
+    }
+}
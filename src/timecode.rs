@@ -0,0 +1,207 @@
+use crate::error::SplitError;
+use crate::framerate::Rational;
+use std::convert::TryFrom;
+
+/// When `frame` starts, in nanoseconds.
+///
+/// Works in integers so large frame numbers don't lose precision.
+pub fn frame_to_nanos(frame: usize, framerate: Rational) -> u128 {
+    frame as u128 * framerate.den as u128 * 1_000_000_000u128 / framerate.num as u128
+}
+
+/// Formats nanoseconds as `HH:MM:SS.nnnnnnnnn`, which mkvmerge and ffmpeg both accept.
+///
+/// Hours don't wrap at a day, and grow past two digits if they have to.
+pub fn format_nanos(nanos: u128) -> String {
+    let seconds = nanos / 1_000_000_000u128;
+    format!(
+        "{:02}:{:02}:{:02}.{:09}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        nanos % 1_000_000_000u128
+    )
+}
+
+/// The timecode `frame` starts at, as `HH:MM:SS.nnnnnnnnn`.
+///
+/// This is exactly what is passed to mkvmerge and ffmpeg: the start of the frame,
+/// rounded down to a whole nanosecond.
+pub fn frame_to_timecode(frame: usize, framerate: Rational) -> String {
+    format_nanos(frame_to_nanos(frame, framerate))
+}
+
+/// Counts `frame` as a non-drop-frame SMPTE timecode, `HH:MM:SS:FF`.
+///
+/// The frame field runs at the nominal rate, so at 30000/1001 a second of timecode
+/// is 30 frames and the timecode drifts slowly ahead of the real running time.
+pub fn frame_to_smpte(frame: usize, framerate: Rational) -> String {
+    let fps = nominal_fps(framerate) as usize;
+    let seconds = frame / fps;
+    format!(
+        "{:02}:{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        frame % fps
+    )
+}
+
+/// The whole number of frames a second of non-drop timecode counts at `framerate`
+fn nominal_fps(framerate: Rational) -> u128 {
+    ((framerate.num + framerate.den / 2) / framerate.den).max(1) as u128
+}
+
+/// The first frame that starts at or after the timecode `tc`, which is the inverse of
+/// [`frame_to_timecode`]: `timecode_to_frame(&frame_to_timecode(n, rate), rate)` is `n`.
+///
+/// Takes `HH:MM:SS.fff` or `MM:SS.fff`, with up to nine digits after the point.
+pub fn timecode_to_frame(tc: &str, framerate: Rational) -> Result<usize, SplitError> {
+    let nanos = parse_timecode(tc)
+        .ok_or_else(|| SplitError::InvalidArgument(format!("Invalid timecode: {}", tc)))?;
+    // Rounding up undoes the rounding down in frame_to_nanos
+    let scale = framerate.den as u128 * 1_000_000_000u128;
+    let frame = (nanos * framerate.num as u128).div_ceil(scale);
+    usize::try_from(frame)
+        .map_err(|_| SplitError::InvalidArgument(format!("Timecode is out of range: {}", tc)))
+}
+
+/// Parses a decimal number of seconds into nanoseconds, without going through a float
+pub fn parse_seconds(value: &str) -> Option<u128> {
+    let (whole, fraction) = match value.find('.') {
+        Some(pos) => (&value[..pos], &value[pos + 1..]),
+        None => (value, ""),
+    };
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if !all_digits(whole) || !all_digits(fraction) || fraction.len() > 9 {
+        return None;
+    }
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<u128>().ok()?
+    };
+    let fraction = format!("{:0<9}", fraction).parse::<u128>().ok()?;
+    whole.checked_mul(1_000_000_000)?.checked_add(fraction)
+}
+
+/// Parses `HH:MM:SS.fff` or `MM:SS.fff` into nanoseconds
+pub fn parse_timecode(value: &str) -> Option<u128> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    let (hours, minutes, seconds) = match parts.len() {
+        2 => ("0", parts[0], parts[1]),
+        3 => (parts[0], parts[1], parts[2]),
+        _ => return None,
+    };
+    let all_digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    if !all_digits(hours) || !all_digits(minutes) {
+        return None;
+    }
+    let hours = hours.parse::<u128>().ok()?;
+    let minutes = minutes.parse::<u128>().ok()?;
+    let seconds = parse_seconds(seconds)?;
+    if minutes >= 60 || seconds >= 60 * 1_000_000_000 {
+        return None;
+    }
+    (hours * 60 + minutes)
+        .checked_mul(60 * 1_000_000_000)?
+        .checked_add(seconds)
+}
+
+/// Parses an SMPTE `HH:MM:SS:FF` timecode into nanoseconds. Like [`frame_to_smpte`]
+/// writes them, the timecode counts frames at the nominal rate, so at 24000/1001 a
+/// second of timecode is 24 frames, and it is the start of that frame at `framerate`
+/// that is returned. Drop-frame timecodes aren't supported.
+pub fn parse_smpte_timecode(value: &str, framerate: Rational) -> Option<u128> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() != 4
+        || !parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+    let hours = parts[0].parse::<u128>().ok()?;
+    let minutes = parts[1].parse::<u128>().ok()?;
+    let seconds = parts[2].parse::<u128>().ok()?;
+    let frames = parts[3].parse::<u128>().ok()?;
+    // A frame field can't reach the next second
+    let fps = nominal_fps(framerate);
+    if minutes >= 60 || seconds >= 60 || frames >= fps {
+        return None;
+    }
+    let frame = ((hours * 60 + minutes) * 60 + seconds)
+        .checked_mul(fps)?
+        .checked_add(frames)?;
+    Some(frame_to_nanos(usize::try_from(frame).ok()?, framerate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILM: Rational = Rational {
+        num: 24000,
+        den: 1001,
+    };
+    const PAL: Rational = Rational { num: 25, den: 1 };
+
+    #[test]
+    fn timecodes_round_trip_to_the_same_frame() {
+        for &rate in &[FILM, PAL, Rational::NTSC] {
+            for &frame in &[0, 1, 23, 24, 1439, 1440, 86_399, 1_000_000] {
+                let timecode = frame_to_timecode(frame, rate);
+                assert_eq!(timecode_to_frame(&timecode, rate).unwrap(), frame);
+            }
+        }
+    }
+
+    #[test]
+    fn formats_frame_starts_rounded_down() {
+        assert_eq!(frame_to_timecode(0, FILM), "00:00:00.000000000");
+        assert_eq!(frame_to_timecode(1, FILM), "00:00:00.041708333");
+        assert_eq!(frame_to_timecode(90_000, PAL), "01:00:00.000000000");
+    }
+
+    #[test]
+    fn smpte_timecodes_round_trip_at_the_nominal_rate() {
+        assert_eq!(frame_to_smpte(24, FILM), "00:00:01:00");
+        assert_eq!(frame_to_smpte(86_400, FILM), "01:00:00:00");
+        for &rate in &[FILM, PAL, Rational::NTSC] {
+            for &frame in &[0, 1, 23, 24, 1439, 1440, 86_399, 1_000_000] {
+                let smpte = frame_to_smpte(frame, rate);
+                assert_eq!(
+                    parse_smpte_timecode(&smpte, rate),
+                    Some(frame_to_nanos(frame, rate))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_smpte_frame_fields_past_the_second() {
+        assert_eq!(parse_smpte_timecode("00:00:01:24", FILM), None);
+        assert_eq!(
+            parse_smpte_timecode("00:00:01:23", FILM),
+            Some(frame_to_nanos(47, FILM))
+        );
+        assert_eq!(parse_smpte_timecode("00:60:00:00", FILM), None);
+        assert_eq!(parse_smpte_timecode("00:00:00", FILM), None);
+    }
+
+    #[test]
+    fn parses_timecodes_and_seconds() {
+        assert_eq!(parse_timecode("01:02:03.5"), Some(3_723_500_000_000));
+        assert_eq!(parse_timecode("02:03.000000001"), Some(123_000_000_001));
+        assert_eq!(parse_timecode("00:60:00"), None);
+        assert_eq!(parse_timecode("1:2:3:4"), None);
+        assert_eq!(parse_seconds(".5"), Some(500_000_000));
+        assert_eq!(parse_seconds("12"), Some(12_000_000_000));
+        assert_eq!(parse_seconds("."), None);
+        assert_eq!(parse_seconds("1.0000000001"), None);
+        assert_eq!(parse_seconds("-1"), None);
+    }
+}
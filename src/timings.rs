@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+/// Wall-clock time spent in each phase of a run, for `--timings`
+#[derive(Debug, Clone, Default)]
+pub struct Timings {
+    /// Phases in the order they first ran, with the time spent in each
+    pub phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// Adds `elapsed` to the time spent in `phase`.
+    pub fn add(&mut self, phase: &'static str, elapsed: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+
+    pub fn print(&self) {
+        for &(phase, elapsed) in &self.phases {
+            eprintln!("{:>8}: {:.3}s", phase, elapsed.as_secs_f64());
+        }
+    }
+}
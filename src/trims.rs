@@ -0,0 +1,1355 @@
+use crate::error::{warn, SplitError};
+use regex::{Captures, Regex};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// A single trimmed range of frames found in a script.
+///
+/// `end` is where the range stops, one past the last frame kept, like a Python slice.
+/// AviSynth's inclusive `Trim(start, end)` is read as `end + 1`, so every way of
+/// writing the same frames gives the same range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trim {
+    pub start: usize,
+    pub end: usize,
+    /// Line the trim was found on, starting from 1
+    pub line: usize,
+    /// The text that was matched in the script
+    pub text: String,
+    /// AviSynth's `Trim(start, 0)`, which runs to the end of the clip.
+    /// `end` stays 0 until the clip's length is known, and is then the clip's length.
+    pub to_end: bool,
+    /// A negative Python slice start, like `clip[-500:]`. `start` counts back
+    /// from the end of the clip until its length is known.
+    pub start_from_end: bool,
+    /// A negative Python slice end, like `clip[:-100]`. `end` counts back
+    /// from the end of the clip until its length is known.
+    pub end_from_end: bool,
+}
+
+impl Trim {
+    /// Whether the clip's length is needed to know where this trim is
+    pub fn needs_length(&self) -> bool {
+        self.to_end || self.start_from_end || self.end_from_end
+    }
+
+    /// Works out the frames that were counted from the end of the clip.
+    pub fn resolve_length(&mut self, total_frames: usize) -> Result<(), SplitError> {
+        if !self.needs_length() {
+            return Ok(());
+        }
+        // Like Python, a start further back than the clip is long clamps to the first frame
+        if self.start_from_end {
+            self.start = total_frames.saturating_sub(self.start);
+        }
+        let (line, text, end_from_end) = (self.line, self.text.clone(), self.end_from_end);
+        let end_before_start = || SplitError::InvalidTrim {
+            line,
+            text: text.clone(),
+            message: format!(
+                "slice end before start after resolving negative index in a clip of {} frames",
+                total_frames
+            ),
+        };
+        if self.to_end {
+            self.end = total_frames;
+        } else if self.end_from_end {
+            // An end further back than the clip is long leaves nothing at all, rather
+            // than clamping to the first frame like the start does
+            self.end = total_frames
+                .checked_sub(self.end)
+                .ok_or_else(end_before_start)?;
+        }
+        if end_from_end && self.start > self.end {
+            return Err(end_before_start());
+        }
+        self.start_from_end = false;
+        self.end_from_end = false;
+        self.to_end = false;
+        if self.start > self.end {
+            return Err(SplitError::InvalidTrim {
+                line: self.line,
+                text: self.text.clone(),
+                message: format!(
+                    "start frame {} is after end frame {} in a clip of {} frames",
+                    self.start, self.end, total_frames
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Moves the trim `offset` frames earlier, for audio that starts that far into the
+    /// script's clip. Frames counted from the end stay where they are.
+    ///
+    /// Returns whether the trim started before the audio and was clamped to its start.
+    pub fn shift_back(&mut self, offset: usize) -> bool {
+        let mut clamped = false;
+        if !self.start_from_end {
+            clamped |= self.start < offset;
+            self.start = self.start.saturating_sub(offset);
+        }
+        if !self.to_end && !self.end_from_end {
+            self.end = self.end.saturating_sub(offset);
+        }
+        clamped
+    }
+}
+
+fn line_of(script: &str, offset: usize) -> usize {
+    script[..offset].matches('\n').count() + 1
+}
+
+fn parse_frame(captures: &Captures, group: usize, script: &str) -> Result<usize, SplitError> {
+    let whole = captures.get(0).unwrap();
+    // Allow Python style digit grouping, like 1_000_000. Numbers are always decimal,
+    // so a zero-padded 0100 is frame 100 and not an octal 64.
+    captures[group]
+        .trim_start_matches('-')
+        .replace('_', "")
+        .parse::<usize>()
+        .map_err(|_| SplitError::InvalidTrim {
+            line: line_of(script, whole.start()),
+            text: collapse_lines(whole.as_str()),
+            message: format!("frame number {} is out of range", &captures[group]),
+        })
+}
+
+/// Reads a positional `Trim(start, end)`, where an end of 0 runs to the end of the clip.
+///
+/// The end is the last frame kept, or with `end_is_count` the number of frames kept.
+fn trim_from_captures(
+    captures: &Captures,
+    script: &str,
+    end_is_count: bool,
+) -> Result<Trim, SplitError> {
+    let whole = captures.get(0).unwrap();
+    let start = parse_frame(captures, 1, script)?;
+    let end = parse_frame(captures, 2, script)?;
+    let line = line_of(script, whole.start());
+    let text = collapse_lines(whole.as_str());
+    let invalid = |message: String| SplitError::InvalidTrim {
+        line,
+        text: text.clone(),
+        message,
+    };
+    let cut = match end {
+        0 => 0,
+        count if end_is_count => start.checked_add(count).ok_or_else(|| {
+            invalid("the frame count runs past the largest frame number".to_owned())
+        })?,
+        last if start > last => {
+            return Err(invalid(format!(
+                "start frame {} is after end frame {}",
+                start, last
+            )))
+        }
+        last => last + 1,
+    };
+    Ok(Trim {
+        start,
+        end: cut,
+        line,
+        text,
+        to_end: end == 0,
+        start_from_end: false,
+        end_from_end: false,
+    })
+}
+
+/// Reads a `Trim` call with keyword arguments, like VapourSynth's `std.Trim`, where
+/// `first` defaults to 0 and `last` (the last frame kept, AviSynth's `end`) or `length`
+/// gives the end, running to the end of the clip without either. Frames may also be given
+/// positionally after the clip.
+///
+/// Returns `None` when a frame isn't a plain number, which is warned about later.
+fn keyword_trim(captures: &Captures, script: &str) -> Result<Option<Trim>, SplitError> {
+    let whole = captures.get(0).unwrap();
+    let line = line_of(script, whole.start());
+    let text = collapse_lines(whole.as_str());
+    let invalid = |message: String| SplitError::InvalidTrim {
+        line,
+        text: text.clone(),
+        message,
+    };
+    let frame = |value: &str| {
+        let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\\');
+        if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit() || c == '_') {
+            return None;
+        }
+        value.replace('_', "").parse::<usize>().ok()
+    };
+
+    let (mut first, mut last, mut length) = (None, None, None);
+    let mut positional = Vec::new();
+    for (i, arg) in captures[2].split(',').enumerate() {
+        let arg = arg.trim_matches(|c: char| c.is_whitespace() || c == '\\');
+        match arg.split_once('=') {
+            Some((key, value)) => {
+                let slot = match key.trim() {
+                    "first" => &mut first,
+                    // AviSynth calls the last frame `end`
+                    "last" | "end" => &mut last,
+                    "length" => &mut length,
+                    // The clip, given by name
+                    _ => continue,
+                };
+                match frame(value) {
+                    Some(value) => *slot = Some(value),
+                    None => return Ok(None),
+                }
+            }
+            // The clip, like `src` in `core.std.Trim(src, first=10)`
+            None if i == 0 && frame(arg).is_none() => (),
+            None => match frame(arg) {
+                Some(value) => positional.push(value),
+                None => return Ok(None),
+            },
+        }
+    }
+    let start = first.or_else(|| positional.first().cloned()).unwrap_or(0);
+    let last = last.or_else(|| positional.get(1).cloned());
+    let end =
+        match (last, length) {
+            (Some(_), Some(_)) => {
+                return Err(invalid("give either last or length, not both".to_owned()))
+            }
+            (Some(last), None) if start > last => {
+                return Err(invalid(format!(
+                    "start frame {} is after end frame {}",
+                    start, last
+                )))
+            }
+            (Some(last), None) => Some(last + 1),
+            (None, Some(0)) => return Err(invalid("length must be at least 1".to_owned())),
+            (None, Some(length)) => Some(start.checked_add(length).ok_or_else(|| {
+                invalid("the length runs past the largest frame number".to_owned())
+            })?),
+            (None, None) => None,
+        };
+    Ok(Some(Trim {
+        start,
+        end: end.unwrap_or(0),
+        line,
+        text,
+        to_end: end.is_none(),
+        start_from_end: false,
+        end_from_end: false,
+    }))
+}
+
+/// Reads a Python slice, where either side may be left out or negative
+fn slice_from_captures(captures: &Captures, script: &str) -> Result<Trim, SplitError> {
+    let whole = captures.get(0).unwrap();
+    let negative = |group: usize| {
+        captures
+            .get(group)
+            .is_some_and(|m| m.as_str().starts_with('-'))
+    };
+    let start = match captures.get(1) {
+        Some(_) => parse_frame(captures, 1, script)?,
+        None => 0,
+    };
+    // The end is exclusive already, and a negative one counts back from the end
+    let end = match captures.get(2) {
+        Some(_) => parse_frame(captures, 2, script)?,
+        None => 0,
+    };
+    Ok(Trim {
+        start,
+        end,
+        line: line_of(script, whole.start()),
+        text: collapse_lines(whole.as_str()),
+        to_end: captures.get(2).is_none(),
+        start_from_end: negative(1),
+        end_from_end: negative(2),
+    })
+}
+
+/// Puts a trim that was spread over several lines back on one, for messages
+fn collapse_lines(text: &str) -> String {
+    if !text.contains('\n') {
+        return text.to_owned();
+    }
+    text.lines()
+        .map(|line| line.trim().trim_matches('\\').trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Compiles a user supplied trim regex, which must capture the start and end frames.
+pub fn compile_trim_regex(pattern: &str) -> Result<Regex, SplitError> {
+    let regex = Regex::new(pattern).map_err(|e| {
+        SplitError::InvalidArgument(format!("Invalid trim regex {}: {}", pattern, e))
+    })?;
+    // captures_len includes the implicit group for the whole match
+    if regex.captures_len() != 3 {
+        return Err(SplitError::InvalidArgument(format!(
+            "Trim regex {} must have exactly two capture groups (start and end frame), found {}",
+            pattern,
+            regex.captures_len() - 1
+        )));
+    }
+    Ok(regex)
+}
+
+/// Splits a call's arguments at the commas that aren't inside nested parentheses,
+/// giving each argument with where it starts in `args`.
+fn split_arguments(args: &str) -> Vec<(usize, &str)> {
+    let mut arguments = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                arguments.push((start, &args[start..i]));
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    arguments.push((start, &args[start..]));
+    arguments
+}
+
+/// Replaces names in trim arguments with the numbers they were set to earlier in the
+/// script, so `op_end = 1100` followed by `Trim(0, op_end)` reads as `Trim(0, 1100)`.
+///
+/// Only unindented `name = 123` assignments count, and only an argument that is just
+/// the name is replaced, using the last assignment before the call. A name set to
+/// anything other than a plain number is warned about and left alone. The first of
+/// three positional arguments, or of one next to keywords, is the clip and is never
+/// replaced. Replacements never add lines, so trims keep their line numbers.
+fn substitute_constants(script: &str, warn_as_error: bool) -> Result<String, SplitError> {
+    static CONSTANT_REGEX: OnceLock<Regex> = OnceLock::new();
+    let constant_regex = CONSTANT_REGEX.get_or_init(|| {
+        Regex::new(r"(?m)^([A-Za-z_]\w*)[ \t]*=[ \t]*([^#\r\n]*?)[ \t]*(?:#[^\r\n]*)?\r?$").unwrap()
+    });
+    static CALL_REGEX: OnceLock<Regex> = OnceLock::new();
+    let call_regex =
+        CALL_REGEX.get_or_init(|| Regex::new(r"[tT]rim\(((?:[^()]|\([^()]*\))*)\)").unwrap());
+    static NAME_REGEX: OnceLock<Regex> = OnceLock::new();
+    let name_regex = NAME_REGEX.get_or_init(|| Regex::new(r"^[A-Za-z_]\w*$").unwrap());
+    static NUMBER_REGEX: OnceLock<Regex> = OnceLock::new();
+    let number_regex = NUMBER_REGEX.get_or_init(|| Regex::new(r"^\d[\d_]*$").unwrap());
+
+    // Each assignment is kept with where it is, as the value in effect at a call
+    // is the last one before it
+    let assignments: Vec<(usize, &str, &str)> = constant_regex
+        .captures_iter(script)
+        .filter(|captures| !captures[2].starts_with('='))
+        .map(|captures| {
+            (
+                captures.get(0).unwrap().start(),
+                captures.get(1).unwrap().as_str(),
+                captures.get(2).unwrap().as_str(),
+            )
+        })
+        .collect();
+    if assignments.is_empty() {
+        return Ok(script.to_owned());
+    }
+
+    let mut replacements = Vec::new();
+    let mut warned = HashSet::new();
+    for captures in call_regex.captures_iter(script) {
+        let args = captures.get(1).unwrap();
+        let arguments = split_arguments(args.as_str());
+        let positional = arguments
+            .iter()
+            .filter(|(_, argument)| !argument.contains('='))
+            .count();
+        for (i, &(offset, argument)) in arguments.iter().enumerate() {
+            let (offset, value) = match argument.split_once('=') {
+                Some((keyword, value)) => (offset + keyword.len() + 1, value),
+                None if i == 0 && positional % 2 == 1 => continue,
+                None => (offset, argument),
+            };
+            let trimmed = value.trim_start_matches(|c: char| c.is_whitespace() || c == '\\');
+            let start = args.start() + offset + value.len() - trimmed.len();
+            let name = trimmed.trim_end_matches(|c: char| c.is_whitespace() || c == '\\');
+            if !name_regex.is_match(name) {
+                continue;
+            }
+            let assigned = assignments
+                .iter()
+                .rev()
+                .find(|&&(at, assigned, _)| at < args.start() && assigned == name);
+            match assigned {
+                Some(&(_, _, number)) if number_regex.is_match(number) => {
+                    replacements.push((start, start + name.len(), number));
+                }
+                Some(&(at, _, value)) if warned.insert(at) => warn(
+                    warn_as_error,
+                    format!(
+                        "{} on line {} is set to `{}`, only plain frame numbers can be used \
+                         in trims",
+                        name,
+                        line_of(script, at),
+                        value
+                    ),
+                )?,
+                _ => (),
+            }
+        }
+    }
+
+    let mut substituted = String::with_capacity(script.len());
+    let mut copied = 0;
+    for (start, end, number) in replacements {
+        substituted.push_str(&script[copied..start]);
+        substituted.push_str(number);
+        copied = end;
+    }
+    substituted.push_str(&script[copied..]);
+    Ok(substituted)
+}
+
+/// Finds every trim in the script, in the order they appear.
+///
+/// AviSynth style `Trim(start, end)` calls and Python `clip[start:end]` slices are
+/// both picked up, so a script mixing the two keeps all of its cuts. Frame numbers
+/// may also be names set to a number earlier in the script, like `Trim(0, op_end)`.
+/// Trims in comments are ignored. If `custom_regex` is given it is used instead of
+/// the built-in patterns, and sees the comments too.
+///
+/// With `end_is_count`, the second value of every positional `Trim` call (or custom
+/// regex match) is a number of frames rather than the last frame, so `Trim(100, 50)` keeps frames
+/// 100 to 149, the same as `Trim(100, 149)`. A count of 0 still runs to the end. Python slices are unaffected.
+pub fn parse_trims(
+    script: &str,
+    custom_regex: Option<&Regex>,
+    end_is_count: bool,
+    warn_as_error: bool,
+) -> Result<Vec<Trim>, SplitError> {
+    // Each trim is kept with where it starts in the script, to put them in source order
+    let mut found = Vec::new();
+
+    if let Some(trim_regex) = custom_regex {
+        for captures in trim_regex.captures_iter(script) {
+            let trim = trim_from_captures(&captures, script, end_is_count)?;
+            found.push((captures.get(0).unwrap().start(), trim));
+        }
+    } else {
+        // A custom regex may be after something in the comments, so only the
+        // built-in patterns skip them
+        let script = &substitute_constants(&blank_comments(script), warn_as_error)?;
+        // This is not the best regex--it takes ALL TRIMS and includes them.
+        // The clip argument may be a name or a simple call like `src.Crop(8)`,
+        // but frame numbers have to be literals. Arguments may be spread over
+        // several lines, with or without AviSynth's `\` line continuations.
+        static TRIM_REGEX: OnceLock<Regex> = OnceLock::new();
+        let trim_regex = TRIM_REGEX.get_or_init(|| {
+            Regex::new(
+                r"[tT]rim\([\s\\]*(?:[\w.]+(?:\([^(),]*\))?[\s\\]*,[\s\\]*)?(\d[\d_]*)[\s\\]*,[\s\\]*(\d[\d_]*)[\s\\]*\)",
+            )
+            .unwrap()
+        });
+        for captures in trim_regex.captures_iter(script) {
+            let trim = trim_from_captures(&captures, script, end_is_count)?;
+            found.push((captures.get(0).unwrap().start(), trim));
+        }
+
+        // VapourSynth's std.Trim can take its frames as keywords, and is reached through
+        // whatever the core is called: `core.std.Trim(clip, first=10, last=20)`,
+        // `vs.core.std.Trim(...)`, `c.std.Trim(...)`, `clip.std.Trim(length=5)` or a
+        // bare `Trim(clip, first=10, last=20)`. AviSynth's `Trim(100, length=50)` and
+        // `Trim(100, end=200)` are read the same way
+        static KEYWORD_REGEX: OnceLock<Regex> = OnceLock::new();
+        let keyword_regex =
+            KEYWORD_REGEX.get_or_init(|| Regex::new(r"\b([tT]rim)\(([^()]*=[^()]*)\)").unwrap());
+        for captures in keyword_regex.captures_iter(script) {
+            let offset = captures.get(1).unwrap().start();
+            if found.iter().any(|&(found_at, _)| found_at == offset) {
+                continue;
+            }
+            // `last` and `length` already say what they are, whatever `end_is_count` says
+            if let Some(trim) = keyword_trim(&captures, script)? {
+                found.push((offset, trim));
+            }
+        }
+        warn_unreadable_trims(script, &found, warn_as_error)?;
+
+        // And for supporting python slice syntax, where the end is exclusive
+        // and either side may be negative to count back from the end
+        static SLICE_REGEX: OnceLock<Regex> = OnceLock::new();
+        let slice_regex = SLICE_REGEX.get_or_init(|| {
+            Regex::new(r"clip\[\s*(-?\d[\d_]*)?\s*:\s*(-?\d[\d_]*)?\s*\]").unwrap()
+        });
+        for captures in slice_regex.captures_iter(script) {
+            found.push((
+                captures.get(0).unwrap().start(),
+                slice_from_captures(&captures, script)?,
+            ));
+        }
+        check_computed_slices(script, found.is_empty(), warn_as_error)?;
+    }
+
+    found.sort_by_key(|&(offset, _)| offset);
+    let trims: Vec<Trim> = found.into_iter().map(|(_, trim)| trim).collect();
+
+    for trim in &trims {
+        if trim.start > trim.end && !trim.needs_length() {
+            return Err(SplitError::InvalidTrim {
+                line: trim.line,
+                text: trim.text.clone(),
+                message: format!("start frame {} is after end frame {}", trim.start, trim.end),
+            });
+        }
+    }
+
+    order_by_concatenation(script, trims, warn_as_error)
+}
+
+/// Writes `(start, end)` ranges as `Trim` calls joined with `++`, one to a line, which
+/// [`parse_trims`] reads back as the same ranges. Each end is one past the last frame,
+/// and is written as the last frame the way AviSynth counts it. An end of `None` runs
+/// to the end of the clip and is written as `Trim(start, 0)`.
+pub fn format_avs_trims(ranges: &[(usize, Option<usize>)]) -> String {
+    let calls: Vec<String> = ranges
+        .iter()
+        .map(|&(start, end)| match end {
+            // `Trim(0, 0)` would be the whole clip, so the first frame alone is a length
+            Some(1) => format!("Trim({}, length=1)", start),
+            Some(end) => format!("Trim({}, {})", start, end - 1),
+            None => format!("Trim({}, 0)", start),
+        })
+        .collect();
+    calls.join(" ++ \\\n") + "\n"
+}
+
+/// Reads the ranges listed in `# KEYWORD: 0-100, 200-300` comments, which take the
+/// place of the trims in the code when a preprocessor generates them from the comment.
+///
+/// Each range ends on its last frame like `Trim(start, end)`, and ranges are kept in the
+/// order they are listed, across every such comment. Returns `None` if there aren't any.
+pub fn parse_trims_comment(script: &str, keyword: &str) -> Result<Option<Vec<Trim>>, SplitError> {
+    static COMMENT_REGEX: OnceLock<Regex> = OnceLock::new();
+    let comment_regex = COMMENT_REGEX.get_or_init(|| Regex::new(r"^\s*#\s*([^:]*):(.*)$").unwrap());
+    let mut trims = None;
+    for (i, line) in script.lines().enumerate() {
+        let ranges = match comment_regex.captures(line) {
+            Some(captures) if &captures[1] == keyword => captures.get(2).unwrap().as_str(),
+            _ => continue,
+        };
+        let trims = trims.get_or_insert_with(Vec::new);
+        for range in ranges
+            .split(',')
+            .map(str::trim)
+            .filter(|range| !range.is_empty())
+        {
+            let invalid = |message: String| SplitError::InvalidTrim {
+                line: i + 1,
+                text: range.to_owned(),
+                message,
+            };
+            let frame = |value: &str| {
+                value
+                    .trim()
+                    .replace('_', "")
+                    .parse::<usize>()
+                    .map_err(|_| invalid(format!("invalid frame number {}", value.trim())))
+            };
+            let (start, end) = match range.split_once('-') {
+                Some((start, end)) => (frame(start)?, frame(end)?),
+                None => return Err(invalid("expected START-END".to_owned())),
+            };
+            if start > end {
+                return Err(invalid(format!(
+                    "start frame {} is after end frame {}",
+                    start, end
+                )));
+            }
+            trims.push(Trim {
+                start,
+                end: end + 1,
+                line: i + 1,
+                text: range.to_owned(),
+                to_end: false,
+                start_from_end: false,
+                end_from_end: false,
+            });
+        }
+    }
+    Ok(trims)
+}
+
+/// Whether the script attaches audio with `AudioDub`, outside of comments.
+///
+/// Trims then cut the audio at the video's framerate, which is only right if the
+/// audio was made to line up with that video.
+pub fn uses_audio_dub(script: &str) -> bool {
+    static AUDIO_DUB_REGEX: OnceLock<Regex> = OnceLock::new();
+    let audio_dub_regex =
+        AUDIO_DUB_REGEX.get_or_init(|| Regex::new(r"(?i)\baudiodub\s*\(").unwrap());
+    script
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(line))
+        .any(|line| audio_dub_regex.is_match(line))
+}
+
+/// Whether anything is left of the script once comments and blank lines are taken
+/// out, with comments as [`blank_comments`] finds them.
+pub fn has_statements(script: &str) -> bool {
+    blank_comments(script)
+        .lines()
+        .any(|line| !line.trim().trim_matches('\\').trim().is_empty())
+}
+
+/// Overwrites the comments in `script` with spaces, so that a commented out trim isn't
+/// read as a cut. Line breaks are kept, so anything found after still has its offset
+/// and line.
+///
+/// Comments are `#` to the end of the line, AviSynth's `/* */` and nesting `[* *]`
+/// block comments, and triple-quoted strings that start a line, which is how Python
+/// scripts write docstrings. A block comment that isn't closed is left alone, since
+/// `[*` also starts a Python list. Quoted strings are skipped over, so a `#` in a path
+/// doesn't start a comment.
+fn blank_comments(script: &str) -> String {
+    let bytes = script.as_bytes();
+    let find = |from: usize, needle: &[u8]| {
+        bytes[from..]
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .map(|position| from + position)
+    };
+    let starts_line = |at: usize| {
+        bytes[..at]
+            .iter()
+            .rev()
+            .take_while(|&&byte| byte != b'\n')
+            .all(u8::is_ascii_whitespace)
+    };
+    let mut blanked = bytes.to_vec();
+    let mut at = 0;
+    while at < bytes.len() {
+        let rest = &bytes[at..];
+        let comment_end = if rest[0] == b'#' {
+            Some(find(at, b"\n").unwrap_or(bytes.len()))
+        } else if rest.starts_with(b"/*") {
+            find(at + 2, b"*/").map(|end| end + 2)
+        } else if rest.starts_with(b"[*") {
+            nested_comment_end(bytes, at)
+        } else if rest.starts_with(b"\"\"\"") || rest.starts_with(b"'''") {
+            let end = find(at + 3, &rest[..3]).map_or(bytes.len(), |end| end + 3);
+            if !starts_line(at) {
+                at = end;
+                continue;
+            }
+            Some(end)
+        } else if rest[0] == b'"' || rest[0] == b'\'' {
+            // Strings end at their quote or the end of the line, whichever comes first
+            let quote = rest[0];
+            at = rest[1..]
+                .iter()
+                .position(|&byte| byte == quote || byte == b'\n')
+                .map_or(bytes.len(), |end| at + 1 + end + 1);
+            continue;
+        } else {
+            None
+        };
+        match comment_end {
+            Some(end) => {
+                for byte in &mut blanked[at..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                at = end;
+            }
+            None => at += 1,
+        }
+    }
+    // Only whole characters were overwritten, each byte with an ASCII space
+    String::from_utf8(blanked).unwrap()
+}
+
+/// Where the `[* *]` comment starting at `start` ends, with any comments nested in it
+fn nested_comment_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut at = start;
+    while at + 1 < bytes.len() {
+        match &bytes[at..at + 2] {
+            b"[*" => depth += 1,
+            b"*]" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(at + 2);
+                }
+            }
+            _ => {
+                at += 1;
+                continue;
+            }
+        }
+        at += 2;
+    }
+    None
+}
+
+/// Keeps only the lines of one edit in a script that holds several, for `--edit`.
+///
+/// Each edit starts at a `# EDIT: name` comment and runs until the next one.
+/// Lines before the first marker belong to no edit. Every other line is blanked
+/// rather than removed, so trims are still reported on their original line.
+pub fn select_edit(script: &str, name: &str) -> Result<String, SplitError> {
+    static EDIT_REGEX: OnceLock<Regex> = OnceLock::new();
+    let edit_regex = EDIT_REGEX.get_or_init(|| Regex::new(r"^\s*#\s*EDIT:\s*(\S+)\s*$").unwrap());
+
+    let mut found = Vec::new();
+    let mut in_edit = false;
+    let mut selected = String::with_capacity(script.len());
+    for line in script.split_inclusive('\n') {
+        if let Some(captures) = edit_regex.captures(line.trim_end()) {
+            in_edit = &captures[1] == name;
+            found.push(captures[1].to_owned());
+        }
+        if in_edit {
+            selected.push_str(line);
+        } else if line.ends_with('\n') {
+            selected.push('\n');
+        }
+    }
+
+    if !found.iter().any(|edit| edit == name) {
+        return Err(SplitError::InvalidArgument(if found.is_empty() {
+            format!(
+                "No edit named {}, the script has no `# EDIT:` markers",
+                name
+            )
+        } else {
+            format!(
+                "No edit named {}, expected one of: {}",
+                name,
+                found.join(", ")
+            )
+        }));
+    }
+    Ok(selected)
+}
+
+/// Warns about `Trim(` calls the trim regex couldn't read, like ones with computed
+/// frame numbers or a clip argument that has commas in it, rather than dropping
+/// them silently.
+fn warn_unreadable_trims(
+    script: &str,
+    found: &[(usize, Trim)],
+    warn_as_error: bool,
+) -> Result<(), SplitError> {
+    static CALL_REGEX: OnceLock<Regex> = OnceLock::new();
+    let call_regex = CALL_REGEX.get_or_init(|| Regex::new(r"[tT]rim\(").unwrap());
+    for call in call_regex.find_iter(script) {
+        if found.iter().any(|&(offset, _)| offset == call.start()) {
+            continue;
+        }
+        let rest = &script[call.start()..];
+        let text = rest.lines().next().unwrap_or(rest).trim_end();
+        warn(
+            warn_as_error,
+            format!(
+                "skipping `{}` on line {}, computed trim arguments aren't supported",
+                text,
+                line_of(script, call.start())
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// Looks for slices whose bounds aren't numbers, like `src[a:b]` in a list
+/// comprehension, which build the cuts while the script runs. When nothing else was
+/// found they are an error pointing at `--cuts-file` and `--trim`, rather than a
+/// puzzling "No trims found". Otherwise each is warned about and skipped.
+fn check_computed_slices(
+    script: &str,
+    nothing_found: bool,
+    warn_as_error: bool,
+) -> Result<(), SplitError> {
+    static COMPUTED_SLICE_REGEX: OnceLock<Regex> = OnceLock::new();
+    let computed_slice_regex = COMPUTED_SLICE_REGEX.get_or_init(|| {
+        Regex::new(r"\w+\[[ \t]*([^\[\]:\n]*?)[ \t]*:[ \t]*([^\[\]:\n]*?)[ \t]*\]").unwrap()
+    });
+    static NUMBER_REGEX: OnceLock<Regex> = OnceLock::new();
+    let number_regex = NUMBER_REGEX.get_or_init(|| Regex::new(r"^-?\d[\d_]*$").unwrap());
+    let bound_is_computed = |bound: &str| !bound.is_empty() && !number_regex.is_match(bound);
+
+    for captures in computed_slice_regex.captures_iter(script) {
+        let whole = captures.get(0).unwrap();
+        let line_start = script[..whole.start()].rfind('\n').map_or(0, |i| i + 1);
+        if script[line_start..whole.start()].contains('#')
+            || !(bound_is_computed(&captures[1]) || bound_is_computed(&captures[2]))
+        {
+            continue;
+        }
+        let line = line_of(script, whole.start());
+        if nothing_found {
+            return Err(SplitError::InvalidTrim {
+                line,
+                text: whole.as_str().to_owned(),
+                message: "the slice bounds are computed when the script runs, so the cuts \
+                          can't be read from it. Pass them with --cuts-file or --trim instead"
+                    .to_owned(),
+            });
+        }
+        warn(
+            warn_as_error,
+            format!(
+                "skipping `{}` on line {}, computed slice bounds aren't supported",
+                whole.as_str(),
+                line
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// Reorders trims that were assigned to variables to match the final concatenation,
+/// e.g. `a = src.Trim(200,300)`, `b = src.Trim(0,100)`, `b + a`.
+///
+/// This is best-effort: if the concatenation mentions anything that isn't a single
+/// trim assigned to a variable, the trims are left in source order with a warning.
+/// Trims assigned to variables that the concatenation does not use are dropped.
+fn order_by_concatenation(
+    script: &str,
+    trims: Vec<Trim>,
+    warn_as_error: bool,
+) -> Result<Vec<Trim>, SplitError> {
+    static ASSIGN_REGEX: OnceLock<Regex> = OnceLock::new();
+    let assign_regex = ASSIGN_REGEX.get_or_init(|| Regex::new(r"^\s*(\w+)\s*=").unwrap());
+
+    static CONCAT_REGEX: OnceLock<Regex> = OnceLock::new();
+    let concat_regex = CONCAT_REGEX.get_or_init(|| {
+        Regex::new(r"^\s*(?:return\s+|\w+\s*=\s*)?(\w+(?:\s*\+\+?\s*\w+)+)\s*$").unwrap()
+    });
+
+    let mut variables: HashMap<&str, usize> = HashMap::new();
+    let mut concatenation = None;
+    for (i, line) in script.lines().enumerate() {
+        if let Some(captures) = concat_regex.captures(line) {
+            concatenation = Some(captures.get(1).unwrap().as_str());
+            continue;
+        }
+        if let Some(captures) = assign_regex.captures(line) {
+            let on_line: Vec<usize> = (0..trims.len())
+                .filter(|&t| trims[t].line == i + 1)
+                .collect();
+            if on_line.len() == 1 {
+                variables.insert(captures.get(1).unwrap().as_str(), on_line[0]);
+            }
+        }
+    }
+
+    let concatenation = match concatenation {
+        Some(concatenation) if !variables.is_empty() => concatenation,
+        _ => return Ok(trims),
+    };
+
+    let mut order = Vec::new();
+    for name in concatenation
+        .split('+')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        match variables.get(name) {
+            Some(&index) => order.push(index),
+            None => {
+                warn(
+                    warn_as_error,
+                    format!(
+                        "could not match `{}` in `{}` to a trim, using trims in script order",
+                        name, concatenation
+                    ),
+                )?;
+                return Ok(trims);
+            }
+        }
+    }
+
+    let unassigned = (0..trims.len()).any(|t| !variables.values().any(|&v| v == t));
+    if unassigned {
+        warn(
+            warn_as_error,
+            format!(
+                "some trims are not assigned to a variable used in `{}`, using trims in \
+                 script order",
+                concatenation
+            ),
+        )?;
+        return Ok(trims);
+    }
+
+    Ok(order
+        .into_iter()
+        .map(|index| trims[index].clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(script: &str) -> Vec<Trim> {
+        parse_trims(script, None, false, false).unwrap()
+    }
+
+    /// The trims of `script` as `(start, end)`, once counted against a clip of
+    /// `total_frames`
+    fn resolved(script: &str, total_frames: usize) -> Vec<(usize, usize)> {
+        parse(script)
+            .into_iter()
+            .map(|mut trim| {
+                trim.resolve_length(total_frames).unwrap();
+                (trim.start, trim.end)
+            })
+            .collect()
+    }
+
+    /// The pieces planned for the trims of `script` in a clip of `total_frames`
+    fn pieces(script: &str, total_frames: usize) -> Vec<(usize, Option<usize>, bool)> {
+        crate::segments::plan_segments(&resolved(script, total_frames), Some(total_frames))
+            .segments
+            .iter()
+            .map(|segment| (segment.start, segment.end, segment.keep))
+            .collect()
+    }
+
+    #[test]
+    fn trim_with_an_end_of_zero_runs_to_the_end() {
+        let trims = parse("Trim(100, 0)");
+        assert_eq!(trims.len(), 1);
+        assert!(trims[0].to_end);
+        assert!(trims[0].needs_length());
+        assert_eq!(resolved("Trim(100, 0)", 500), vec![(100, 500)]);
+        assert_eq!(resolved("Trim(0, 0)", 500), vec![(0, 500)]);
+        // Both spellings keep the last frame
+        assert_eq!(pieces("Trim(100, 0)", 500), pieces("Trim(100, 499)", 500));
+        assert_eq!(
+            pieces("Trim(100, 499)", 500),
+            vec![(0, Some(100), false), (100, Some(500), true)]
+        );
+    }
+
+    #[test]
+    fn trim_ends_keep_their_last_frame() {
+        assert_eq!(resolved("Trim(100, 200)", 500), vec![(100, 201)]);
+    }
+
+    #[test]
+    fn trim_with_an_end_of_zero_still_runs_to_the_end_as_a_count() {
+        let trims = parse_trims("Trim(100, 0) ++ Trim(200, 50)", None, true, false).unwrap();
+        assert!(trims[0].to_end);
+        assert_eq!((trims[1].start, trims[1].end), (200, 250));
+        assert!(!trims[1].to_end);
+    }
+
+    #[test]
+    fn trim_to_the_end_of_a_clip_that_ends_before_it_is_an_error() {
+        let mut trim = parse("Trim(600, 0)").remove(0);
+        assert!(matches!(
+            trim.resolve_length(500),
+            Err(SplitError::InvalidTrim { .. })
+        ));
+    }
+
+    #[test]
+    fn slices_may_leave_out_either_side() {
+        assert_eq!(resolved("clip = clip[100:200]", 500), vec![(100, 200)]);
+        assert_eq!(resolved("clip = clip[:200]", 500), vec![(0, 200)]);
+        assert_eq!(resolved("clip = clip[100:]", 500), vec![(100, 500)]);
+        assert_eq!(
+            resolved("clip = clip[1_000:2_000]", 5000),
+            vec![(1000, 2000)]
+        );
+        // Like Python, a slice keeps `end - start` frames, and an end at the clip's
+        // length is the same as leaving it out
+        assert_eq!(
+            resolved("clip = clip[400:500]", 500),
+            resolved("clip = clip[400:]", 500)
+        );
+        assert_eq!(
+            resolved("clip = clip[-100:]", 500),
+            resolved("clip = clip[400:500]", 500)
+        );
+    }
+
+    #[test]
+    fn negative_slices_count_back_from_the_end() {
+        let trims = parse("clip = clip[-50:]");
+        assert!(trims[0].start_from_end);
+        assert_eq!(resolved("clip = clip[-50:]", 500), vec![(450, 500)]);
+        assert_eq!(resolved("clip = clip[100:-50]", 500), vec![(100, 450)]);
+        assert_eq!(resolved("clip = clip[-100:-50]", 500), vec![(400, 450)]);
+    }
+
+    #[test]
+    fn a_negative_slice_start_past_the_clip_clamps_to_its_start() {
+        assert_eq!(resolved("clip = clip[-800:]", 500), vec![(0, 500)]);
+    }
+
+    #[test]
+    fn a_negative_slice_end_before_its_start_is_an_error() {
+        let mut trim = parse("clip = clip[400:-200]").remove(0);
+        assert!(matches!(
+            trim.resolve_length(500),
+            Err(SplitError::InvalidTrim { .. })
+        ));
+        // An end further back than the clip is long leaves nothing
+        let mut trim = parse("clip = clip[:-600]").remove(0);
+        assert!(trim.resolve_length(500).is_err());
+        // It is fine in a longer clip
+        assert_eq!(resolved("clip = clip[400:-200]", 1000), vec![(400, 800)]);
+    }
+
+    #[test]
+    fn keyword_trims_are_read_through_any_core() {
+        let ranges = |script: &str| {
+            parse(script)
+                .into_iter()
+                .map(|trim| (trim.start, trim.end, trim.to_end))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ranges("clip = core.std.Trim(src, first=10, last=20)"),
+            vec![(10, 21, false)]
+        );
+        assert_eq!(
+            ranges("clip = c.std.Trim(src, first=10, length=5)"),
+            vec![(10, 15, false)]
+        );
+        assert_eq!(
+            ranges("clip = clip.std.Trim(last=20)"),
+            vec![(0, 21, false)]
+        );
+        assert_eq!(
+            ranges("clip = core.std.Trim(src, first=10)"),
+            vec![(10, 0, true)]
+        );
+        assert_eq!(
+            ranges("Trim(src, first=10, last=20)"),
+            vec![(10, 21, false)]
+        );
+        assert_eq!(ranges("Trim(100, end=200)"), vec![(100, 201, false)]);
+        assert_eq!(ranges("Trim(100, length=50)"), vec![(100, 150, false)]);
+    }
+
+    #[test]
+    fn keyword_trims_reject_both_last_and_length() {
+        assert!(matches!(
+            parse_trims(
+                "core.std.Trim(src, first=1, last=5, length=5)",
+                None,
+                false,
+                false
+            ),
+            Err(SplitError::InvalidTrim { .. })
+        ));
+        assert!(parse_trims("core.std.Trim(src, length=0)", None, false, false).is_err());
+    }
+
+    fn starts(script: &str) -> Vec<usize> {
+        parse(script).into_iter().map(|trim| trim.start).collect()
+    }
+
+    #[test]
+    fn commented_out_trims_are_ignored() {
+        assert_eq!(starts("# Trim(0, 10)\nTrim(20, 30)"), vec![20]);
+        assert_eq!(starts("Trim(20, 30) # ++ Trim(40, 50)"), vec![20]);
+        assert_eq!(
+            starts("/* Trim(0, 10)\n Trim(5, 6) */ Trim(20, 30)"),
+            vec![20]
+        );
+        assert_eq!(
+            starts("[* outer [* Trim(0, 10) *] Trim(5, 6) *] Trim(20, 30)"),
+            vec![20]
+        );
+        assert_eq!(
+            starts("\"\"\"\nclip[0:10]\n\"\"\"\nclip = clip[20:30]"),
+            vec![20]
+        );
+    }
+
+    #[test]
+    fn blanked_comments_keep_lines_and_strings() {
+        let trims = parse("# Trim(0, 10)\n/* two\nlines */\nTrim(20, 30)");
+        assert_eq!(trims[0].line, 4);
+        // A `#` inside a string doesn't start a comment
+        assert_eq!(starts("src = \"a#b.mkv\"\nTrim(20, 30)"), vec![20]);
+        // Nor is an unclosed `[*` one, since it also starts a Python list
+        assert_eq!(starts("x = [*range(3)]\nclip = clip[20:30]"), vec![20]);
+        // A triple-quoted string in the middle of a line is code
+        assert_eq!(blank_comments("s = '''# kept'''"), "s = '''# kept'''");
+    }
+
+    #[test]
+    fn a_script_of_only_comments_has_no_statements() {
+        assert!(!has_statements("# nothing\n/* here */\n  \\\n"));
+        assert!(has_statements("# a clip\nBlankClip()"));
+    }
+
+    #[test]
+    fn trims_follow_the_final_concatenation() {
+        let script = "a = src.Trim(200, 300)\nb = src.Trim(0, 100)\nb ++ a\n";
+        assert_eq!(starts(script), vec![0, 200]);
+        let script = "a = src.Trim(200, 300)\nb = src.Trim(0, 100)\nreturn b + a\n";
+        assert_eq!(starts(script), vec![0, 200]);
+    }
+
+    #[test]
+    fn trims_not_in_the_concatenation_are_dropped() {
+        let script = "a = src.Trim(0, 100)\nb = src.Trim(200, 300)\nc = src.Trim(400, 500)\n\
+                      out = c + a\n";
+        assert_eq!(starts(script), vec![400, 0]);
+    }
+
+    #[test]
+    fn an_unknown_name_in_the_concatenation_keeps_script_order() {
+        let script = "a = src.Trim(200, 300)\nb = src.Trim(0, 100)\nb ++ a ++ credits\n";
+        assert_eq!(starts(script), vec![200, 0]);
+        assert!(matches!(
+            parse_trims(script, None, false, true),
+            Err(SplitError::CheckFailed(_))
+        ));
+    }
+
+    #[test]
+    fn the_second_value_is_an_end_or_a_count() {
+        let ranges = |script: &str, end_is_count| {
+            parse_trims(script, None, end_is_count, false)
+                .unwrap()
+                .into_iter()
+                .map(|trim| (trim.start, trim.end))
+                .collect::<Vec<_>>()
+        };
+        let script = "Trim(100, 150) ++ Trim(300, 0)";
+        assert_eq!(ranges(script, false)[0], (100, 151));
+        assert_eq!(ranges(script, true)[0], (100, 250));
+        // A count of 0 still runs to the end either way
+        assert!(parse_trims(script, None, true, false).unwrap()[1].to_end);
+        // 50 frames from frame 100 are frames 100 to 149
+        assert_eq!(
+            ranges("Trim(100, 50)", true),
+            ranges("Trim(100, 149)", false)
+        );
+    }
+
+    #[test]
+    fn a_reversed_trim_is_an_error_on_its_line() {
+        let script = "src = BlankClip()\n\nsrc.Trim(0, 100) ++ \\\n\
+                      src.Trim(200, 300) ++ \\\n\
+                      src.Trim(500, 400)\n";
+        match parse_trims(script, None, false, false) {
+            Err(SplitError::InvalidTrim {
+                line: 5,
+                text,
+                message,
+            }) => {
+                assert_eq!(text, "Trim(500, 400)");
+                assert!(
+                    message.contains("500 is after end frame 400"),
+                    "{}",
+                    message
+                );
+            }
+            other => panic!("expected an invalid trim on line 5, got {:?}", other),
+        }
+        // Every way of writing a range is checked the same way
+        for script in &[
+            "Trim(101, 100)",
+            "core.std.Trim(src, first=20, last=10)",
+            "clip = clip[200:100]",
+        ] {
+            assert!(
+                matches!(
+                    parse_trims(script, None, false, false),
+                    Err(SplitError::InvalidTrim { line: 1, .. })
+                ),
+                "{}",
+                script
+            );
+        }
+        assert!(parse_trims_comment("# TRIMS: 0-10, 30-20", "TRIMS").is_err());
+        // A single frame isn't reversed
+        assert!(parse_trims("Trim(100, 100)", None, false, false).is_ok());
+    }
+
+    #[test]
+    fn trims_and_slices_are_collected_in_script_order() {
+        let script =
+            "a = src.Trim(0, 99)\nb = clip[200:300]\nc = src.Trim(400, 499) ++ clip[600:]\n";
+        let trims = parse(script);
+        let found: Vec<_> = trims
+            .iter()
+            .map(|trim| (trim.start, trim.end, trim.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(0, 100, 1), (200, 300, 2), (400, 500, 3), (600, 0, 3)]
+        );
+        assert!(trims[3].to_end);
+    }
+
+    #[test]
+    fn frame_numbers_may_have_underscores() {
+        assert_eq!(
+            resolved("Trim(0, 1_000_000)", 2_000_000),
+            vec![(0, 1_000_001)]
+        );
+        assert_eq!(
+            resolved("core.std.Trim(src, first=1_000, length=2_000)", 5000),
+            vec![(1000, 3000)]
+        );
+        // Leading zeros are still decimal
+        assert_eq!(resolved("Trim(0100, 0200)", 500), vec![(100, 201)]);
+    }
+
+    #[test]
+    fn the_clip_may_be_a_simple_call() {
+        assert_eq!(
+            resolved("Trim(src.ConvertToYV12(), 0, 99)", 500),
+            vec![(0, 100)]
+        );
+        assert_eq!(resolved("Trim(Crop(src), 100, 199)", 500), vec![(100, 200)]);
+        assert_eq!(resolved("Trim(last, 100, 199)", 500), vec![(100, 200)]);
+    }
+
+    #[test]
+    fn trims_with_computed_arguments_are_warned_about() {
+        for script in &[
+            "Trim(AudioDub(v, a), 100, 500)",
+            "Trim(0, op_end * 2)",
+            "Trim(FuncReturningFrame(a, b), 500)",
+        ] {
+            // Skipped with a warning, which --warn-as-error turns into an error
+            assert_eq!(parse(script), vec![], "{}", script);
+            assert!(
+                matches!(
+                    parse_trims(script, None, false, true),
+                    Err(SplitError::CheckFailed(ref message))
+                        if message.contains("computed trim arguments")
+                ),
+                "{}",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn trims_may_be_continued_over_several_lines() {
+        let script =
+            "src = BlankClip()\nsrc.Trim(100, \\\n    199) ++ src.Trim(\n  300,\n  399\n)\n";
+        let trims = parse(script);
+        let found: Vec<_> = trims
+            .iter()
+            .map(|trim| (trim.start, trim.end, trim.line, trim.text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (100, 200, 2, "Trim(100, 199)"),
+                (300, 400, 3, "Trim( 300, 399 )"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_the_ranges_in_a_trims_comment() {
+        let script = "# TRIMS: 0-100, 200-3_00\nsrc.Trim(0, 100) ++ src.Trim(200, 300)\n\
+                      #TRIMS: 400-500\n# CUTS: 600-700\n";
+        let trims = parse_trims_comment(script, "TRIMS").unwrap().unwrap();
+        let found: Vec<_> = trims
+            .iter()
+            .map(|trim| (trim.start, trim.end, trim.line))
+            .collect();
+        assert_eq!(found, vec![(0, 101, 1), (200, 301, 1), (400, 501, 3)]);
+        // The keyword can be changed, and has to match exactly
+        let trims = parse_trims_comment(script, "CUTS").unwrap().unwrap();
+        assert_eq!((trims[0].start, trims[0].end), (600, 701));
+        assert_eq!(parse_trims_comment(script, "TRIM").unwrap(), None);
+        assert_eq!(
+            parse_trims_comment("src.Trim(0, 100)", "TRIMS").unwrap(),
+            None
+        );
+        assert!(matches!(
+            parse_trims_comment("\n# TRIMS: 0-100, 200", "TRIMS"),
+            Err(SplitError::InvalidTrim { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn selects_one_edit_of_several() {
+        let script =
+            "src = BlankClip()\n# EDIT: tv\nsrc.Trim(0, 99)\n# EDIT: dc\nsrc.Trim(0, 199)\n";
+        let tv = select_edit(script, "tv").unwrap();
+        assert_eq!(starts(&tv), vec![0]);
+        assert_eq!(parse(&tv)[0].end, 100);
+        let dc = parse(&select_edit(script, "dc").unwrap());
+        // Lines outside the edit are blanked, so trims keep their line
+        assert_eq!((dc.len(), dc[0].end, dc[0].line), (1, 200, 5));
+    }
+
+    #[test]
+    fn an_unknown_edit_lists_the_ones_there_are() {
+        let script = "# EDIT: tv\nsrc.Trim(0, 99)\n# EDIT: dc\nsrc.Trim(0, 199)\n";
+        match select_edit(script, "uncut") {
+            Err(SplitError::InvalidArgument(message)) => {
+                assert!(message.ends_with("expected one of: tv, dc"), "{}", message)
+            }
+            other => panic!("expected an invalid argument, got {:?}", other),
+        }
+        assert!(matches!(
+            select_edit("src.Trim(0, 99)\n", "tv"),
+            Err(SplitError::InvalidArgument(ref message)) if message.contains("no `# EDIT:`")
+        ));
+    }
+
+    #[test]
+    fn finds_audio_dub_outside_comments() {
+        assert!(uses_audio_dub(
+            "v = FFVideoSource(\"a.mkv\")\nAudioDub(v, a)\n"
+        ));
+        assert!(uses_audio_dub("clip = audiodub (v, a)"));
+        assert!(!uses_audio_dub("# AudioDub(v, a)\nv.Trim(0, 100)\n"));
+        assert!(!uses_audio_dub("v.Trim(0, 100) # AudioDub(v, a)"));
+        assert!(!uses_audio_dub("MyAudioDubber(v)"));
+    }
+
+    #[test]
+    fn computed_slices_point_at_the_other_ways_to_give_cuts() {
+        let script = "cuts = [(0, 100), (200, 300)]\nclips = [src[a:n*2] for a, n in cuts]\n";
+        match parse_trims(script, None, false, false) {
+            Err(SplitError::InvalidTrim {
+                line: 2,
+                text,
+                message,
+            }) => {
+                assert_eq!(text, "src[a:n*2]");
+                assert!(message.contains("--cuts-file or --trim"), "{}", message);
+            }
+            other => panic!("expected an invalid trim on line 2, got {:?}", other),
+        }
+        // Alongside literal trims they are skipped with a warning instead
+        let script = "a = clip[0:100]\nb = src[a:n*2]\n";
+        assert_eq!(starts(script), vec![0]);
+        assert!(matches!(
+            parse_trims(script, None, false, true),
+            Err(SplitError::CheckFailed(_))
+        ));
+    }
+
+    #[test]
+    fn written_trims_read_back_as_the_same_ranges() {
+        let ranges = [(0, Some(1)), (10, Some(101)), (200, Some(201)), (400, None)];
+        let written = format_avs_trims(&ranges);
+        assert_eq!(
+            written,
+            "Trim(0, length=1) ++ \\\nTrim(10, 100) ++ \\\nTrim(200, 200) ++ \\\nTrim(400, 0)\n"
+        );
+        let read: Vec<_> = parse(&written)
+            .into_iter()
+            .map(|trim| (trim.start, if trim.to_end { None } else { Some(trim.end) }))
+            .collect();
+        assert_eq!(read, ranges);
+    }
+}